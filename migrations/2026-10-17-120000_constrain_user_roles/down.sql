@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_business_type_check;
@@ -0,0 +1,8 @@
+-- Normalise existing free-form values before enforcing the allowed set
+UPDATE users SET business_type = lower(trim(business_type)) WHERE business_type IS NOT NULL;
+UPDATE users SET business_type = NULL
+    WHERE business_type NOT IN ('supplier', 'buyer', 'both', 'admin');
+
+ALTER TABLE users
+    ADD CONSTRAINT users_business_type_check
+    CHECK (business_type IN ('supplier', 'buyer', 'both', 'admin'));
@@ -0,0 +1,3 @@
+-- Access tokens carry the version current when they were issued. Bumping it
+-- on a password reset invalidates every token issued before.
+ALTER TABLE users ADD COLUMN token_version INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,9 @@
+-- Deactivated accounts keep their row so orders and reviews stay intact
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE users ADD COLUMN anonymized_at TIMESTAMP;
+
+-- An anonymized account is always deactivated as well
+ALTER TABLE users ADD CONSTRAINT users_anonymized_deleted_check
+    CHECK (anonymized_at IS NULL OR deleted_at IS NOT NULL);
+
+CREATE INDEX idx_users_active ON users(id) WHERE deleted_at IS NULL;
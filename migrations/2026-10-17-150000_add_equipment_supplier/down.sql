@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_equipment_images_equipment;
+DROP INDEX IF EXISTS idx_equipment_supplier;
+
+ALTER TABLE equipment DROP COLUMN IF EXISTS supplier_id;
@@ -0,0 +1,5 @@
+-- The supplier who listed the equipment; NULL for listings that predate ownership
+ALTER TABLE equipment ADD COLUMN supplier_id INTEGER REFERENCES users(id);
+
+CREATE INDEX idx_equipment_supplier ON equipment(supplier_id);
+CREATE INDEX idx_equipment_images_equipment ON equipment_images(equipment_id);
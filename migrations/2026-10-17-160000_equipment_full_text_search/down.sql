@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_equipment_model_number_lower;
+DROP INDEX IF EXISTS idx_equipment_search_vector;
+
+ALTER TABLE equipment DROP COLUMN IF EXISTS search_vector;
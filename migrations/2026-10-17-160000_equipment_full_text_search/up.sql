@@ -0,0 +1,17 @@
+-- Weighted full-text document for equipment search, kept up to date by
+-- PostgreSQL itself. Model numbers and names weigh most, then the
+-- manufacturer, then the description.
+--
+-- The column is intentionally left out of src/schema.rs so that it is never
+-- part of `equipment`'s default selection; queries refer to it by name.
+ALTER TABLE equipment ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('english'::regconfig, coalesce(model_number, '')), 'A') ||
+    setweight(to_tsvector('english'::regconfig, coalesce(name, '')), 'A') ||
+    setweight(to_tsvector('english'::regconfig, coalesce(manufacturer, '')), 'B') ||
+    setweight(to_tsvector('english'::regconfig, coalesce(description, '')), 'C')
+) STORED;
+
+CREATE INDEX idx_equipment_search_vector ON equipment USING GIN (search_vector);
+
+-- Exact model number matches are found and boosted regardless of the text query
+CREATE INDEX idx_equipment_model_number_lower ON equipment (lower(model_number));
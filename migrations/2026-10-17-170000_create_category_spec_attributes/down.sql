@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_equipment_specifications;
+
+DROP TABLE IF EXISTS category_spec_attributes;
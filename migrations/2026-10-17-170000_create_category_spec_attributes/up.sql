@@ -0,0 +1,19 @@
+-- Specification schema of a category: the attributes listings in it may
+-- (or must) carry in `equipment.specifications`. Subcategories inherit the
+-- attributes of their ancestors.
+CREATE TABLE category_spec_attributes (
+    id SERIAL PRIMARY KEY,
+    category_id INTEGER NOT NULL REFERENCES equipment_categories(id) ON DELETE CASCADE,
+    name VARCHAR(64) NOT NULL,
+    data_type VARCHAR NOT NULL CHECK (data_type IN ('number', 'integer', 'text', 'boolean')),
+    unit VARCHAR(32),
+    required BOOLEAN NOT NULL DEFAULT FALSE,
+    -- JSON array of permitted values, NULL when any value of the type is allowed
+    allowed_values JSONB CHECK (allowed_values IS NULL OR jsonb_typeof(allowed_values) = 'array'),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (category_id, name)
+);
+
+-- Containment lookups used by specification equality filters
+CREATE INDEX idx_equipment_specifications ON equipment USING GIN (specifications jsonb_path_ops);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE orders DROP CONSTRAINT IF EXISTS orders_status_check;
+ALTER TABLE equipment DROP CONSTRAINT IF EXISTS equipment_condition_check;
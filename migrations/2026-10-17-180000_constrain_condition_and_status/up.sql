@@ -0,0 +1,12 @@
+-- Normalise casing and whitespace before enforcing the allowed sets. Any
+-- other unexpected value makes the migration fail rather than being guessed.
+UPDATE equipment SET condition = lower(trim(condition)) WHERE condition <> lower(trim(condition));
+UPDATE orders SET status = lower(trim(status)) WHERE status <> lower(trim(status));
+
+ALTER TABLE equipment
+    ADD CONSTRAINT equipment_condition_check
+    CHECK (condition IN ('new', 'used', 'refurbished'));
+
+ALTER TABLE orders
+    ADD CONSTRAINT orders_status_check
+    CHECK (status IN ('pending', 'confirmed', 'shipped', 'delivered', 'cancelled'));
@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_equipment_images_one_primary;
+
+ALTER TABLE equipment_images
+    ALTER COLUMN is_primary DROP NOT NULL;
+
+ALTER TABLE equipment_images
+    DROP COLUMN thumbnails,
+    DROP COLUMN height,
+    DROP COLUMN width,
+    DROP COLUMN byte_size,
+    DROP COLUMN content_type,
+    DROP COLUMN storage_key,
+    DROP COLUMN position;
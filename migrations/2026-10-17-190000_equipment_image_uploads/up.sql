@@ -0,0 +1,33 @@
+-- Uploaded images keep their storage key and metadata; rows that only hold
+-- an external `image_url` leave them NULL
+ALTER TABLE equipment_images
+    ADD COLUMN position INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN storage_key VARCHAR(255),
+    ADD COLUMN content_type VARCHAR(64),
+    ADD COLUMN byte_size INTEGER,
+    ADD COLUMN width INTEGER,
+    ADD COLUMN height INTEGER,
+    ADD COLUMN thumbnails JSONB NOT NULL DEFAULT '[]';
+
+-- Existing images: keep the oldest flagged image as primary, or the oldest
+-- image when none is flagged, and order the rest by age
+UPDATE equipment_images SET is_primary = false WHERE is_primary IS NULL;
+
+WITH ranked AS (
+    SELECT id,
+           row_number() OVER (PARTITION BY equipment_id ORDER BY is_primary DESC, id) AS rank,
+           row_number() OVER (PARTITION BY equipment_id ORDER BY id) - 1 AS position
+    FROM equipment_images
+)
+UPDATE equipment_images i
+SET is_primary = (r.rank = 1), position = r.position
+FROM ranked r
+WHERE i.id = r.id;
+
+ALTER TABLE equipment_images
+    ALTER COLUMN is_primary SET NOT NULL;
+
+-- At most one primary image per equipment; the data layer keeps it at
+-- exactly one whenever an equipment has images
+CREATE UNIQUE INDEX idx_equipment_images_one_primary
+    ON equipment_images(equipment_id) WHERE is_primary;
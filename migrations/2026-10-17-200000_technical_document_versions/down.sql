@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE technical_documents
+    DROP CONSTRAINT IF EXISTS technical_documents_revision_key,
+    DROP CONSTRAINT IF EXISTS technical_documents_revision_check,
+    DROP CONSTRAINT IF EXISTS technical_documents_type_check;
+
+ALTER TABLE technical_documents
+    DROP COLUMN uploaded_by,
+    DROP COLUMN file_name,
+    DROP COLUMN byte_size,
+    DROP COLUMN content_type,
+    DROP COLUMN storage_key,
+    DROP COLUMN revision,
+    DROP COLUMN title;
@@ -0,0 +1,44 @@
+-- Normalise casing and whitespace before enforcing the allowed types. Any
+-- other unexpected value makes the migration fail rather than being guessed.
+UPDATE technical_documents
+SET document_type = lower(trim(document_type))
+WHERE document_type <> lower(trim(document_type));
+
+ALTER TABLE technical_documents
+    ADD CONSTRAINT technical_documents_type_check
+    CHECK (document_type IN (
+        'manual', 'specification', 'certification', 'warranty',
+        'safety_data_sheet', 'service_bulletin', 'other'
+    ));
+
+-- Each row is one version of a document. A document is identified by its
+-- equipment, type and title; `revision` numbers its versions from 1, while
+-- `version` stays the publisher's own label, such as "Rev C"
+ALTER TABLE technical_documents
+    ADD COLUMN title VARCHAR(200),
+    ADD COLUMN revision INTEGER,
+    ADD COLUMN storage_key VARCHAR(255),
+    ADD COLUMN content_type VARCHAR(64),
+    ADD COLUMN byte_size INTEGER,
+    ADD COLUMN file_name VARCHAR(255),
+    ADD COLUMN uploaded_by INTEGER REFERENCES users(id);
+
+-- Existing rows become one document per equipment and type, versioned in
+-- the order they were added
+UPDATE technical_documents SET title = initcap(replace(document_type, '_', ' '));
+
+WITH ranked AS (
+    SELECT id,
+           row_number() OVER (PARTITION BY equipment_id, document_type ORDER BY created_at, id) AS revision
+    FROM technical_documents
+)
+UPDATE technical_documents d
+SET revision = r.revision
+FROM ranked r
+WHERE d.id = r.id;
+
+ALTER TABLE technical_documents
+    ALTER COLUMN title SET NOT NULL,
+    ALTER COLUMN revision SET NOT NULL,
+    ADD CONSTRAINT technical_documents_revision_check CHECK (revision > 0),
+    ADD CONSTRAINT technical_documents_revision_key UNIQUE (equipment_id, document_type, title, revision);
@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE equipment ADD COLUMN dimensions_cm VARCHAR;
+
+UPDATE equipment
+SET dimensions_cm = concat_ws('x', trim_scale(length_cm), trim_scale(width_cm), trim_scale(height_cm))
+WHERE length_cm IS NOT NULL;
+
+ALTER TABLE equipment
+    DROP CONSTRAINT IF EXISTS equipment_dimensions_positive_check,
+    DROP CONSTRAINT IF EXISTS equipment_dimensions_complete_check,
+    DROP COLUMN height_cm,
+    DROP COLUMN width_cm,
+    DROP COLUMN length_cm;
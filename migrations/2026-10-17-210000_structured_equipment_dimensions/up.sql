@@ -0,0 +1,48 @@
+-- `dimensions_cm` held free text in the form "length x width x height".
+-- Replace it with one numeric column per dimension so listings can be
+-- filtered by size.
+ALTER TABLE equipment
+    ADD COLUMN length_cm NUMERIC(10,2),
+    ADD COLUMN width_cm NUMERIC(10,2),
+    ADD COLUMN height_cm NUMERIC(10,2);
+
+-- Accepts "800x300x400", "800 X 300 X 400 cm", "12.5 × 3 × 4" and the like;
+-- blank values are simply dropped
+WITH parsed AS (
+    SELECT id,
+           regexp_match(
+               dimensions_cm,
+               '^\s*(\d+(?:\.\d+)?)\s*[x×*]\s*(\d+(?:\.\d+)?)\s*[x×*]\s*(\d+(?:\.\d+)?)\s*(?:cm)?\s*$',
+               'i'
+           ) AS parts
+    FROM equipment
+    WHERE trim(coalesce(dimensions_cm, '')) <> ''
+)
+UPDATE equipment e
+SET length_cm = round(p.parts[1]::numeric, 2),
+    width_cm = round(p.parts[2]::numeric, 2),
+    height_cm = round(p.parts[3]::numeric, 2)
+FROM parsed p
+WHERE e.id = p.id AND p.parts IS NOT NULL;
+
+-- Any other value makes the migration fail rather than being guessed or lost
+DO $$
+DECLARE
+    unparsed TEXT;
+BEGIN
+    SELECT string_agg(id::text, ', ' ORDER BY id) INTO unparsed
+    FROM equipment
+    WHERE trim(coalesce(dimensions_cm, '')) <> '' AND length_cm IS NULL;
+
+    IF unparsed IS NOT NULL THEN
+        RAISE EXCEPTION 'Unrecognised dimensions_cm on equipment %; fix them before migrating', unparsed;
+    END IF;
+END
+$$;
+
+ALTER TABLE equipment
+    DROP COLUMN dimensions_cm,
+    ADD CONSTRAINT equipment_dimensions_complete_check
+        CHECK ((length_cm IS NULL) = (width_cm IS NULL) AND (width_cm IS NULL) = (height_cm IS NULL)),
+    ADD CONSTRAINT equipment_dimensions_positive_check
+        CHECK (length_cm > 0 AND width_cm > 0 AND height_cm > 0);
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE equipment DROP CONSTRAINT IF EXISTS equipment_stock_level_check;
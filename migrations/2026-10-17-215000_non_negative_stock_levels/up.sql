@@ -0,0 +1,8 @@
+-- Orders decrement stock as they are placed; a listing can never have sold
+-- more than it had. Levels already below zero were oversold and have none
+-- left.
+UPDATE equipment SET stock_level = 0 WHERE stock_level < 0;
+
+ALTER TABLE equipment
+    ADD CONSTRAINT equipment_stock_level_check
+    CHECK (stock_level >= 0);
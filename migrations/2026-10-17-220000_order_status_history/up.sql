@@ -0,0 +1,24 @@
+-- Each status change of an order, in the order it happened. The first row
+-- of an order has no `from_status`: it records the order being placed.
+-- `changed_by` is empty when the change was not made by a user.
+CREATE TABLE order_status_history (
+    id SERIAL PRIMARY KEY,
+    order_id INTEGER NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    from_status VARCHAR,
+    to_status VARCHAR NOT NULL,
+    changed_by INTEGER REFERENCES users(id),
+    note TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT order_status_history_from_status_check
+        CHECK (from_status IN ('pending', 'confirmed', 'shipped', 'delivered', 'cancelled')),
+    CONSTRAINT order_status_history_to_status_check
+        CHECK (to_status IN ('pending', 'confirmed', 'shipped', 'delivered', 'cancelled'))
+);
+
+CREATE INDEX order_status_history_order_id_idx ON order_status_history (order_id, id);
+
+-- Existing orders get a single entry for their current status, since how
+-- they got there was not recorded
+INSERT INTO order_status_history (order_id, from_status, to_status, changed_by, note, created_at)
+SELECT id, NULL, status, NULL, 'Recorded when order history was introduced', updated_at
+FROM orders;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS cart_items;
+DROP TABLE IF EXISTS carts;
@@ -0,0 +1,28 @@
+-- One cart per user, kept on the server so it can be built up over several
+-- sessions and devices until it is checked out
+CREATE TABLE carts (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT carts_user_id_key UNIQUE (user_id)
+);
+
+-- Lines mirror order_items. The listing's price is recorded when a line is
+-- added so later price changes can be pointed out; checkout always charges
+-- the price current at that moment.
+CREATE TABLE cart_items (
+    id SERIAL PRIMARY KEY,
+    cart_id INTEGER NOT NULL REFERENCES carts(id) ON DELETE CASCADE,
+    equipment_id INTEGER NOT NULL REFERENCES equipment(id),
+    quantity INTEGER NOT NULL,
+    price_when_added NUMERIC(15,2) NOT NULL,
+    warranty_selected BOOLEAN,
+    special_requirements TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT cart_items_quantity_check CHECK (quantity > 0),
+    CONSTRAINT cart_items_equipment_key UNIQUE (cart_id, equipment_id)
+);
+
+CREATE INDEX cart_items_equipment_id_idx ON cart_items (equipment_id);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS refunds;
+DROP TABLE IF EXISTS order_cancellations;
+ALTER TABLE order_items DROP COLUMN IF EXISTS cancelled_at;
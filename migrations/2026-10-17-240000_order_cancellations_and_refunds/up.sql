@@ -0,0 +1,37 @@
+-- Lines can be cancelled on their own; a cancelled line's quantity has been
+-- returned to stock
+ALTER TABLE order_items ADD COLUMN cancelled_at TIMESTAMP;
+
+-- Each cancellation of a whole order (no `order_item_id`) or of one of its
+-- lines, with a reason from a fixed list for reporting
+CREATE TABLE order_cancellations (
+    id SERIAL PRIMARY KEY,
+    order_id INTEGER NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    order_item_id INTEGER REFERENCES order_items(id) ON DELETE CASCADE,
+    reason VARCHAR NOT NULL,
+    note TEXT,
+    cancelled_by INTEGER REFERENCES users(id),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT order_cancellations_reason_check
+        CHECK (reason IN (
+            'buyer_request', 'duplicate_order', 'pricing_error',
+            'out_of_stock', 'unable_to_fulfil', 'other'
+        ))
+);
+
+CREATE INDEX order_cancellations_order_id_idx ON order_cancellations (order_id);
+CREATE INDEX order_cancellations_created_at_idx ON order_cancellations (created_at);
+
+-- What is owed back to the buyer for a cancellation: the order prices of
+-- the lines it cancelled
+CREATE TABLE refunds (
+    id SERIAL PRIMARY KEY,
+    order_id INTEGER NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    cancellation_id INTEGER NOT NULL REFERENCES order_cancellations(id) ON DELETE CASCADE,
+    amount NUMERIC(15,2) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT refunds_cancellation_id_key UNIQUE (cancellation_id),
+    CONSTRAINT refunds_amount_check CHECK (amount > 0)
+);
+
+CREATE INDEX refunds_order_id_idx ON refunds (order_id);
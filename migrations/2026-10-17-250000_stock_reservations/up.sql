@@ -0,0 +1,17 @@
+-- Quantities held for a buyer while they check out. A hold counts against
+-- what others can buy until `expires_at`; expired holds are ignored and
+-- removed by a background task. Placing an order replaces the buyer's
+-- holds with the stock decrement.
+CREATE TABLE stock_reservations (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    equipment_id INTEGER NOT NULL REFERENCES equipment(id),
+    quantity INTEGER NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT stock_reservations_quantity_check CHECK (quantity > 0),
+    CONSTRAINT stock_reservations_user_equipment_key UNIQUE (user_id, equipment_id)
+);
+
+CREATE INDEX stock_reservations_equipment_id_idx ON stock_reservations (equipment_id, expires_at);
+CREATE INDEX stock_reservations_expires_at_idx ON stock_reservations (expires_at);
@@ -0,0 +1,155 @@
+//! Email verification and password reset flows.
+//!
+//! Tokens are 32 random bytes, hex encoded and delivered by email. Only their
+//! SHA-256 digest is persisted, and each token can be used once.
+
+use std::sync::Arc;
+use chrono::{Duration, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use log::{error, info};
+use crate::auth::password;
+use crate::db::{self, DbPool};
+use crate::errors::{codes, ServiceError};
+use crate::mailer::{Email, Mailer};
+use crate::models::User;
+
+pub const VERIFICATION_TOKEN_TTL_HOURS: i64 = 24;
+pub const RESET_TOKEN_TTL_HOURS: i64 = 1;
+
+/// Composes account emails and hands them to the configured `Mailer`
+#[derive(Clone)]
+pub struct AccountMailer {
+    mailer: Arc<dyn Mailer>,
+    base_url: String,
+}
+
+impl AccountMailer {
+    /// `base_url` is the public address links in emails point at
+    pub fn new(mailer: Arc<dyn Mailer>, base_url: impl Into<String>) -> Self {
+        Self {
+            mailer,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn send_verification(&self, user: &User, token: &str) -> Result<(), ServiceError> {
+        self.mailer.send(&Email {
+            to: user.email.clone(),
+            subject: "Verify your email address".into(),
+            body: format!(
+                "Hi {},\n\nConfirm your email address by opening the link below within {} hours:\n\n{}/verify-email?token={}\n",
+                user.username, VERIFICATION_TOKEN_TTL_HOURS, self.base_url, token
+            ),
+        })
+    }
+
+    fn send_password_reset(&self, user: &User, token: &str) -> Result<(), ServiceError> {
+        self.mailer.send(&Email {
+            to: user.email.clone(),
+            subject: "Reset your password".into(),
+            body: format!(
+                "Hi {},\n\nChoose a new password by opening the link below within {} hour(s):\n\n{}/reset-password?token={}\n\nIf you did not ask for this, you can ignore this email.\n",
+                user.username, RESET_TOKEN_TTL_HOURS, self.base_url, token
+            ),
+        })
+    }
+}
+
+/// Returns a fresh token and the digest to store for it
+pub fn generate_token() -> (String, String) {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let token = to_hex(&bytes);
+    let digest = hash_token(&token);
+    (token, digest)
+}
+
+pub fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Issues a verification token for the user and emails it
+pub fn send_email_verification(
+    pool: &DbPool,
+    mailer: &AccountMailer,
+    user: &User,
+) -> Result<(), ServiceError> {
+    if user.is_email_verified() {
+        return Err(ServiceError::Conflict("Email address is already verified".into())
+            .with_code(codes::EMAIL_ALREADY_VERIFIED));
+    }
+
+    let (token, digest) = generate_token();
+    let expires = Utc::now().naive_utc() + Duration::hours(VERIFICATION_TOKEN_TTL_HOURS);
+    db::account_tokens::create_email_verification_token(pool, user.id, &digest, expires)?;
+    mailer.send_verification(user, &token)?;
+
+    info!("Sent email verification to user {}", user.id);
+    Ok(())
+}
+
+pub fn confirm_email_verification(pool: &DbPool, token: &str) -> Result<User, ServiceError> {
+    let user = db::account_tokens::verify_email(pool, &hash_token(token))?;
+    info!("Verified email for user {}", user.id);
+    Ok(user)
+}
+
+/// Emails a reset token if an account exists for `email`. Succeeds either way
+/// so the endpoint cannot be used to discover registered addresses.
+pub fn request_password_reset(
+    pool: &DbPool,
+    mailer: &AccountMailer,
+    email: &str,
+) -> Result<(), ServiceError> {
+    let Some(user) = db::users::find_user_by_email(pool, email)? else {
+        info!("Password reset requested for unknown email");
+        return Ok(());
+    };
+
+    let (token, digest) = generate_token();
+    let expires = Utc::now().naive_utc() + Duration::hours(RESET_TOKEN_TTL_HOURS);
+    db::account_tokens::create_password_reset_token(pool, user.id, &digest, expires)?;
+    if let Err(e) = mailer.send_password_reset(&user, &token) {
+        // Reported to the log only; the response must look the same as for unknown emails
+        error!("Failed to send password reset email to user {}: {}", user.id, e);
+    }
+
+    Ok(())
+}
+
+/// Validates and hashes the new password, then consumes the reset token
+pub fn confirm_password_reset(
+    pool: &DbPool,
+    token: &str,
+    new_password: &str,
+) -> Result<User, ServiceError> {
+    let digest = hash_token(token);
+
+    // Look the owner up first so the password can be checked against their username
+    let owner = db::account_tokens::find_password_reset_user(pool, &digest)?;
+    password::validate_password_strength(new_password, &owner.username)?;
+    let hash = password::hash_password(new_password)?;
+
+    let user = db::account_tokens::reset_password(pool, &digest, &hash)?;
+    info!("Reset password for user {}", user.id);
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_token() {
+        let (token, digest) = generate_token();
+        assert_eq!(token.len(), 64);
+        assert_eq!(digest, hash_token(&token));
+        assert_ne!(token, digest);
+        assert_ne!(generate_token().0, token);
+    }
+}
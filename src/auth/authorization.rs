@@ -0,0 +1,152 @@
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::pin::Pin;
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+use crate::auth::AuthenticatedUser;
+use crate::errors::ServiceError;
+use crate::models::User;
+
+/// A rule deciding which users may perform an action
+pub trait Policy {
+    /// Human-readable requirement, used in the 403 message
+    const REQUIREMENT: &'static str;
+
+    fn allows(user: &User) -> bool;
+}
+
+/// Listing and managing equipment: suppliers (and admins)
+pub struct CanSell;
+
+/// Buying: buyers
+pub struct CanBuy;
+
+/// Placing orders: buyers who have verified their email address
+pub struct VerifiedBuyer;
+
+/// Administrative operations
+pub struct AdminOnly;
+
+impl Policy for CanSell {
+    const REQUIREMENT: &'static str = "a supplier account";
+
+    fn allows(user: &User) -> bool {
+        user.business_type.is_some_and(|r| r.can_sell())
+    }
+}
+
+impl Policy for CanBuy {
+    const REQUIREMENT: &'static str = "a buyer account";
+
+    fn allows(user: &User) -> bool {
+        user.business_type.is_some_and(|r| r.can_buy())
+    }
+}
+
+impl Policy for VerifiedBuyer {
+    const REQUIREMENT: &'static str = "a buyer account with a verified email address";
+
+    fn allows(user: &User) -> bool {
+        CanBuy::allows(user) && user.is_email_verified()
+    }
+}
+
+impl Policy for AdminOnly {
+    const REQUIREMENT: &'static str = "an admin account";
+
+    fn allows(user: &User) -> bool {
+        user.business_type.is_some_and(|r| r.is_admin())
+    }
+}
+
+/// Declarative authorization guard. A handler taking `Authorized<CanSell>`
+/// only runs for authenticated users whose role satisfies the policy; other
+/// authenticated users get 403, anonymous requests get 401.
+pub struct Authorized<P: Policy> {
+    user: AuthenticatedUser,
+    _policy: PhantomData<P>,
+}
+
+impl<P: Policy> Authorized<P> {
+    pub fn check(user: AuthenticatedUser) -> Result<Self, ServiceError> {
+        if P::allows(&user) {
+            Ok(Self { user, _policy: PhantomData })
+        } else {
+            Err(ServiceError::Forbidden(format!(
+                "This action requires {}", P::REQUIREMENT
+            )))
+        }
+    }
+
+    pub fn into_inner(self) -> User {
+        self.user.into_inner()
+    }
+}
+
+impl<P: Policy> Deref for Authorized<P> {
+    type Target = User;
+
+    fn deref(&self) -> &User {
+        &self.user
+    }
+}
+
+impl<P: Policy + 'static> FromRequest for Authorized<P> {
+    type Error = ServiceError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let user = AuthenticatedUser::from_request(req, payload);
+        Box::pin(async move { Authorized::check(user.await?) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::models::UserRole;
+
+    fn user(role: Option<UserRole>, verified: bool) -> User {
+        let now = Utc::now().naive_utc();
+        User {
+            id: 1,
+            username: "operator".into(),
+            email: "operator@example.com".into(),
+            password_hash: String::new(),
+            company_name: None,
+            business_type: role,
+            contact_number: None,
+            created_at: now,
+            updated_at: now,
+            email_verified_at: verified.then_some(now),
+            deleted_at: None,
+            anonymized_at: None,
+            token_version: 0,
+        }
+    }
+
+    #[test]
+    fn test_policies() {
+        assert!(CanSell::allows(&user(Some(UserRole::Supplier), false)));
+        assert!(CanSell::allows(&user(Some(UserRole::Both), false)));
+        assert!(CanSell::allows(&user(Some(UserRole::Admin), false)));
+        assert!(!CanSell::allows(&user(Some(UserRole::Buyer), false)));
+        assert!(!CanSell::allows(&user(None, false)));
+
+        assert!(CanBuy::allows(&user(Some(UserRole::Buyer), false)));
+        assert!(CanBuy::allows(&user(Some(UserRole::Both), false)));
+        assert!(!CanBuy::allows(&user(Some(UserRole::Supplier), false)));
+        assert!(!CanBuy::allows(&user(None, false)));
+
+        assert!(AdminOnly::allows(&user(Some(UserRole::Admin), false)));
+        assert!(!AdminOnly::allows(&user(Some(UserRole::Both), false)));
+    }
+
+    #[test]
+    fn test_verified_buyer_requires_verified_email() {
+        assert!(VerifiedBuyer::allows(&user(Some(UserRole::Buyer), true)));
+        assert!(!VerifiedBuyer::allows(&user(Some(UserRole::Buyer), false)));
+        assert!(!VerifiedBuyer::allows(&user(Some(UserRole::Supplier), true)));
+    }
+}
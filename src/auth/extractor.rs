@@ -0,0 +1,91 @@
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpRequest};
+use log::error;
+use crate::auth::token::TokenConfig;
+use crate::db::{self, DbPool};
+use crate::errors::{codes, ServiceError};
+use crate::models::User;
+
+/// The user behind the request's `Authorization: Bearer <token>` header.
+///
+/// Taking this as a handler argument makes the endpoint require a valid,
+/// unexpired token issued since the user's last password reset; requests
+/// without one are rejected with 401.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser(pub User);
+
+impl AuthenticatedUser {
+    pub fn into_inner(self) -> User {
+        self.0
+    }
+}
+
+impl Deref for AuthenticatedUser {
+    type Target = User;
+
+    fn deref(&self) -> &User {
+        &self.0
+    }
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+    let value = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    if scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty() {
+        Some(token.trim().to_string())
+    } else {
+        None
+    }
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = ServiceError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = bearer_token(req);
+        let tokens = req.app_data::<web::Data<TokenConfig>>().cloned();
+        let pool = req.app_data::<web::Data<DbPool>>().cloned();
+
+        Box::pin(async move {
+            let (tokens, pool) = match (tokens, pool) {
+                (Some(tokens), Some(pool)) => (tokens, pool),
+                _ => {
+                    error!("AuthenticatedUser used without TokenConfig and DbPool app data");
+                    return Err(ServiceError::InternalServerError(
+                        "Authentication is not configured".into()
+                    ));
+                }
+            };
+
+            let token = token.ok_or_else(|| {
+                ServiceError::Unauthorized("Missing bearer token".into())
+                    .with_code(codes::AUTH_TOKEN_MISSING)
+            })?;
+            let claims = tokens.verify(&token)?;
+            let user_id = claims.user_id()?;
+
+            let user = web::block(move || db::users::get_user_by_id(&pool, user_id))
+                .await
+                .map_err(|e| ServiceError::InternalServerError(e.to_string()))?
+                .map_err(|e| match e.root() {
+                    // The account was removed after the token was issued
+                    ServiceError::NotFound(_) => {
+                        ServiceError::Unauthorized("Invalid or expired token".into())
+                            .with_code(codes::AUTH_TOKEN_INVALID)
+                    }
+                    _ => e,
+                })?;
+
+            // Issued before the password was last reset
+            if claims.ver != user.token_version {
+                return Err(ServiceError::Unauthorized("Invalid or expired token".into())
+                    .with_code(codes::AUTH_TOKEN_INVALID));
+            }
+
+            Ok(AuthenticatedUser(user))
+        })
+    }
+}
@@ -0,0 +1,51 @@
+pub mod account;
+pub mod authorization;
+pub mod extractor;
+pub mod password;
+pub mod token;
+
+pub use account::AccountMailer;
+pub use authorization::{AdminOnly, Authorized, CanBuy, CanSell, Policy, VerifiedBuyer};
+pub use extractor::AuthenticatedUser;
+pub use token::TokenConfig;
+
+use log::{info, warn};
+use crate::db::{self, DbPool};
+use crate::errors::{codes, ServiceError};
+use crate::models::User;
+
+/// Verifies an email/password pair and returns the matching user.
+///
+/// Unknown emails cost the same Argon2 work as wrong passwords, so response
+/// times do not reveal which emails are registered. When the stored hash was
+/// produced with outdated parameters it is replaced with a fresh hash of the
+/// (now known) plaintext password.
+pub fn authenticate(pool: &DbPool, email: &str, plaintext: &str) -> Result<User, ServiceError> {
+    let invalid = || {
+        ServiceError::Unauthorized("Invalid email or password".into())
+            .with_code(codes::AUTH_INVALID_CREDENTIALS)
+    };
+
+    let Some(user) = db::users::find_user_by_email(pool, email)? else {
+        password::verify_password(plaintext, password::DUMMY_HASH);
+        return Err(invalid());
+    };
+    if !password::verify_password(plaintext, &user.password_hash) {
+        return Err(invalid());
+    }
+
+    if password::needs_rehash(&user.password_hash) {
+        match password::hash_password(plaintext)
+            .and_then(|hash| db::users::update_password_hash(pool, user.id, &hash))
+        {
+            Ok(updated) => {
+                info!("Upgraded password hash for user {}", user.id);
+                return Ok(updated);
+            }
+            // A failed upgrade must not block the login; it will be retried next time
+            Err(e) => warn!("Failed to upgrade password hash for user {}: {}", user.id, e),
+        }
+    }
+
+    Ok(user)
+}
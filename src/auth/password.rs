@@ -0,0 +1,153 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::rngs::OsRng;
+use log::error;
+use crate::errors::ServiceError;
+
+// Argon2id parameters (OWASP baseline). Changing any of these causes existing
+// hashes to be upgraded the next time their owner logs in.
+pub const MEMORY_COST_KIB: u32 = 19_456;
+pub const TIME_COST: u32 = 2;
+pub const PARALLELISM: u32 = 1;
+
+/// Hash of a password no account has, verified against when a login names an
+/// unknown email so that it takes as long as one with a wrong password. Must
+/// use the parameters above; a test checks it does.
+pub const DUMMY_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$SLWvXlXqvkApUV1yfgZKTw$LDhkXTov2O2r1UzsLWIFFly6wt/vaX7co1V4Lg+uKDo";
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+
+fn hasher() -> Argon2<'static> {
+    let params = Params::new(MEMORY_COST_KIB, TIME_COST, PARALLELISM, None)
+        .expect("Argon2 parameters are valid");
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+}
+
+/// Hashes a plaintext password with Argon2id and a fresh random salt,
+/// returning the PHC string to store in `users.password_hash`
+pub fn hash_password(password: &str) -> Result<String, ServiceError> {
+    let salt = SaltString::generate(&mut OsRng);
+    hasher()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| {
+            error!("Failed to hash password: {}", e);
+            ServiceError::InternalServerError("Failed to hash password".into())
+        })
+}
+
+/// Checks a plaintext password against a stored PHC hash. Hashes that cannot
+/// be parsed never match.
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed) => hasher().verify_password(password.as_bytes(), &parsed).is_ok(),
+        Err(e) => {
+            error!("Stored password hash could not be parsed: {}", e);
+            false
+        }
+    }
+}
+
+/// Returns true when a stored hash was produced with a different algorithm,
+/// version or cost parameters than the ones currently configured
+pub fn needs_rehash(password_hash: &str) -> bool {
+    let parsed = match PasswordHash::new(password_hash) {
+        Ok(parsed) => parsed,
+        Err(_) => return true,
+    };
+
+    if parsed.algorithm != Algorithm::Argon2id.ident()
+        || parsed.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+
+    match Params::try_from(&parsed) {
+        Ok(params) => {
+            params.m_cost() != MEMORY_COST_KIB
+                || params.t_cost() != TIME_COST
+                || params.p_cost() != PARALLELISM
+        }
+        Err(_) => true,
+    }
+}
+
+/// Checks a new or changed password against the password policy and
+/// describes the first rule it breaks
+pub fn strength_problem(password: &str, username: &str) -> Option<String> {
+    let length = password.chars().count();
+    if length < MIN_PASSWORD_LENGTH {
+        return Some(format!("Password must be at least {} characters", MIN_PASSWORD_LENGTH));
+    }
+    if length > MAX_PASSWORD_LENGTH {
+        return Some(format!("Password must be at most {} characters", MAX_PASSWORD_LENGTH));
+    }
+    if !password.chars().any(char::is_alphabetic) || !password.chars().any(|c| c.is_ascii_digit()) {
+        return Some("Password must contain at least one letter and one digit".into());
+    }
+    if !username.is_empty() && password.to_lowercase().contains(&username.to_lowercase()) {
+        return Some("Password must not contain the username".into());
+    }
+    None
+}
+
+/// Enforces the password policy for new and changed passwords
+pub fn validate_password_strength(password: &str, username: &str) -> Result<(), ServiceError> {
+    match strength_problem(password, username) {
+        Some(reason) => Err(ServiceError::invalid_field("password", reason)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_and_verify() {
+        let hash = hash_password("correct horse 42").expect("Hashing should succeed");
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("correct horse 42", &hash));
+        assert!(!verify_password("wrong horse 42", &hash));
+        assert!(!needs_rehash(&hash));
+    }
+
+    #[test]
+    fn test_salts_are_unique() {
+        let first = hash_password("correct horse 42").unwrap();
+        let second = hash_password("correct horse 42").unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_needs_rehash_on_parameter_change() {
+        let weaker = Argon2::new(
+            Algorithm::Argon2id,
+            Version::V0x13,
+            Params::new(MEMORY_COST_KIB / 2, 1, PARALLELISM, None).unwrap(),
+        );
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = weaker.hash_password(b"correct horse 42", &salt).unwrap().to_string();
+
+        assert!(verify_password("correct horse 42", &hash));
+        assert!(needs_rehash(&hash));
+        assert!(needs_rehash("not-a-phc-string"));
+    }
+
+    #[test]
+    fn test_dummy_hash_matches_current_parameters() {
+        assert!(!needs_rehash(DUMMY_HASH));
+        assert!(!verify_password("correct horse 42", DUMMY_HASH));
+    }
+
+    #[test]
+    fn test_password_strength_rules() {
+        assert!(validate_password_strength("short1", "miner").is_err());
+        assert!(validate_password_strength("onlyletters", "miner").is_err());
+        assert!(validate_password_strength("1234567890", "miner").is_err());
+        assert!(validate_password_strength("theminer2024", "miner").is_err());
+        assert!(validate_password_strength(&"a1".repeat(65), "miner").is_err());
+        assert!(validate_password_strength("haul truck 797", "miner").is_ok());
+    }
+}
@@ -0,0 +1,134 @@
+use chrono::Utc;
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use crate::errors::{codes, ServiceError};
+
+/// Minimum HMAC key length in bytes; shorter secrets are rejected at startup
+pub const MIN_SECRET_LENGTH: usize = 32;
+
+/// Claims carried by an access token
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Claims {
+    /// User id, as a string per RFC 7519
+    pub sub: String,
+    pub iat: i64,
+    pub exp: i64,
+    /// The user's token version at issue. Tokens from before versions
+    /// existed have none and count as version 0.
+    #[serde(default)]
+    pub ver: i32,
+}
+
+fn invalid_token() -> ServiceError {
+    ServiceError::Unauthorized("Invalid or expired token".into()).with_code(codes::AUTH_TOKEN_INVALID)
+}
+
+impl Claims {
+    pub fn user_id(&self) -> Result<i32, ServiceError> {
+        self.sub
+            .parse()
+            .map_err(|_| invalid_token())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccessToken {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+}
+
+/// Signs and verifies HS256 access tokens. Registered as app data so the
+/// login handler and the `AuthenticatedUser` extractor share one key.
+#[derive(Clone)]
+pub struct TokenConfig {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    ttl_secs: i64,
+}
+
+impl TokenConfig {
+    pub fn new(secret: &[u8], ttl_secs: i64) -> Self {
+        Self {
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            ttl_secs,
+        }
+    }
+
+    pub fn issue(&self, user_id: i32, token_version: i32) -> Result<AccessToken, ServiceError> {
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            sub: user_id.to_string(),
+            iat: now,
+            exp: now + self.ttl_secs,
+            ver: token_version,
+        };
+        self.sign(&claims).map(|access_token| AccessToken {
+            access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: self.ttl_secs,
+        })
+    }
+
+    pub fn sign(&self, claims: &Claims) -> Result<String, ServiceError> {
+        encode(&Header::new(Algorithm::HS256), claims, &self.encoding_key).map_err(|e| {
+            error!("Failed to sign access token: {}", e);
+            ServiceError::InternalServerError("Failed to issue access token".into())
+        })
+    }
+
+    /// Checks the signature and expiry of a token and returns its claims
+    pub fn verify(&self, token: &str) -> Result<Claims, ServiceError> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+        validation.set_required_spec_claims(&["exp", "sub"]);
+
+        decode::<Claims>(token, &self.decoding_key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| {
+                debug!("Rejected access token: {}", e);
+                invalid_token()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"an-hmac-secret-that-is-long-enough!!";
+
+    #[test]
+    fn test_issue_and_verify() {
+        let tokens = TokenConfig::new(SECRET, 3600);
+        let issued = tokens.issue(42, 0).expect("Token should be issued");
+        assert_eq!(issued.token_type, "Bearer");
+
+        let claims = tokens.verify(&issued.access_token).expect("Token should verify");
+        assert_eq!(claims.user_id().unwrap(), 42);
+    }
+
+    #[test]
+    fn test_rejects_expired_token() {
+        let tokens = TokenConfig::new(SECRET, 3600);
+        let now = Utc::now().timestamp();
+        let expired = tokens
+            .sign(&Claims { sub: "42".into(), iat: now - 7200, exp: now - 3600, ver: 0 })
+            .unwrap();
+        let error = tokens.verify(&expired).unwrap_err();
+        assert!(matches!(error.root(), ServiceError::Unauthorized(_)));
+        assert_eq!(error.code(), codes::AUTH_TOKEN_INVALID);
+    }
+
+    #[test]
+    fn test_rejects_token_signed_with_other_key() {
+        let issued = TokenConfig::new(b"another-hmac-secret-that-is-long-enough", 3600)
+            .issue(42, 0)
+            .unwrap();
+        let tokens = TokenConfig::new(SECRET, 3600);
+        let error = tokens.verify(&issued.access_token).unwrap_err();
+        assert!(matches!(error.root(), ServiceError::Unauthorized(_)));
+    }
+}
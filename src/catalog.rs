@@ -0,0 +1,331 @@
+//! CSV representation of the equipment catalog, shared by bulk import and
+//! export. An exported file can be edited in a spreadsheet and imported
+//! again; the `id` column is informational and ignored on import, where rows
+//! are matched to existing listings by manufacturer and model number.
+
+use std::str::FromStr;
+use bigdecimal::BigDecimal;
+use serde::Serialize;
+use crate::errors::{codes, ServiceError};
+use crate::models::{Equipment, EquipmentCondition, NewEquipment};
+
+/// Largest number of data rows accepted in one import
+pub const MAX_IMPORT_ROWS: usize = 5_000;
+/// Largest CSV body accepted by the import endpoint
+pub const MAX_IMPORT_BYTES: usize = 10 * 1024 * 1024;
+
+/// Columns every import must have
+pub const REQUIRED_COLUMNS: [&str; 7] = [
+    "category_id", "name", "manufacturer", "model_number", "condition", "price", "stock_level",
+];
+
+/// Export column order. Imports may use any subset containing the required
+/// columns, in any order; updated listings keep their values for the
+/// columns a file leaves out.
+pub const COLUMNS: [&str; 18] = [
+    "id", "category_id", "name", "description", "manufacturer", "model_number",
+    "year_manufactured", "condition", "price", "stock_level", "specifications",
+    "weight_kg", "length_cm", "width_cm", "height_cm",
+    "power_requirements", "certification_info", "warranty_info",
+];
+
+/// One exported catalog row; `specifications` holds a JSON object
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogRow {
+    pub id: i32,
+    pub category_id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub manufacturer: String,
+    pub model_number: String,
+    pub year_manufactured: Option<i32>,
+    pub condition: EquipmentCondition,
+    pub price: BigDecimal,
+    pub stock_level: i32,
+    pub specifications: Option<String>,
+    pub weight_kg: Option<BigDecimal>,
+    pub length_cm: Option<BigDecimal>,
+    pub width_cm: Option<BigDecimal>,
+    pub height_cm: Option<BigDecimal>,
+    pub power_requirements: Option<String>,
+    pub certification_info: Option<String>,
+    pub warranty_info: Option<String>,
+}
+
+impl From<Equipment> for CatalogRow {
+    fn from(item: Equipment) -> Self {
+        Self {
+            id: item.id,
+            category_id: item.category_id,
+            name: item.name,
+            description: item.description,
+            manufacturer: item.manufacturer,
+            model_number: item.model_number,
+            year_manufactured: item.year_manufactured,
+            condition: item.condition,
+            price: item.price,
+            stock_level: item.stock_level,
+            specifications: item.specifications.map(|specs| specs.to_string()),
+            weight_kg: item.weight_kg,
+            length_cm: item.length_cm,
+            width_cm: item.width_cm,
+            height_cm: item.height_cm,
+            power_requirements: item.power_requirements,
+            certification_info: item.certification_info,
+            warranty_info: item.warranty_info,
+        }
+    }
+}
+
+/// A problem with one row of an import. `line` is the line number in the
+/// file, counting the header as line 1.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RowError {
+    pub line: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub reason: String,
+}
+
+impl RowError {
+    pub fn new(line: u64, field: Option<String>, reason: impl Into<String>) -> Self {
+        Self { line, field, reason: reason.into() }
+    }
+
+    /// One row error per field of a validation error, or a single one for
+    /// any other error
+    pub fn from_service_error(line: u64, error: &ServiceError) -> Vec<Self> {
+        match error.details() {
+            [] => vec![Self::new(line, None, error.public_message())],
+            details => details
+                .iter()
+                .map(|detail| Self::new(line, Some(detail.field.clone()), detail.reason.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// A row read from an import file: the listing it describes, or why it
+/// could not be read
+#[derive(Debug)]
+pub struct ParsedRow {
+    pub line: u64,
+    pub item: Result<NewEquipment, Vec<RowError>>,
+}
+
+/// A parsed import file: the columns it has and its rows
+#[derive(Debug)]
+pub struct ImportFile {
+    pub columns: Vec<String>,
+    pub rows: Vec<ParsedRow>,
+}
+
+impl ImportFile {
+    fn has(&self, column: &str) -> bool {
+        self.columns.iter().any(|header| header == column)
+    }
+
+    /// The listing a row describes when it updates `existing`. Columns the
+    /// file does not have keep their current values rather than being
+    /// cleared; the required ones are always there.
+    pub fn merge(&self, item: NewEquipment, existing: Equipment) -> NewEquipment {
+        fn pick<T>(present: bool, imported: T, current: T) -> T {
+            if present { imported } else { current }
+        }
+        NewEquipment {
+            description: pick(self.has("description"), item.description, existing.description),
+            year_manufactured: pick(self.has("year_manufactured"), item.year_manufactured, existing.year_manufactured),
+            specifications: pick(self.has("specifications"), item.specifications, existing.specifications),
+            weight_kg: pick(self.has("weight_kg"), item.weight_kg, existing.weight_kg),
+            length_cm: pick(self.has("length_cm"), item.length_cm, existing.length_cm),
+            width_cm: pick(self.has("width_cm"), item.width_cm, existing.width_cm),
+            height_cm: pick(self.has("height_cm"), item.height_cm, existing.height_cm),
+            power_requirements: pick(self.has("power_requirements"), item.power_requirements, existing.power_requirements),
+            certification_info: pick(self.has("certification_info"), item.certification_info, existing.certification_info),
+            warranty_info: pick(self.has("warranty_info"), item.warranty_info, existing.warranty_info),
+            ..item
+        }
+    }
+}
+
+/// The cells of one import row, looked up by column name. Cells are read
+/// one at a time so each problem is reported against its column.
+struct Cells<'a> {
+    line: u64,
+    headers: &'a csv::StringRecord,
+    record: &'a csv::StringRecord,
+    errors: Vec<RowError>,
+}
+
+impl<'a> Cells<'a> {
+    /// The cell's text; empty and missing cells are absent
+    fn text(&self, column: &str) -> Option<&'a str> {
+        self.headers
+            .iter()
+            .position(|header| header == column)
+            .and_then(|index| self.record.get(index))
+            .filter(|cell| !cell.is_empty())
+    }
+
+    fn fail(&mut self, column: &str, reason: String) {
+        self.errors.push(RowError::new(self.line, Some(column.to_string()), reason));
+    }
+
+    fn optional<T: FromStr>(&mut self, column: &str) -> Option<T>
+    where
+        T::Err: std::fmt::Display,
+    {
+        let cell = self.text(column)?;
+        cell.parse()
+            .map_err(|e| self.fail(column, format!("Invalid {} '{}': {}", column, cell, e)))
+            .ok()
+    }
+
+    fn required<T: FromStr>(&mut self, column: &str) -> Option<T>
+    where
+        T::Err: std::fmt::Display,
+    {
+        if self.text(column).is_none() {
+            self.fail(column, format!("{} is required", column));
+            return None;
+        }
+        self.optional(column)
+    }
+
+    fn optional_string(&self, column: &str) -> Option<String> {
+        self.text(column).map(String::from)
+    }
+
+    /// Required text columns are left empty when blank so validation
+    /// reports them with its usual message
+    fn string(&self, column: &str) -> String {
+        self.optional_string(column).unwrap_or_default()
+    }
+
+    fn json(&mut self, column: &str) -> Option<serde_json::Value> {
+        let cell = self.text(column)?;
+        serde_json::from_str(cell)
+            .map_err(|e| self.fail(column, format!("{} must be valid JSON: {}", column, e)))
+            .ok()
+    }
+}
+
+fn read_row(
+    line: u64,
+    headers: &csv::StringRecord,
+    record: &csv::StringRecord,
+    supplier_id: i32,
+) -> Result<NewEquipment, Vec<RowError>> {
+    let mut cells = Cells { line, headers, record, errors: Vec::new() };
+    let category_id = cells.required::<i32>("category_id");
+    let condition = cells.required::<EquipmentCondition>("condition");
+    let price = cells.required::<BigDecimal>("price");
+    let stock_level = cells.required::<i32>("stock_level");
+    let year_manufactured = cells.optional::<i32>("year_manufactured");
+    let weight_kg = cells.optional::<BigDecimal>("weight_kg");
+    let length_cm = cells.optional::<BigDecimal>("length_cm");
+    let width_cm = cells.optional::<BigDecimal>("width_cm");
+    let height_cm = cells.optional::<BigDecimal>("height_cm");
+    let specifications = cells.json("specifications");
+
+    let (Some(category_id), Some(condition), Some(price), Some(stock_level), true) =
+        (category_id, condition, price, stock_level, cells.errors.is_empty())
+    else {
+        return Err(cells.errors);
+    };
+    let now = chrono::Utc::now().naive_utc();
+
+    Ok(NewEquipment {
+        category_id,
+        name: cells.string("name"),
+        description: cells.optional_string("description"),
+        manufacturer: cells.string("manufacturer"),
+        model_number: cells.string("model_number"),
+        year_manufactured,
+        condition,
+        price,
+        stock_level,
+        specifications,
+        weight_kg,
+        power_requirements: cells.optional_string("power_requirements"),
+        certification_info: cells.optional_string("certification_info"),
+        warranty_info: cells.optional_string("warranty_info"),
+        created_at: now,
+        updated_at: now,
+        supplier_id: Some(supplier_id),
+        length_cm,
+        width_cm,
+        height_cm,
+    })
+}
+
+fn invalid_csv(reason: impl Into<String>) -> ServiceError {
+    ServiceError::BadRequest(reason.into()).with_code(codes::CSV_INVALID)
+}
+
+/// Reads an import file. Problems with the file as a whole, such as missing
+/// or unknown columns, fail the import; problems with single rows are
+/// returned alongside the other rows.
+pub fn parse_import(data: &[u8], supplier_id: i32) -> Result<ImportFile, ServiceError> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(data);
+
+    let headers = reader
+        .headers()
+        .map_err(|e| invalid_csv(format!("Unreadable CSV header: {}", e)))?
+        .clone();
+    if let Some(unknown) = headers.iter().find(|header| !COLUMNS.contains(header)) {
+        return Err(invalid_csv(format!("Unknown column '{}'", unknown)));
+    }
+    let missing: Vec<&str> = REQUIRED_COLUMNS
+        .into_iter()
+        .filter(|column| !headers.iter().any(|header| header == *column))
+        .collect();
+    if !missing.is_empty() {
+        return Err(invalid_csv(format!("Missing required columns: {}", missing.join(", "))));
+    }
+
+    let mut rows = Vec::new();
+    for (index, result) in reader.records().enumerate() {
+        if index == MAX_IMPORT_ROWS {
+            return Err(invalid_csv(format!("Imports are limited to {} rows", MAX_IMPORT_ROWS)));
+        }
+        // Positions are exact even when quoted cells span several lines
+        let fallback_line = index as u64 + 2;
+
+        let (line, item) = match result {
+            Ok(record) => {
+                let line = record.position().map_or(fallback_line, |pos| pos.line());
+                (line, read_row(line, &headers, &record, supplier_id))
+            }
+            Err(error) => {
+                let line = error.position().map_or(fallback_line, |pos| pos.line());
+                let reason = match error.kind() {
+                    csv::ErrorKind::UnequalLengths { expected_len, len, .. } => {
+                        format!("Expected {} cells but found {}", expected_len, len)
+                    }
+                    _ => error.to_string(),
+                };
+                (line, Err(vec![RowError::new(line, None, reason)]))
+            }
+        };
+        rows.push(ParsedRow { line, item });
+    }
+
+    Ok(ImportFile { columns: headers.iter().map(String::from).collect(), rows })
+}
+
+/// Serializes rows as CSV, with the header row first when `with_header` is set
+pub fn write_rows(rows: impl IntoIterator<Item = CatalogRow>, with_header: bool) -> Result<Vec<u8>, ServiceError> {
+    let write_error = |e: csv::Error| ServiceError::InternalServerError(format!("Failed to write CSV: {}", e));
+    // The header is written explicitly so it is there even without rows
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+    if with_header {
+        writer.write_record(COLUMNS).map_err(write_error)?;
+    }
+    for row in rows {
+        writer.serialize(row).map_err(write_error)?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| ServiceError::InternalServerError(format!("Failed to write CSV: {}", e)))
+}
@@ -0,0 +1,181 @@
+use std::env;
+use thiserror::Error;
+use crate::auth::token::{TokenConfig, MIN_SECRET_LENGTH};
+use crate::reservations::ReservationConfig;
+
+const DEFAULT_HOST: &str = "0.0.0.0";
+const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_TOKEN_TTL_SECS: i64 = 3600;
+const DEFAULT_MAIL_OUTBOX_DIR: &str = "outbox";
+const DEFAULT_PUBLIC_BASE_URL: &str = "http://localhost:8080";
+const DEFAULT_MEDIA_DIR: &str = "media";
+const DEFAULT_RESERVATION_TTL_SECS: i64 = 900;
+const DEFAULT_RESERVATION_SWEEP_SECS: u64 = 60;
+/// Path uploaded media is served under
+pub const MEDIA_PATH: &str = "/media";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Missing environment variable: {0}")]
+    Missing(String),
+    #[error("Invalid value for {0}: {1}")]
+    Invalid(String, String),
+}
+
+/// Application settings read from the environment at startup
+#[derive(Clone, Debug)]
+pub struct AppConfig {
+    pub host: String,
+    pub port: u16,
+    pub database_url: String,
+    pub shutdown_timeout_secs: u64,
+    pub jwt_secret: String,
+    pub token_ttl_secs: i64,
+    /// Directory the outbox mailer writes messages to
+    pub mail_outbox_dir: String,
+    /// Public address of the service, used for links in emails
+    pub public_base_url: String,
+    /// Directory uploaded media is stored in and served from
+    pub media_dir: String,
+    /// How long stock stays held for a buyer checking out
+    pub reservation_ttl_secs: i64,
+    /// How often expired holds are removed
+    pub reservation_sweep_secs: u64,
+}
+
+impl AppConfig {
+    /// Builds the configuration from `HOST`, `PORT`, `DATABASE_URL`,
+    /// `SHUTDOWN_TIMEOUT_SECS`, `JWT_SECRET`, `TOKEN_TTL_SECS`,
+    /// `MAIL_OUTBOX_DIR`, `PUBLIC_BASE_URL`, `MEDIA_DIR`,
+    /// `RESERVATION_TTL_SECS` and `RESERVATION_SWEEP_SECS`, falling back to
+    /// defaults where sensible
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let database_url = env::var("DATABASE_URL")
+            .map_err(|_| ConfigError::Missing("DATABASE_URL".to_string()))?;
+
+        let jwt_secret = env::var("JWT_SECRET")
+            .map_err(|_| ConfigError::Missing("JWT_SECRET".to_string()))?;
+        if jwt_secret.len() < MIN_SECRET_LENGTH {
+            return Err(ConfigError::Invalid(
+                "JWT_SECRET".to_string(),
+                format!("must be at least {} bytes", MIN_SECRET_LENGTH),
+            ));
+        }
+
+        Ok(Self {
+            host: env::var("HOST").unwrap_or_else(|_| DEFAULT_HOST.to_string()),
+            port: parse_var("PORT", DEFAULT_PORT)?,
+            database_url,
+            shutdown_timeout_secs: parse_var("SHUTDOWN_TIMEOUT_SECS", DEFAULT_SHUTDOWN_TIMEOUT_SECS)?,
+            jwt_secret,
+            token_ttl_secs: parse_var("TOKEN_TTL_SECS", DEFAULT_TOKEN_TTL_SECS)?,
+            mail_outbox_dir: env::var("MAIL_OUTBOX_DIR")
+                .unwrap_or_else(|_| DEFAULT_MAIL_OUTBOX_DIR.to_string()),
+            public_base_url: env::var("PUBLIC_BASE_URL")
+                .unwrap_or_else(|_| DEFAULT_PUBLIC_BASE_URL.to_string()),
+            media_dir: env::var("MEDIA_DIR").unwrap_or_else(|_| DEFAULT_MEDIA_DIR.to_string()),
+            reservation_ttl_secs: parse_var("RESERVATION_TTL_SECS", DEFAULT_RESERVATION_TTL_SECS)?,
+            reservation_sweep_secs: parse_var("RESERVATION_SWEEP_SECS", DEFAULT_RESERVATION_SWEEP_SECS)?,
+        })
+    }
+
+    pub fn bind_address(&self) -> (String, u16) {
+        (self.host.clone(), self.port)
+    }
+
+    /// Public URL prefix uploaded media is served under
+    pub fn media_base_url(&self) -> String {
+        format!("{}{}", self.public_base_url.trim_end_matches('/'), MEDIA_PATH)
+    }
+
+    pub fn token_config(&self) -> TokenConfig {
+        TokenConfig::new(self.jwt_secret.as_bytes(), self.token_ttl_secs)
+    }
+
+    pub fn reservation_config(&self) -> ReservationConfig {
+        ReservationConfig::new(self.reservation_ttl_secs)
+    }
+}
+
+fn parse_var<T: std::str::FromStr>(name: &str, default: T) -> Result<T, ConfigError> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| ConfigError::Invalid(name.to_string(), value)),
+        Err(_) => Ok(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "an-hmac-secret-that-is-long-enough!!";
+
+    #[test]
+    fn test_from_env_defaults() {
+        temp_env::with_vars(
+            [
+                ("DATABASE_URL", Some("postgres://localhost/rust_market")),
+                ("JWT_SECRET", Some(SECRET)),
+                ("HOST", None),
+                ("PORT", None),
+                ("SHUTDOWN_TIMEOUT_SECS", None),
+                ("TOKEN_TTL_SECS", None),
+                ("MAIL_OUTBOX_DIR", None),
+                ("PUBLIC_BASE_URL", None),
+                ("MEDIA_DIR", None),
+                ("RESERVATION_TTL_SECS", None),
+                ("RESERVATION_SWEEP_SECS", None),
+            ],
+            || {
+                let config = AppConfig::from_env().expect("Config should load");
+                assert_eq!(config.bind_address(), (DEFAULT_HOST.to_string(), DEFAULT_PORT));
+                assert_eq!(config.shutdown_timeout_secs, DEFAULT_SHUTDOWN_TIMEOUT_SECS);
+                assert_eq!(config.token_ttl_secs, DEFAULT_TOKEN_TTL_SECS);
+                assert_eq!(config.mail_outbox_dir, DEFAULT_MAIL_OUTBOX_DIR);
+                assert_eq!(config.public_base_url, DEFAULT_PUBLIC_BASE_URL);
+                assert_eq!(config.media_dir, DEFAULT_MEDIA_DIR);
+                assert_eq!(config.reservation_ttl_secs, DEFAULT_RESERVATION_TTL_SECS);
+                assert_eq!(config.reservation_sweep_secs, DEFAULT_RESERVATION_SWEEP_SECS);
+            },
+        );
+    }
+
+    #[test]
+    fn test_from_env_short_jwt_secret() {
+        temp_env::with_vars(
+            [
+                ("DATABASE_URL", Some("postgres://localhost/rust_market")),
+                ("JWT_SECRET", Some("too-short")),
+            ],
+            || {
+                let result = AppConfig::from_env();
+                assert!(matches!(result, Err(ConfigError::Invalid(name, _)) if name == "JWT_SECRET"));
+            },
+        );
+    }
+
+    #[test]
+    fn test_from_env_invalid_port() {
+        temp_env::with_vars(
+            [
+                ("DATABASE_URL", Some("postgres://localhost/rust_market")),
+                ("JWT_SECRET", Some(SECRET)),
+                ("PORT", Some("not-a-port")),
+            ],
+            || {
+                let result = AppConfig::from_env();
+                assert!(matches!(result, Err(ConfigError::Invalid(name, _)) if name == "PORT"));
+            },
+        );
+    }
+
+    #[test]
+    fn test_from_env_missing_database_url() {
+        temp_env::with_var("DATABASE_URL", None::<&str>, || {
+            assert!(matches!(AppConfig::from_env(), Err(ConfigError::Missing(_))));
+        });
+    }
+}
@@ -0,0 +1,177 @@
+//! Single-use tokens for email verification and password resets.
+//!
+//! Callers pass the SHA-256 digest of a token, never the token itself, so a
+//! leaked database cannot be used to take over accounts.
+
+use diesel::prelude::*;
+use chrono::{NaiveDateTime, Utc};
+use crate::errors::{codes, ServiceError};
+use crate::models::User;
+use log::error;
+
+fn invalid_token() -> ServiceError {
+    ServiceError::BadRequest("Invalid or expired token".into()).with_code(codes::ACCOUNT_TOKEN_INVALID)
+}
+
+fn database_error(context: &str, error: diesel::result::Error) -> ServiceError {
+    error!("{}: {:?}", context, error);
+    ServiceError::DatabaseError(error.to_string())
+}
+
+/// Stores a new verification token for the user, replacing any that are
+/// still outstanding
+pub fn create_email_verification_token(
+    pool: &crate::db::DbPool,
+    owner_id: i32,
+    digest: &str,
+    expires: NaiveDateTime,
+) -> Result<(), ServiceError> {
+    use crate::schema::email_verification_tokens::dsl::*;
+
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        diesel::delete(email_verification_tokens.filter(user_id.eq(owner_id)).filter(used_at.is_null()))
+            .execute(conn)?;
+        diesel::insert_into(email_verification_tokens)
+            .values((user_id.eq(owner_id), token_hash.eq(digest), expires_at.eq(expires)))
+            .execute(conn)
+    })
+    .map_err(|e| database_error("Failed to create email verification token", e))?;
+
+    Ok(())
+}
+
+/// Consumes a verification token and marks the owner's email as verified
+pub fn verify_email(pool: &crate::db::DbPool, digest: &str) -> Result<User, ServiceError> {
+    use crate::schema::email_verification_tokens::dsl::*;
+    use crate::schema::users;
+
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+    let now = Utc::now().naive_utc();
+
+    conn.transaction(|conn| {
+        let token: Option<(i32, i32)> = email_verification_tokens
+            .inner_join(users::table)
+            .filter(users::deleted_at.is_null())
+            .filter(token_hash.eq(digest))
+            .filter(used_at.is_null())
+            .filter(expires_at.gt(now))
+            .select((id, user_id))
+            .for_update()
+            .first(conn)
+            .optional()?;
+        let Some((token_id, owner_id)) = token else {
+            return Ok(None);
+        };
+
+        diesel::update(email_verification_tokens.find(token_id))
+            .set(used_at.eq(now))
+            .execute(conn)?;
+        diesel::update(users::table.find(owner_id))
+            .set((users::email_verified_at.eq(now), users::updated_at.eq(now)))
+            .get_result::<User>(conn)
+            .map(Some)
+    })
+    .map_err(|e| database_error("Failed to verify email", e))?
+    .ok_or_else(invalid_token)
+}
+
+/// Stores a new password reset token for the user, replacing any that are
+/// still outstanding
+pub fn create_password_reset_token(
+    pool: &crate::db::DbPool,
+    owner_id: i32,
+    digest: &str,
+    expires: NaiveDateTime,
+) -> Result<(), ServiceError> {
+    use crate::schema::password_reset_tokens::dsl::*;
+
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        diesel::delete(password_reset_tokens.filter(user_id.eq(owner_id)).filter(used_at.is_null()))
+            .execute(conn)?;
+        diesel::insert_into(password_reset_tokens)
+            .values((user_id.eq(owner_id), token_hash.eq(digest), expires_at.eq(expires)))
+            .execute(conn)
+    })
+    .map_err(|e| database_error("Failed to create password reset token", e))?;
+
+    Ok(())
+}
+
+/// The owner of a reset token that is still usable
+pub fn find_password_reset_user(pool: &crate::db::DbPool, digest: &str) -> Result<User, ServiceError> {
+    use crate::schema::password_reset_tokens::dsl::*;
+    use crate::schema::users;
+
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    password_reset_tokens
+        .inner_join(users::table)
+        .filter(users::deleted_at.is_null())
+        .filter(token_hash.eq(digest))
+        .filter(used_at.is_null())
+        .filter(expires_at.gt(Utc::now().naive_utc()))
+        .select(users::all_columns)
+        .first::<User>(conn)
+        .optional()
+        .map_err(|e| database_error("Failed to look up password reset token", e))?
+        .ok_or_else(invalid_token)
+}
+
+/// Consumes a reset token and replaces the owner's password hash. Other
+/// outstanding reset tokens for the same user are invalidated as well, and
+/// so are the access tokens issued before the reset.
+pub fn reset_password(
+    pool: &crate::db::DbPool,
+    digest: &str,
+    new_password_hash: &str,
+) -> Result<User, ServiceError> {
+    use crate::schema::password_reset_tokens::dsl::*;
+    use crate::schema::users;
+
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+    let now = Utc::now().naive_utc();
+
+    conn.transaction(|conn| {
+        let owner: Option<i32> = password_reset_tokens
+            .inner_join(users::table)
+            .filter(users::deleted_at.is_null())
+            .filter(token_hash.eq(digest))
+            .filter(used_at.is_null())
+            .filter(expires_at.gt(now))
+            .select(user_id)
+            .for_update()
+            .first(conn)
+            .optional()?;
+        let Some(owner_id) = owner else {
+            return Ok(None);
+        };
+
+        diesel::update(password_reset_tokens.filter(user_id.eq(owner_id)).filter(used_at.is_null()))
+            .set(used_at.eq(now))
+            .execute(conn)?;
+        diesel::update(users::table.find(owner_id))
+            .set((
+                users::password_hash.eq(new_password_hash),
+                users::token_version.eq(users::token_version + 1),
+                users::updated_at.eq(now),
+            ))
+            .get_result::<User>(conn)
+            .map(Some)
+    })
+    .map_err(|e| database_error("Failed to reset password", e))?
+    .ok_or_else(invalid_token)
+}
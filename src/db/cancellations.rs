@@ -0,0 +1,232 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use crate::db::db_error;
+use crate::db::orders::{ensure_transition, load_items, lock_order, record_status_change};
+use crate::errors::{codes, ServiceError};
+use crate::models::{
+    CancelOrderRequest, CancellationOutcome, CancellationReason, CancellationRecord, CancellationSummary,
+    NewOrderCancellation, NewOrderStatusChange, NewRefund, Order, OrderCancellation, OrderDetails, OrderItem,
+    OrderStatus, Refund,
+};
+use crate::schema::{equipment, order_cancellations, order_items, orders, refunds};
+
+/// Cancels the given line of an order, or every remaining line when
+/// `item_id` is `None`, in the caller's transaction. The lines' quantities
+/// go back to stock and the buyer is owed what they were ordered at. An
+/// order left without lines becomes cancelled. Orders that have shipped
+/// cannot be cancelled.
+pub(crate) fn cancel_lines(
+    conn: &mut PgConnection,
+    order_id: i32,
+    item_id: Option<i32>,
+    cancelled_by: i32,
+    request: &CancelOrderRequest,
+) -> Result<CancellationOutcome, ServiceError> {
+    let current = lock_order(conn, order_id)?;
+    ensure_transition(&current, OrderStatus::Cancelled)?;
+
+    let active: Vec<OrderItem> = load_items(conn, order_id)?
+        .into_iter()
+        .filter(|item| item.cancelled_at.is_none())
+        .collect();
+    let (cancelled, remaining): (Vec<OrderItem>, Vec<OrderItem>) = match item_id {
+        None => (active, Vec::new()),
+        Some(item_id) => {
+            if !active.iter().any(|item| item.id == item_id) {
+                return Err(missing_or_cancelled(conn, order_id, item_id)?);
+            }
+            active.into_iter().partition(|item| item.id == item_id)
+        }
+    };
+
+    // Locked in id order, as placing orders does, so the two cannot deadlock
+    let mut listing_ids: Vec<i32> = cancelled.iter().map(|item| item.equipment_id).collect();
+    listing_ids.sort_unstable();
+    equipment::table
+        .filter(equipment::id.eq_any(&listing_ids))
+        .order(equipment::id.asc())
+        .select(equipment::id)
+        .for_update()
+        .load::<i32>(conn)
+        .map_err(db_error("Failed to lock equipment"))?;
+
+    let now = chrono::Utc::now().naive_utc();
+    for item in &cancelled {
+        diesel::update(equipment::table.find(item.equipment_id))
+            .set(equipment::stock_level.eq(equipment::stock_level + item.quantity))
+            .execute(conn)
+            .map_err(db_error("Failed to restock equipment"))?;
+    }
+    let cancelled_ids: Vec<i32> = cancelled.iter().map(|item| item.id).collect();
+    diesel::update(order_items::table.filter(order_items::id.eq_any(&cancelled_ids)))
+        .set(order_items::cancelled_at.eq(now))
+        .execute(conn)
+        .map_err(db_error("Failed to cancel order items"))?;
+
+    let cancellation: OrderCancellation = diesel::insert_into(order_cancellations::table)
+        .values(&NewOrderCancellation {
+            order_id,
+            order_item_id: item_id,
+            reason: request.reason,
+            note: request.note.clone(),
+            cancelled_by: Some(cancelled_by),
+            created_at: now,
+        })
+        .get_result(conn)
+        .map_err(db_error("Failed to record cancellation"))?;
+
+    let amount = cancelled
+        .iter()
+        .map(|item| &item.price_at_time * BigDecimal::from(item.quantity))
+        .sum::<BigDecimal>()
+        .with_scale(2);
+    let refund: Option<Refund> = if amount > BigDecimal::from(0) {
+        let refund = diesel::insert_into(refunds::table)
+            .values(&NewRefund { order_id, cancellation_id: cancellation.id, amount, created_at: now })
+            .get_result(conn)
+            .map_err(db_error("Failed to record refund"))?;
+        Some(refund)
+    } else {
+        None
+    };
+
+    let update = diesel::update(orders::table.find(order_id));
+    let order: Order = if remaining.is_empty() {
+        let order = update
+            .set((orders::status.eq(OrderStatus::Cancelled), orders::updated_at.eq(now)))
+            .get_result(conn)
+            .map_err(db_error("Failed to cancel order"))?;
+        let note = match &request.note {
+            Some(note) => format!("{}: {}", request.reason, note),
+            None => request.reason.to_string(),
+        };
+        record_status_change(conn, &NewOrderStatusChange {
+            order_id,
+            from_status: Some(current.status),
+            to_status: OrderStatus::Cancelled,
+            changed_by: Some(cancelled_by),
+            note: Some(note),
+            created_at: now,
+        })?;
+        order
+    } else {
+        update
+            .set(orders::updated_at.eq(now))
+            .get_result(conn)
+            .map_err(db_error("Failed to update order"))?
+    };
+
+    let items = load_items(conn, order_id)?;
+    Ok(CancellationOutcome {
+        cancellation: CancellationRecord { cancellation, refund },
+        order: OrderDetails { order, items },
+    })
+}
+
+/// The error for a line that is not among the order's active lines
+fn missing_or_cancelled(conn: &mut PgConnection, order_id: i32, item_id: i32) -> Result<ServiceError, ServiceError> {
+    let cancelled: Option<bool> = order_items::table
+        .filter(order_items::id.eq(item_id))
+        .filter(order_items::order_id.eq(order_id))
+        .select(order_items::cancelled_at.is_not_null())
+        .first(conn)
+        .optional()
+        .map_err(db_error("Failed to load order item"))?;
+    Ok(match cancelled {
+        Some(_) => ServiceError::Conflict(format!("Order item {} is already cancelled", item_id))
+            .with_code(codes::ORDER_ITEM_CANCELLED),
+        None => ServiceError::NotFound(format!("Order item {} not found", item_id))
+            .with_code(codes::ORDER_ITEM_NOT_FOUND),
+    })
+}
+
+/// Cancels every remaining line of an order, and so the order itself
+pub fn cancel_order(
+    pool: &crate::db::DbPool,
+    order_id: i32,
+    cancelled_by: i32,
+    request: &CancelOrderRequest,
+) -> Result<CancellationOutcome, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| cancel_lines(conn, order_id, None, cancelled_by, request))
+}
+
+/// Cancels one line of an order; cancelling its last line cancels the order
+pub fn cancel_order_item(
+    pool: &crate::db::DbPool,
+    order_id: i32,
+    item_id: i32,
+    cancelled_by: i32,
+    request: &CancelOrderRequest,
+) -> Result<CancellationOutcome, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| cancel_lines(conn, order_id, Some(item_id), cancelled_by, request))
+}
+
+/// Cancellations of an order with their refunds, oldest first
+pub fn order_cancellations(
+    pool: &crate::db::DbPool,
+    order_id: i32,
+) -> Result<Vec<CancellationRecord>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    Ok(order_cancellations::table
+        .left_join(refunds::table)
+        .filter(order_cancellations::order_id.eq(order_id))
+        .order(order_cancellations::id.asc())
+        .load::<(OrderCancellation, Option<Refund>)>(conn)
+        .map_err(db_error("Failed to load cancellations"))?
+        .into_iter()
+        .map(|(cancellation, refund)| CancellationRecord { cancellation, refund })
+        .collect())
+}
+
+/// Number of cancellations and amount refunded per reason for
+/// cancellations made between `from` and `to`, both inclusive. Reasons
+/// without cancellations are left out.
+pub fn cancellation_summary(
+    pool: &crate::db::DbPool,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<Vec<CancellationSummary>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    let mut query = order_cancellations::table
+        .left_join(refunds::table)
+        .group_by(order_cancellations::reason)
+        .select((
+            order_cancellations::reason,
+            diesel::dsl::count(order_cancellations::id),
+            diesel::dsl::sum(refunds::amount.nullable()),
+        ))
+        .order(order_cancellations::reason.asc())
+        .into_boxed();
+    if let Some(from) = from {
+        query = query.filter(order_cancellations::created_at.ge(from.and_time(chrono::NaiveTime::MIN)));
+    }
+    if let Some(to) = to.and_then(|to| to.succ_opt()) {
+        query = query.filter(order_cancellations::created_at.lt(to.and_time(chrono::NaiveTime::MIN)));
+    }
+
+    Ok(query
+        .load::<(CancellationReason, i64, Option<BigDecimal>)>(conn)
+        .map_err(db_error("Failed to summarize cancellations"))?
+        .into_iter()
+        .map(|(reason, cancellations, refunded)| CancellationSummary {
+            reason,
+            cancellations,
+            refunded_amount: refunded.unwrap_or_else(|| BigDecimal::from(0)).with_scale(2),
+        })
+        .collect())
+}
@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use crate::db::db_error;
+use crate::db::orders::create_order;
+use crate::db::reservations::{active_holds, shortage};
+use crate::errors::{codes, ServiceError};
+use crate::models::{
+    AddCartItemRequest, Cart, CartItem, CartLine, CartView, CheckoutRequest, Equipment, NewCartItem, OrderDetails,
+    OrderLineRequest, PlaceOrderRequest, UpdateCartItem,
+};
+use crate::schema::{cart_items, carts, equipment, stock_reservations};
+use crate::validation::{Validate, MAX_ORDER_LINES};
+
+fn cart_item_not_found(item_id: i32) -> ServiceError {
+    ServiceError::NotFound(format!("Cart item {} not found", item_id))
+        .with_code(codes::CART_ITEM_NOT_FOUND)
+}
+
+/// Fails unless `requested` of the listing is available to the user,
+/// counting what other buyers hold
+fn ensure_available(
+    conn: &mut PgConnection,
+    user_id: i32,
+    listing: &Equipment,
+    requested: i32,
+) -> Result<(), ServiceError> {
+    let held = active_holds(conn, &[listing.id], Some(user_id))
+        .map_err(db_error("Failed to load stock reservations"))?;
+    match shortage(listing, held.get(&listing.id).copied().unwrap_or(0), requested) {
+        None => Ok(()),
+        Some(shortage) => Err(ServiceError::Conflict(format!("Insufficient stock: {}", shortage))
+            .with_code(codes::INSUFFICIENT_STOCK)),
+    }
+}
+
+/// The user's cart, locked for the rest of the transaction so changes to
+/// it are applied one at a time
+fn lock_cart(conn: &mut PgConnection, user_id: i32) -> Result<Option<Cart>, ServiceError> {
+    carts::table
+        .filter(carts::user_id.eq(user_id))
+        .for_update()
+        .first(conn)
+        .optional()
+        .map_err(db_error("Failed to lock cart"))
+}
+
+fn lock_or_create_cart(conn: &mut PgConnection, user_id: i32) -> Result<Cart, ServiceError> {
+    diesel::insert_into(carts::table)
+        .values(carts::user_id.eq(user_id))
+        .on_conflict(carts::user_id)
+        .do_nothing()
+        .execute(conn)
+        .map_err(db_error("Failed to create cart"))?;
+    lock_cart(conn, user_id)?.ok_or_else(|| ServiceError::DatabaseError("Cart vanished after creation".into()))
+}
+
+fn touch_cart(conn: &mut PgConnection, cart: &Cart) -> Result<(), ServiceError> {
+    diesel::update(carts::table.find(cart.id))
+        .set(carts::updated_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(conn)
+        .map(|_| ())
+        .map_err(db_error("Failed to update cart"))
+}
+
+/// A line of the user's cart, with the cart locked
+fn lock_item(conn: &mut PgConnection, user_id: i32, item_id: i32) -> Result<(Cart, CartItem), ServiceError> {
+    let cart = lock_cart(conn, user_id)?.ok_or_else(|| cart_item_not_found(item_id))?;
+    let item = cart_items::table
+        .filter(cart_items::id.eq(item_id))
+        .filter(cart_items::cart_id.eq(cart.id))
+        .first(conn)
+        .optional()
+        .map_err(db_error("Failed to load cart item"))?
+        .ok_or_else(|| cart_item_not_found(item_id))?;
+    Ok((cart, item))
+}
+
+fn load_items(conn: &mut PgConnection, cart: &Cart) -> Result<Vec<CartItem>, ServiceError> {
+    cart_items::table
+        .filter(cart_items::cart_id.eq(cart.id))
+        .order(cart_items::id.asc())
+        .load(conn)
+        .map_err(db_error("Failed to load cart items"))
+}
+
+/// The lines of the user's cart, with the cart locked; empty when the user
+/// has no cart
+pub(crate) fn lock_cart_items(conn: &mut PgConnection, user_id: i32) -> Result<Vec<CartItem>, ServiceError> {
+    match lock_cart(conn, user_id)? {
+        Some(cart) => load_items(conn, &cart),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The cart with every line checked against its listing's current price
+/// and stock
+fn cart_view(conn: &mut PgConnection, user_id: i32, cart: Option<&Cart>) -> Result<CartView, ServiceError> {
+    let rows: Vec<(CartItem, Equipment)> = match cart {
+        Some(cart) => cart_items::table
+            .inner_join(equipment::table)
+            .filter(cart_items::cart_id.eq(cart.id))
+            .order(cart_items::id.asc())
+            .load(conn)
+            .map_err(db_error("Failed to load cart items"))?,
+        None => Vec::new(),
+    };
+
+    let ids: Vec<i32> = rows.iter().map(|(item, _)| item.equipment_id).collect();
+    let held = active_holds(conn, &ids, Some(user_id)).map_err(db_error("Failed to load stock reservations"))?;
+    let own_holds: HashMap<i32, NaiveDateTime> = stock_reservations::table
+        .filter(stock_reservations::user_id.eq(user_id))
+        .filter(stock_reservations::equipment_id.eq_any(&ids))
+        .filter(stock_reservations::expires_at.gt(chrono::Utc::now().naive_utc()))
+        .select((stock_reservations::equipment_id, stock_reservations::expires_at))
+        .load::<(i32, NaiveDateTime)>(conn)
+        .map_err(db_error("Failed to load stock reservations"))?
+        .into_iter()
+        .collect();
+
+    let items: Vec<CartLine> = rows
+        .into_iter()
+        .map(|(item, listing)| CartLine {
+            line_total: (&listing.price * BigDecimal::from(item.quantity)).with_scale(2),
+            price_changed: item.price_when_added != listing.price,
+            available: shortage(&listing, held.get(&listing.id).copied().unwrap_or(0), item.quantity).is_none(),
+            reserved_until: own_holds.get(&listing.id).copied(),
+            equipment_name: listing.name,
+            unit_price: listing.price,
+            stock_level: listing.stock_level,
+            item,
+        })
+        .collect();
+    let total_amount = items.iter().map(|line| &line.line_total).sum::<BigDecimal>().with_scale(2);
+    let ready_for_checkout = !items.is_empty() && items.iter().all(|line| line.available);
+
+    Ok(CartView {
+        id: cart.map(|cart| cart.id),
+        user_id,
+        items,
+        total_amount,
+        ready_for_checkout,
+    })
+}
+
+/// The user's cart as it would be checked out now. Users without a cart
+/// get an empty one.
+pub fn get_cart(pool: &crate::db::DbPool, user_id: i32) -> Result<CartView, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    let cart: Option<Cart> = carts::table
+        .filter(carts::user_id.eq(user_id))
+        .first(conn)
+        .optional()
+        .map_err(db_error("Failed to load cart"))?;
+    cart_view(conn, user_id, cart.as_ref())
+}
+
+/// Adds a listing to the user's cart, creating the cart if needed. A
+/// listing already in the cart has the quantity added to its line, and the
+/// line's options and recorded price are replaced.
+pub fn add_item(
+    pool: &crate::db::DbPool,
+    user_id: i32,
+    request: &AddCartItemRequest,
+) -> Result<CartView, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        let cart = lock_or_create_cart(conn, user_id)?;
+        let listing: Equipment = equipment::table
+            .find(request.equipment_id)
+            .first(conn)
+            .optional()
+            .map_err(db_error("Failed to load equipment"))?
+            .ok_or_else(|| ServiceError::invalid_field(
+                "equipment_id",
+                format!("Equipment {} does not exist", request.equipment_id),
+            ))?;
+        let existing: Option<CartItem> = cart_items::table
+            .filter(cart_items::cart_id.eq(cart.id))
+            .filter(cart_items::equipment_id.eq(listing.id))
+            .first(conn)
+            .optional()
+            .map_err(db_error("Failed to load cart item"))?;
+
+        let quantity = existing
+            .as_ref()
+            .map_or(Some(request.quantity), |item| item.quantity.checked_add(request.quantity))
+            .unwrap_or(i32::MAX);
+        ensure_available(conn, user_id, &listing, quantity)?;
+
+        let now = chrono::Utc::now().naive_utc();
+        match existing {
+            Some(item) => diesel::update(cart_items::table.find(item.id))
+                .set((
+                    cart_items::quantity.eq(quantity),
+                    cart_items::price_when_added.eq(&listing.price),
+                    cart_items::warranty_selected.eq(request.warranty_selected),
+                    cart_items::special_requirements.eq(&request.special_requirements),
+                    cart_items::updated_at.eq(now),
+                ))
+                .execute(conn)
+                .map_err(db_error("Failed to update cart item"))?,
+            None => {
+                let lines: i64 = cart_items::table
+                    .filter(cart_items::cart_id.eq(cart.id))
+                    .count()
+                    .get_result(conn)
+                    .map_err(db_error("Failed to count cart items"))?;
+                if lines >= MAX_ORDER_LINES as i64 {
+                    return Err(ServiceError::Conflict(format!(
+                        "A cart can have at most {} items", MAX_ORDER_LINES
+                    ))
+                    .with_code(codes::CART_LIMIT_REACHED));
+                }
+                diesel::insert_into(cart_items::table)
+                    .values(&NewCartItem {
+                        cart_id: cart.id,
+                        equipment_id: listing.id,
+                        quantity,
+                        price_when_added: listing.price.clone(),
+                        warranty_selected: request.warranty_selected,
+                        special_requirements: request.special_requirements.clone(),
+                        created_at: now,
+                        updated_at: now,
+                    })
+                    .execute(conn)
+                    .map_err(db_error("Failed to add cart item"))?
+            }
+        };
+        touch_cart(conn, &cart)?;
+
+        cart_view(conn, user_id, Some(&cart))
+    })
+}
+
+/// Changes the quantity or options of a line of the user's cart
+pub fn update_item(
+    pool: &crate::db::DbPool,
+    user_id: i32,
+    item_id: i32,
+    update: UpdateCartItem,
+) -> Result<CartView, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        let (cart, item) = lock_item(conn, user_id, item_id)?;
+        if let Some(quantity) = update.quantity {
+            let listing: Equipment = equipment::table
+                .find(item.equipment_id)
+                .first(conn)
+                .map_err(db_error("Failed to load equipment"))?;
+            ensure_available(conn, user_id, &listing, quantity)?;
+        }
+
+        diesel::update(cart_items::table.find(item.id))
+            .set(update.into_changeset(chrono::Utc::now().naive_utc()))
+            .execute(conn)
+            .map_err(db_error("Failed to update cart item"))?;
+        touch_cart(conn, &cart)?;
+
+        cart_view(conn, user_id, Some(&cart))
+    })
+}
+
+/// Removes a line from the user's cart
+pub fn remove_item(pool: &crate::db::DbPool, user_id: i32, item_id: i32) -> Result<(), ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        let (cart, item) = lock_item(conn, user_id, item_id)?;
+        diesel::delete(cart_items::table.find(item.id))
+            .execute(conn)
+            .map_err(db_error("Failed to remove cart item"))?;
+        touch_cart(conn, &cart)
+    })
+}
+
+/// Removes every line from the user's cart
+pub fn clear_cart(pool: &crate::db::DbPool, user_id: i32) -> Result<(), ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        let Some(cart) = lock_cart(conn, user_id)? else {
+            return Ok(());
+        };
+        diesel::delete(cart_items::table.filter(cart_items::cart_id.eq(cart.id)))
+            .execute(conn)
+            .map_err(db_error("Failed to clear cart"))?;
+        touch_cart(conn, &cart)
+    })
+}
+
+/// Places an order for everything in the user's cart and empties it, in
+/// one transaction. The order is placed exactly as `POST /orders` would,
+/// at current prices; if `expected_total` is given and differs from the
+/// order's total, nothing is changed.
+pub fn checkout(
+    pool: &crate::db::DbPool,
+    user_id: i32,
+    request: &CheckoutRequest,
+) -> Result<OrderDetails, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        let cart = lock_cart(conn, user_id)?;
+        let items = match &cart {
+            Some(cart) => load_items(conn, cart)?,
+            None => Vec::new(),
+        };
+        let Some(cart) = cart.filter(|_| !items.is_empty()) else {
+            return Err(ServiceError::Conflict("Cart is empty".into()).with_code(codes::CART_EMPTY));
+        };
+
+        let order_request = PlaceOrderRequest {
+            items: items
+                .into_iter()
+                .map(|item| OrderLineRequest {
+                    equipment_id: item.equipment_id,
+                    quantity: item.quantity,
+                    warranty_selected: item.warranty_selected,
+                    special_requirements: item.special_requirements,
+                })
+                .collect(),
+            shipping_address: request.shipping_address.clone(),
+            shipping_method: request.shipping_method.clone(),
+            special_instructions: request.special_instructions.clone(),
+        };
+        order_request.validate()?;
+        let order = create_order(conn, user_id, &order_request)?;
+
+        if let Some(expected) = &request.expected_total {
+            if *expected != order.order.total_amount {
+                return Err(ServiceError::Conflict(format!(
+                    "Cart total is now {}, not the expected {}", order.order.total_amount, expected
+                ))
+                .with_code(codes::CART_CHANGED));
+            }
+        }
+
+        diesel::delete(cart_items::table.filter(cart_items::cart_id.eq(cart.id)))
+            .execute(conn)
+            .map_err(db_error("Failed to empty cart"))?;
+        touch_cart(conn, &cart)?;
+        Ok(order)
+    })
+}
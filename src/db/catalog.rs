@@ -0,0 +1,228 @@
+use std::collections::{hash_map::Entry, HashMap};
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Text};
+use serde::Serialize;
+use chrono::Utc;
+use crate::catalog::{ImportFile, RowError};
+use crate::db::equipment::{filtered_equipment, write_error, EquipmentFilter};
+use crate::db::categories::category_scope;
+use crate::db::spec_schemas::effective_schema;
+use crate::errors::ServiceError;
+use crate::models::{Equipment, EquipmentChangeset, NewEquipment, SpecAttribute};
+use crate::schema::equipment;
+use crate::validation::validate_listing;
+use log::error;
+
+define_sql_function!(fn lower(x: Text) -> Text);
+
+/// First key of the advisory lock taken by imports; the second is the
+/// supplier id
+const IMPORT_LOCK_NAMESPACE: i32 = 17_001;
+
+/// Outcome of a bulk import. In a dry run the counts describe what would
+/// have happened; nothing is written.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub rows: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub failed: usize,
+    pub errors: Vec<RowError>,
+}
+
+enum Upserted {
+    Created,
+    Updated,
+}
+
+/// Ends the import transaction early: a dry run rolls back with its report,
+/// anything else fails the whole import
+enum Abort {
+    DryRun(ImportReport),
+    Failed(ServiceError),
+}
+
+impl From<diesel::result::Error> for Abort {
+    fn from(error: diesel::result::Error) -> Self {
+        Abort::Failed(ServiceError::DatabaseError(error.to_string()))
+    }
+}
+
+/// Creates or updates the supplier's listings from imported rows. A row
+/// updates the supplier's listing with the same manufacturer and model
+/// number, compared case-insensitively, and creates one otherwise. Updates
+/// leave the columns the file does not have as they are.
+///
+/// Every row is validated and written on its own savepoint, so a bad row is
+/// reported and skipped without affecting the rest. A dry run does all the
+/// same work and then rolls it back.
+pub fn import_catalog(
+    pool: &crate::db::DbPool,
+    supplier_id: i32,
+    file: ImportFile,
+    dry_run: bool,
+) -> Result<ImportReport, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    let outcome = conn.transaction(|conn| {
+        // Imports by one supplier run one at a time, so two of them cannot
+        // both create the same listing
+        diesel::sql_query("SELECT pg_advisory_xact_lock($1, $2)")
+            .bind::<Integer, _>(IMPORT_LOCK_NAMESPACE)
+            .bind::<Integer, _>(supplier_id)
+            .execute(conn)?;
+
+        let report = import_rows(conn, supplier_id, file, dry_run)?;
+        if dry_run {
+            return Err(Abort::DryRun(report));
+        }
+        Ok(report)
+    });
+
+    match outcome {
+        Ok(report) | Err(Abort::DryRun(report)) => Ok(report),
+        Err(Abort::Failed(error)) => {
+            error!("Failed to import equipment: {}", error);
+            Err(error)
+        }
+    }
+}
+
+fn import_rows(
+    conn: &mut PgConnection,
+    supplier_id: i32,
+    mut file: ImportFile,
+    dry_run: bool,
+) -> Result<ImportReport, Abort> {
+    let rows = std::mem::take(&mut file.rows);
+    let mut report = ImportReport { dry_run, rows: rows.len(), ..Default::default() };
+    let mut schemas: HashMap<i32, Vec<SpecAttribute>> = HashMap::new();
+    // Line of the first row imported for each manufacturer and model number
+    let mut imported: HashMap<(String, String), u64> = HashMap::new();
+
+    for row in rows {
+        let item = match row.item {
+            Ok(item) => item,
+            Err(row_errors) => {
+                report.failed += 1;
+                report.errors.extend(row_errors);
+                continue;
+            }
+        };
+
+        let key = (item.manufacturer.to_lowercase(), item.model_number.to_lowercase());
+        if let Some(first) = imported.get(&key) {
+            report.failed += 1;
+            report.errors.push(RowError::new(
+                row.line,
+                None,
+                format!("Same manufacturer and model number as line {}", first),
+            ));
+            continue;
+        }
+
+        match import_row(conn, supplier_id, &file, item, &mut schemas) {
+            Ok(upserted) => {
+                imported.insert(key, row.line);
+                match upserted {
+                    Upserted::Created => report.created += 1,
+                    Upserted::Updated => report.updated += 1,
+                }
+            }
+            // Server-side failures are not the row's fault; stop rather than
+            // blame every remaining row
+            Err(error) if matches!(error.root(), ServiceError::DatabaseError(_)) => {
+                return Err(Abort::Failed(error));
+            }
+            Err(error) => {
+                report.failed += 1;
+                report.errors.extend(RowError::from_service_error(row.line, &error));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+fn import_row(
+    conn: &mut PgConnection,
+    supplier_id: i32,
+    file: &ImportFile,
+    item: NewEquipment,
+    schemas: &mut HashMap<i32, Vec<SpecAttribute>>,
+) -> Result<Upserted, ServiceError> {
+    let schema = match schemas.entry(item.category_id) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(effective_schema(conn, item.category_id).map_err(|error| {
+            error!("Failed to load specification schema: {:?}", error);
+            ServiceError::DatabaseError(error.to_string())
+        })?),
+    };
+
+    // A savepoint, so a failed write leaves the rest of the import intact
+    conn.transaction(|conn| {
+        let mut matches: Vec<Equipment> = equipment::table
+            .filter(equipment::supplier_id.eq(supplier_id))
+            .filter(lower(equipment::manufacturer).eq(item.manufacturer.to_lowercase()))
+            .filter(lower(equipment::model_number).eq(item.model_number.to_lowercase()))
+            .for_update()
+            .load(conn)
+            .map_err(|error| write_error("Failed to match imported equipment", error))?;
+
+        match matches.len() {
+            0 => {
+                validate_listing(&item, schema)?;
+                diesel::insert_into(equipment::table)
+                    .values(&item)
+                    .execute(conn)
+                    .map_err(|error| write_error("Failed to create imported equipment", error))?;
+                Ok(Upserted::Created)
+            }
+            1 => {
+                let existing = matches.remove(0);
+                let id = existing.id;
+                let item = file.merge(item, existing);
+                validate_listing(&item, schema)?;
+                diesel::update(equipment::table.find(id))
+                    .set((EquipmentChangeset::from(item), equipment::updated_at.eq(Utc::now().naive_utc())))
+                    .execute(conn)
+                    .map_err(|error| write_error("Failed to update imported equipment", error))?;
+                Ok(Upserted::Updated)
+            }
+            count => Err(ServiceError::Conflict(format!(
+                "Matches {} of your listings with this manufacturer and model number",
+                count
+            ))),
+        }
+    })
+}
+
+/// Up to `limit` items matching `filter` with ids above `after_id`, in id
+/// order. Exports page through the catalog with this so memory stays
+/// bounded however large it is.
+pub fn export_batch(
+    pool: &crate::db::DbPool,
+    filter: &EquipmentFilter,
+    after_id: i32,
+    limit: i64,
+) -> Result<Vec<Equipment>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    category_scope(conn, filter.category_id, filter.include_subcategories)
+        .and_then(|categories| {
+            filtered_equipment(filter, categories.as_deref())
+                .filter(equipment::id.gt(after_id))
+                .order(equipment::id.asc())
+                .limit(limit)
+                .load(conn)
+        })
+        .map_err(|error| {
+            error!("Failed to export equipment: {:?}", error);
+            ServiceError::DatabaseError(error.to_string())
+        })
+}
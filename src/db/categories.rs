@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use diesel::prelude::*;
+use diesel::sql_types::Integer;
+use chrono::Utc;
+use crate::models::{CategoryChangeset, CategoryNode, EquipmentCategory, NewEquipmentCategory};
+use crate::errors::{codes, ServiceError};
+use crate::schema::{equipment, equipment_categories};
+use log::error;
+
+/// Bound on ancestor walks; far deeper than any real catalog
+const MAX_DEPTH: i32 = 64;
+
+#[derive(QueryableByName)]
+struct CategoryId {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+}
+
+fn category_not_found(category_id: i32) -> ServiceError {
+    ServiceError::NotFound(format!("Category {} not found", category_id))
+        .with_code(codes::CATEGORY_NOT_FOUND)
+}
+
+/// Maps write errors: duplicate names and dangling parents are client errors
+fn write_error(context: &str, error: diesel::result::Error) -> ServiceError {
+    error!("{}: {:?}", context, error);
+    match error {
+        diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::UniqueViolation,
+            _
+        ) => ServiceError::Conflict("Category name is already taken".into())
+            .with_code(codes::CATEGORY_NAME_TAKEN),
+        diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::ForeignKeyViolation,
+            _
+        ) => ServiceError::invalid_field("parent_category_id", "Parent category does not exist"),
+        _ => ServiceError::DatabaseError(error.to_string())
+    }
+}
+
+/// Ids of the category and every category below it. `UNION` rather than
+/// `UNION ALL` keeps the recursion finite even if the data contains a cycle.
+pub(crate) fn descendant_ids(conn: &mut PgConnection, category_id: i32) -> QueryResult<Vec<i32>> {
+    diesel::sql_query(
+        "WITH RECURSIVE subtree AS ( \
+             SELECT id FROM equipment_categories WHERE id = $1 \
+             UNION \
+             SELECT c.id FROM equipment_categories c JOIN subtree s ON c.parent_category_id = s.id \
+         ) \
+         SELECT id FROM subtree"
+    )
+    .bind::<Integer, _>(category_id)
+    .load::<CategoryId>(conn)
+    .map(|rows| rows.into_iter().map(|row| row.id).collect())
+}
+
+/// The category ids an equipment query restricted to `category_id` should
+/// match: just that category, or its whole subtree
+pub(crate) fn category_scope(
+    conn: &mut PgConnection,
+    category_id: Option<i32>,
+    include_subcategories: bool,
+) -> QueryResult<Option<Vec<i32>>> {
+    match category_id {
+        Some(category) if include_subcategories => descendant_ids(conn, category).map(Some),
+        Some(category) => Ok(Some(vec![category])),
+        None => Ok(None),
+    }
+}
+
+/// Ids from the category itself up to its top-level ancestor, nearest
+/// first; empty if the category does not exist
+pub(crate) fn ancestor_ids(conn: &mut PgConnection, category_id: i32) -> QueryResult<Vec<i32>> {
+    diesel::sql_query(
+        "WITH RECURSIVE ancestors AS ( \
+             SELECT id, parent_category_id, 0 AS depth FROM equipment_categories WHERE id = $1 \
+             UNION ALL \
+             SELECT p.id, p.parent_category_id, a.depth + 1 FROM equipment_categories p \
+             JOIN ancestors a ON p.id = a.parent_category_id \
+             WHERE a.depth < $2 \
+         ) \
+         SELECT id FROM ancestors ORDER BY depth"
+    )
+    .bind::<Integer, _>(category_id)
+    .bind::<Integer, _>(MAX_DEPTH)
+    .load::<CategoryId>(conn)
+    .map(|rows| rows.into_iter().map(|row| row.id).collect())
+}
+
+pub fn create_category(
+    pool: &crate::db::DbPool,
+    new_category: NewEquipmentCategory,
+) -> Result<EquipmentCategory, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    diesel::insert_into(equipment_categories::table)
+        .values(&new_category)
+        .get_result(conn)
+        .map_err(|error| write_error("Failed to create category", error))
+}
+
+pub fn get_category_by_id(pool: &crate::db::DbPool, category_id: i32) -> Result<EquipmentCategory, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    equipment_categories::table
+        .find(category_id)
+        .first(conn)
+        .map_err(|error| match error {
+            diesel::result::Error::NotFound => category_not_found(category_id),
+            _ => {
+                error!("Failed to get category by id: {:?}", error);
+                ServiceError::DatabaseError(error.to_string())
+            }
+        })
+}
+
+/// Every category, ordered by name
+pub fn list_categories(pool: &crate::db::DbPool) -> Result<Vec<EquipmentCategory>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    equipment_categories::table
+        .order((equipment_categories::name.asc(), equipment_categories::id.asc()))
+        .load(conn)
+        .map_err(|error| {
+            error!("Failed to list categories: {:?}", error);
+            ServiceError::DatabaseError(error.to_string())
+        })
+}
+
+/// The full hierarchy as a forest of top-level categories, siblings ordered
+/// by name
+pub fn category_tree(pool: &crate::db::DbPool) -> Result<Vec<CategoryNode>, ServiceError> {
+    let mut by_parent: HashMap<Option<i32>, Vec<EquipmentCategory>> = HashMap::new();
+    for category in list_categories(pool)? {
+        by_parent.entry(category.parent_category_id).or_default().push(category);
+    }
+
+    fn build(parent: Option<i32>, by_parent: &mut HashMap<Option<i32>, Vec<EquipmentCategory>>) -> Vec<CategoryNode> {
+        by_parent
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|category| CategoryNode {
+                children: build(Some(category.id), by_parent),
+                category,
+            })
+            .collect()
+    }
+
+    Ok(build(None, &mut by_parent))
+}
+
+/// The path from the top-level category down to `category_id`, inclusive
+pub fn breadcrumbs(pool: &crate::db::DbPool, category_id: i32) -> Result<Vec<EquipmentCategory>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    let load = |conn: &mut PgConnection| -> QueryResult<Vec<EquipmentCategory>> {
+        let path = ancestor_ids(conn, category_id)?;
+        let mut categories: HashMap<i32, EquipmentCategory> = equipment_categories::table
+            .filter(equipment_categories::id.eq_any(&path))
+            .load::<EquipmentCategory>(conn)?
+            .into_iter()
+            .map(|category| (category.id, category))
+            .collect();
+        Ok(path.iter().rev().filter_map(|id| categories.remove(id)).collect())
+    };
+
+    let path = load(conn).map_err(|error| {
+        error!("Failed to load category breadcrumbs: {:?}", error);
+        ServiceError::DatabaseError(error.to_string())
+    })?;
+
+    if path.is_empty() {
+        return Err(category_not_found(category_id));
+    }
+    Ok(path)
+}
+
+/// Updates a category. Moving a category under itself or one of its own
+/// descendants is rejected.
+pub fn update_category(
+    pool: &crate::db::DbPool,
+    category_id: i32,
+    changes: &CategoryChangeset,
+) -> Result<EquipmentCategory, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        if let Some(Some(parent)) = changes.parent_category_id {
+            // Serialises moves so two concurrent ones cannot form a cycle
+            // between them; plain reads are unaffected
+            diesel::sql_query("LOCK TABLE equipment_categories IN SHARE ROW EXCLUSIVE MODE")
+                .execute(conn)
+                .map_err(|error| write_error("Failed to lock categories", error))?;
+            let subtree = descendant_ids(conn, category_id)
+                .map_err(|error| write_error("Failed to load category subtree", error))?;
+            if subtree.contains(&parent) {
+                return Err(ServiceError::invalid_field(
+                    "parent_category_id",
+                    "A category cannot be moved under itself or one of its subcategories",
+                )
+                .with_code(codes::CATEGORY_CYCLE));
+            }
+        }
+
+        diesel::update(equipment_categories::table.find(category_id))
+            .set((changes, equipment_categories::updated_at.eq(Utc::now().naive_utc())))
+            .get_result(conn)
+            .map_err(|error| match error {
+                diesel::result::Error::NotFound => category_not_found(category_id),
+                _ => write_error("Failed to update category", error),
+            })
+    })
+}
+
+/// Deletes an empty leaf category. Categories with subcategories or
+/// equipment are kept so nothing is orphaned.
+pub fn delete_category(pool: &crate::db::DbPool, category_id: i32) -> Result<(), ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    let in_use = |what: &str| {
+        ServiceError::Conflict(format!("Category {} still has {}", category_id, what))
+            .with_code(codes::CATEGORY_IN_USE)
+    };
+
+    conn.transaction(|conn| {
+        let db_error = |error: diesel::result::Error| {
+            error!("Failed to delete category: {:?}", error);
+            ServiceError::DatabaseError(error.to_string())
+        };
+
+        let children: i64 = equipment_categories::table
+            .filter(equipment_categories::parent_category_id.eq(category_id))
+            .count()
+            .get_result(conn)
+            .map_err(db_error)?;
+        if children > 0 {
+            return Err(in_use("subcategories"));
+        }
+
+        let items: i64 = equipment::table
+            .filter(equipment::category_id.eq(category_id))
+            .count()
+            .get_result(conn)
+            .map_err(db_error)?;
+        if items > 0 {
+            return Err(in_use("equipment"));
+        }
+
+        let count = diesel::delete(equipment_categories::table.find(category_id))
+            .execute(conn)
+            .map_err(|error| match error {
+                // A row added concurrently after the checks above
+                diesel::result::Error::DatabaseError(
+                    diesel::result::DatabaseErrorKind::ForeignKeyViolation,
+                    _
+                ) => in_use("subcategories or equipment"),
+                _ => db_error(error),
+            })?;
+        if count == 0 {
+            return Err(category_not_found(category_id));
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use diesel::prelude::*;
+use crate::db::db_error;
+use crate::db::equipment::lock_equipment;
+use crate::errors::{codes, ServiceError};
+use crate::models::{DocumentSummary, DocumentType, NewTechnicalDocument, TechnicalDocument};
+use crate::schema::technical_documents;
+
+fn document_not_found(document_id: i32) -> ServiceError {
+    ServiceError::NotFound(format!("Document {} not found", document_id))
+        .with_code(codes::DOCUMENT_NOT_FOUND)
+}
+
+fn find_document(
+    conn: &mut PgConnection,
+    equipment_id: i32,
+    document_id: i32,
+) -> Result<TechnicalDocument, ServiceError> {
+    technical_documents::table
+        .filter(technical_documents::id.eq(document_id))
+        .filter(technical_documents::equipment_id.eq(equipment_id))
+        .first(conn)
+        .optional()
+        .map_err(db_error("Failed to load document"))?
+        .ok_or_else(|| document_not_found(document_id))
+}
+
+/// The latest version of each document of a listing, ordered by type and
+/// title, optionally limited to one type
+pub fn list_latest_documents(
+    pool: &crate::db::DbPool,
+    equipment_id: i32,
+    document_type: Option<DocumentType>,
+) -> Result<Vec<DocumentSummary>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    let mut latest = technical_documents::table
+        .filter(technical_documents::equipment_id.eq(equipment_id))
+        .distinct_on((technical_documents::document_type, technical_documents::title))
+        .order((
+            technical_documents::document_type.asc(),
+            technical_documents::title.asc(),
+            technical_documents::revision.desc(),
+        ))
+        .into_boxed();
+    let mut counts = technical_documents::table
+        .filter(technical_documents::equipment_id.eq(equipment_id))
+        .group_by((technical_documents::document_type, technical_documents::title))
+        .select((technical_documents::document_type, technical_documents::title, diesel::dsl::count_star()))
+        .into_boxed();
+    if let Some(document_type) = document_type {
+        latest = latest.filter(technical_documents::document_type.eq(document_type));
+        counts = counts.filter(technical_documents::document_type.eq(document_type));
+    }
+
+    let latest: Vec<TechnicalDocument> = latest.load(conn).map_err(db_error("Failed to list documents"))?;
+    let counts: HashMap<(DocumentType, String), i64> = counts
+        .load::<(DocumentType, String, i64)>(conn)
+        .map_err(db_error("Failed to count document versions"))?
+        .into_iter()
+        .map(|(document_type, title, count)| ((document_type, title), count))
+        .collect();
+
+    Ok(latest
+        .into_iter()
+        .map(|latest| {
+            let version_count = counts
+                .get(&(latest.document_type, latest.title.clone()))
+                .copied()
+                .unwrap_or(1);
+            DocumentSummary { latest, version_count }
+        })
+        .collect())
+}
+
+/// Every version of every document of a listing
+pub fn all_documents(pool: &crate::db::DbPool, equipment_id: i32) -> Result<Vec<TechnicalDocument>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    technical_documents::table
+        .filter(technical_documents::equipment_id.eq(equipment_id))
+        .order(technical_documents::id.asc())
+        .load(conn)
+        .map_err(db_error("Failed to list documents"))
+}
+
+/// Adds a version of the document identified by the equipment, type and
+/// title of `new_document`, creating the document if it has none yet.
+/// `revision` is decided here.
+pub fn add_document_version(
+    pool: &crate::db::DbPool,
+    mut new_document: NewTechnicalDocument,
+) -> Result<TechnicalDocument, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        // Serializes uploads so two versions never get the same revision
+        lock_equipment(conn, new_document.equipment_id)?;
+
+        let last_revision: Option<i32> = technical_documents::table
+            .filter(technical_documents::equipment_id.eq(new_document.equipment_id))
+            .filter(technical_documents::document_type.eq(new_document.document_type))
+            .filter(technical_documents::title.eq(&new_document.title))
+            .select(diesel::dsl::max(technical_documents::revision))
+            .first(conn)
+            .map_err(db_error("Failed to find latest revision"))?;
+        new_document.revision = last_revision.map_or(1, |revision| revision + 1);
+
+        diesel::insert_into(technical_documents::table)
+            .values(&new_document)
+            .get_result(conn)
+            .map_err(db_error("Failed to add document"))
+    })
+}
+
+/// One version of a document of a listing
+pub fn get_document(
+    pool: &crate::db::DbPool,
+    equipment_id: i32,
+    document_id: i32,
+) -> Result<TechnicalDocument, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    find_document(conn, equipment_id, document_id)
+}
+
+/// Every version of the document `document_id` is a version of, newest first
+pub fn document_versions(
+    pool: &crate::db::DbPool,
+    equipment_id: i32,
+    document_id: i32,
+) -> Result<Vec<TechnicalDocument>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    let document = find_document(conn, equipment_id, document_id)?;
+    technical_documents::table
+        .filter(technical_documents::equipment_id.eq(equipment_id))
+        .filter(technical_documents::document_type.eq(document.document_type))
+        .filter(technical_documents::title.eq(&document.title))
+        .order(technical_documents::revision.desc())
+        .load(conn)
+        .map_err(db_error("Failed to list document versions"))
+}
+
+/// Removes one version and returns it so its stored file can be cleaned up.
+/// The remaining versions keep their revisions.
+pub fn delete_document(
+    pool: &crate::db::DbPool,
+    equipment_id: i32,
+    document_id: i32,
+) -> Result<TechnicalDocument, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    diesel::delete(
+        technical_documents::table
+            .filter(technical_documents::id.eq(document_id))
+            .filter(technical_documents::equipment_id.eq(equipment_id))
+    )
+    .get_result(conn)
+    .optional()
+    .map_err(db_error("Failed to delete document"))?
+    .ok_or_else(|| document_not_found(document_id))
+}
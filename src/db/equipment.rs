@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use diesel::prelude::*;
+use diesel::pg::Pg;
+use chrono::Utc;
+use crate::models::{Equipment, EquipmentChangeset, EquipmentImage, EquipmentResponse, NewEquipment};
+use crate::errors::{codes, ServiceError};
+use crate::db::contains_pattern;
+use crate::db::categories::category_scope;
+use crate::pagination::Pagination;
+use crate::schema::{equipment, equipment_categories, equipment_images};
+use log::error;
+
+/// Optional filters for listing equipment
+#[derive(Debug, Default, Clone)]
+pub struct EquipmentFilter {
+    pub category_id: Option<i32>,
+    /// Also match equipment in subcategories of `category_id`
+    pub include_subcategories: bool,
+    pub supplier_id: Option<i32>,
+    /// Case-insensitive substring match on the manufacturer
+    pub manufacturer: Option<String>,
+}
+
+/// `categories` is the resolved set of category ids to match, if any
+pub(crate) fn filtered_equipment(filter: &EquipmentFilter, categories: Option<&[i32]>) -> equipment::BoxedQuery<'static, Pg> {
+    let mut query = equipment::table.into_boxed();
+
+    if let Some(ids) = categories {
+        query = query.filter(equipment::category_id.eq_any(ids.to_vec()));
+    }
+    if let Some(supplier) = filter.supplier_id {
+        query = query.filter(equipment::supplier_id.eq(supplier));
+    }
+    if let Some(manufacturer) = &filter.manufacturer {
+        query = query.filter(equipment::manufacturer.ilike(contains_pattern(manufacturer)).escape('\\'));
+    }
+
+    query
+}
+
+fn equipment_not_found(equipment_id: i32) -> ServiceError {
+    ServiceError::NotFound(format!("Equipment {} not found", equipment_id))
+        .with_code(codes::EQUIPMENT_NOT_FOUND)
+}
+
+/// Locks the equipment row so changes to its images or documents apply one
+/// at a time within the calling transaction
+pub(crate) fn lock_equipment(conn: &mut PgConnection, equipment_id: i32) -> Result<(), ServiceError> {
+    equipment::table
+        .find(equipment_id)
+        .select(equipment::id)
+        .for_update()
+        .first::<i32>(conn)
+        .optional()
+        .map_err(crate::db::db_error("Failed to lock equipment"))?
+        .map(|_| ())
+        .ok_or_else(|| equipment_not_found(equipment_id))
+}
+
+/// Maps write errors, turning a dangling `category_id` into a field error
+pub(crate) fn write_error(context: &str, error: diesel::result::Error) -> ServiceError {
+    error!("{}: {:?}", context, error);
+    match error {
+        diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::ForeignKeyViolation,
+            _
+        ) => ServiceError::invalid_field("category_id", "Category does not exist"),
+        _ => ServiceError::DatabaseError(error.to_string())
+    }
+}
+
+/// Attaches category names, primary images and available quantities to a
+/// batch of equipment using one query for each rather than one per item
+pub(crate) fn with_details(
+    conn: &mut PgConnection,
+    items: Vec<Equipment>,
+) -> QueryResult<Vec<EquipmentResponse>> {
+    let category_ids: Vec<i32> = items.iter().map(|item| item.category_id).collect();
+    let equipment_ids: Vec<i32> = items.iter().map(|item| item.id).collect();
+
+    let category_names: HashMap<i32, String> = equipment_categories::table
+        .filter(equipment_categories::id.eq_any(&category_ids))
+        .select((equipment_categories::id, equipment_categories::name))
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .collect();
+
+    // Lowest id wins should several images be flagged as primary
+    let mut primary_images: HashMap<i32, EquipmentImage> = HashMap::new();
+    for image in equipment_images::table
+        .filter(equipment_images::equipment_id.eq_any(&equipment_ids))
+        .filter(equipment_images::is_primary.eq(true))
+        .order(equipment_images::id.asc())
+        .load::<EquipmentImage>(conn)?
+    {
+        primary_images.entry(image.equipment_id).or_insert(image);
+    }
+    let held = crate::db::reservations::active_holds(conn, &equipment_ids, None)?;
+
+    Ok(items
+        .into_iter()
+        .map(|item| EquipmentResponse {
+            category_name: category_names.get(&item.category_id).cloned().unwrap_or_default(),
+            primary_image: primary_images.remove(&item.id),
+            available_quantity: (i64::from(item.stock_level) - held.get(&item.id).copied().unwrap_or(0)).max(0) as i32,
+            equipment: item,
+            imperial: None,
+        })
+        .collect())
+}
+
+pub fn create_equipment(pool: &crate::db::DbPool, new_item: NewEquipment) -> Result<Equipment, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    diesel::insert_into(equipment::table)
+        .values(&new_item)
+        .get_result(conn)
+        .map_err(|error| write_error("Failed to create equipment", error))
+}
+
+pub fn get_equipment_by_id(pool: &crate::db::DbPool, equipment_id: i32) -> Result<Equipment, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    equipment::table
+        .find(equipment_id)
+        .first(conn)
+        .map_err(|error| match error {
+            diesel::result::Error::NotFound => equipment_not_found(equipment_id),
+            _ => {
+                error!("Failed to get equipment by id: {:?}", error);
+                ServiceError::DatabaseError(error.to_string())
+            }
+        })
+}
+
+/// A single item with its category name and primary image
+pub fn get_equipment_details(pool: &crate::db::DbPool, equipment_id: i32) -> Result<EquipmentResponse, ServiceError> {
+    let item = get_equipment_by_id(pool, equipment_id)?;
+
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    with_details(conn, vec![item])
+        .map(|mut items| items.remove(0))
+        .map_err(|error| {
+            error!("Failed to load equipment details: {:?}", error);
+            ServiceError::DatabaseError(error.to_string())
+        })
+}
+
+pub fn update_equipment(
+    pool: &crate::db::DbPool,
+    equipment_id: i32,
+    changes: &EquipmentChangeset,
+) -> Result<Equipment, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    diesel::update(equipment::table.find(equipment_id))
+        .set((changes, equipment::updated_at.eq(Utc::now().naive_utc())))
+        .get_result(conn)
+        .map_err(|error| match error {
+            diesel::result::Error::NotFound => equipment_not_found(equipment_id),
+            _ => write_error("Failed to update equipment", error),
+        })
+}
+
+/// Removes a listing together with its images, documents, maintenance
+/// records, stock holds and the cart lines holding it. Equipment that has
+/// been ordered or reviewed cannot be deleted.
+pub fn delete_equipment(pool: &crate::db::DbPool, equipment_id: i32) -> Result<(), ServiceError> {
+    use crate::schema::{cart_items, maintenance_records, stock_reservations, technical_documents};
+
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    let count = conn.transaction(|conn| {
+        diesel::delete(equipment_images::table.filter(equipment_images::equipment_id.eq(equipment_id)))
+            .execute(conn)?;
+        diesel::delete(technical_documents::table.filter(technical_documents::equipment_id.eq(equipment_id)))
+            .execute(conn)?;
+        diesel::delete(maintenance_records::table.filter(maintenance_records::equipment_id.eq(equipment_id)))
+            .execute(conn)?;
+        diesel::delete(cart_items::table.filter(cart_items::equipment_id.eq(equipment_id)))
+            .execute(conn)?;
+        diesel::delete(stock_reservations::table.filter(stock_reservations::equipment_id.eq(equipment_id)))
+            .execute(conn)?;
+        diesel::delete(equipment::table.find(equipment_id)).execute(conn)
+    })
+    .map_err(|error| {
+        error!("Failed to delete equipment: {:?}", error);
+        match error {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::ForeignKeyViolation,
+                _
+            ) => ServiceError::Conflict(format!(
+                "Equipment {} has orders or reviews and cannot be deleted", equipment_id
+            ))
+            .with_code(codes::EQUIPMENT_IN_USE),
+            _ => ServiceError::DatabaseError(error.to_string())
+        }
+    })?;
+
+    if count == 0 {
+        return Err(equipment_not_found(equipment_id));
+    }
+    Ok(())
+}
+
+/// Returns one page of equipment matching `filter`, newest first, along with
+/// the total number of matches
+pub fn list_equipment(
+    pool: &crate::db::DbPool,
+    filter: &EquipmentFilter,
+    pagination: Pagination,
+) -> Result<(Vec<EquipmentResponse>, i64), ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    let categories = category_scope(conn, filter.category_id, filter.include_subcategories)
+        .map_err(|error| {
+            error!("Failed to resolve subcategories: {:?}", error);
+            ServiceError::DatabaseError(error.to_string())
+        })?;
+
+    let total = filtered_equipment(filter, categories.as_deref())
+        .count()
+        .get_result(conn)
+        .map_err(|error| {
+            error!("Failed to count equipment: {:?}", error);
+            ServiceError::DatabaseError(error.to_string())
+        })?;
+
+    let page = filtered_equipment(filter, categories.as_deref())
+        .order((equipment::created_at.desc(), equipment::id.desc()))
+        .limit(pagination.per_page)
+        .offset(pagination.offset())
+        .load(conn)
+        .and_then(|items| with_details(conn, items))
+        .map_err(|error| {
+            error!("Failed to list equipment: {:?}", error);
+            ServiceError::DatabaseError(error.to_string())
+        })?;
+
+    Ok((page, total))
+}
@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+use diesel::prelude::*;
+use crate::db::db_error;
+use crate::db::equipment::lock_equipment;
+use crate::errors::{codes, ServiceError};
+use crate::models::{EquipmentImage, NewEquipmentImage};
+use crate::schema::equipment_images;
+
+/// Most images a single listing may have
+pub const MAX_IMAGES_PER_EQUIPMENT: i64 = 20;
+
+fn image_not_found(image_id: i32) -> ServiceError {
+    ServiceError::NotFound(format!("Image {} not found", image_id))
+        .with_code(codes::IMAGE_NOT_FOUND)
+}
+
+fn load_images(conn: &mut PgConnection, equipment_id: i32) -> QueryResult<Vec<EquipmentImage>> {
+    equipment_images::table
+        .filter(equipment_images::equipment_id.eq(equipment_id))
+        .order((equipment_images::position.asc(), equipment_images::id.asc()))
+        .load(conn)
+}
+
+/// Makes `image_id` the only primary image of the equipment. The old
+/// primary is cleared first so the unique index never sees two; callers
+/// hold the equipment lock so no other write races it.
+fn make_primary(conn: &mut PgConnection, equipment_id: i32, image_id: i32) -> QueryResult<()> {
+    diesel::update(
+        equipment_images::table
+            .filter(equipment_images::equipment_id.eq(equipment_id))
+            .filter(equipment_images::is_primary.eq(true))
+            .filter(equipment_images::id.ne(image_id))
+    )
+    .set(equipment_images::is_primary.eq(false))
+    .execute(conn)?;
+    diesel::update(equipment_images::table.find(image_id))
+        .set(equipment_images::is_primary.eq(true))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// The images of a listing in display order
+pub fn list_images(pool: &crate::db::DbPool, equipment_id: i32) -> Result<Vec<EquipmentImage>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    load_images(conn, equipment_id).map_err(db_error("Failed to list images"))
+}
+
+/// Adds an image after the existing ones. The first image of a listing is
+/// always primary; a later one replaces the primary if `is_primary` is set.
+/// `position` and `is_primary` of `new_image` are decided here.
+pub fn add_image(pool: &crate::db::DbPool, mut new_image: NewEquipmentImage) -> Result<EquipmentImage, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+    let equipment_id = new_image.equipment_id;
+
+    conn.transaction(|conn| {
+        lock_equipment(conn, equipment_id)?;
+
+        let (count, last_position): (i64, Option<i32>) = equipment_images::table
+            .filter(equipment_images::equipment_id.eq(equipment_id))
+            .select((diesel::dsl::count_star(), diesel::dsl::max(equipment_images::position)))
+            .first(conn)
+            .map_err(db_error("Failed to count images"))?;
+        if count >= MAX_IMAGES_PER_EQUIPMENT {
+            return Err(ServiceError::Conflict(format!(
+                "A listing can have at most {} images", MAX_IMAGES_PER_EQUIPMENT
+            ))
+            .with_code(codes::IMAGE_LIMIT_REACHED));
+        }
+
+        let make_primary_after_insert = new_image.is_primary || count == 0;
+        new_image.position = last_position.map_or(0, |position| position + 1);
+        new_image.is_primary = false;
+        let image: EquipmentImage = diesel::insert_into(equipment_images::table)
+            .values(&new_image)
+            .get_result(conn)
+            .map_err(db_error("Failed to add image"))?;
+
+        if !make_primary_after_insert {
+            return Ok(image);
+        }
+        make_primary(conn, equipment_id, image.id).map_err(db_error("Failed to set primary image"))?;
+        Ok(EquipmentImage { is_primary: true, ..image })
+    })
+}
+
+/// Makes an image the primary one of its listing
+pub fn set_primary_image(
+    pool: &crate::db::DbPool,
+    equipment_id: i32,
+    image_id: i32,
+) -> Result<Vec<EquipmentImage>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        lock_equipment(conn, equipment_id)?;
+        let images = load_images(conn, equipment_id).map_err(db_error("Failed to load images"))?;
+        if !images.iter().any(|image| image.id == image_id) {
+            return Err(image_not_found(image_id));
+        }
+
+        make_primary(conn, equipment_id, image_id).map_err(db_error("Failed to set primary image"))?;
+        load_images(conn, equipment_id).map_err(db_error("Failed to load images"))
+    })
+}
+
+/// Puts the images of a listing in the given order. `image_ids` must list
+/// each of its images exactly once.
+pub fn reorder_images(
+    pool: &crate::db::DbPool,
+    equipment_id: i32,
+    image_ids: &[i32],
+) -> Result<Vec<EquipmentImage>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        lock_equipment(conn, equipment_id)?;
+        let images = load_images(conn, equipment_id).map_err(db_error("Failed to load images"))?;
+
+        let current: HashSet<i32> = images.iter().map(|image| image.id).collect();
+        let requested: HashSet<i32> = image_ids.iter().copied().collect();
+        if requested.len() != image_ids.len() || requested != current {
+            return Err(ServiceError::invalid_field(
+                "image_ids",
+                "Must list every image of the listing exactly once",
+            ));
+        }
+
+        for (position, image_id) in image_ids.iter().enumerate() {
+            diesel::update(equipment_images::table.find(image_id))
+                .set(equipment_images::position.eq(position as i32))
+                .execute(conn)
+                .map_err(db_error("Failed to reorder images"))?;
+        }
+        load_images(conn, equipment_id).map_err(db_error("Failed to load images"))
+    })
+}
+
+/// Removes an image and returns it so its stored files can be cleaned up.
+/// If it was the primary image, the first remaining one takes its place.
+pub fn delete_image(
+    pool: &crate::db::DbPool,
+    equipment_id: i32,
+    image_id: i32,
+) -> Result<EquipmentImage, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        lock_equipment(conn, equipment_id)?;
+        let removed: EquipmentImage = diesel::delete(
+            equipment_images::table
+                .filter(equipment_images::id.eq(image_id))
+                .filter(equipment_images::equipment_id.eq(equipment_id))
+        )
+        .get_result(conn)
+        .optional()
+        .map_err(db_error("Failed to delete image"))?
+        .ok_or_else(|| image_not_found(image_id))?;
+
+        if removed.is_primary {
+            let next = load_images(conn, equipment_id)
+                .map_err(db_error("Failed to load images"))?
+                .into_iter()
+                .next();
+            if let Some(next) = next {
+                make_primary(conn, equipment_id, next.id).map_err(db_error("Failed to set primary image"))?;
+            }
+        }
+        Ok(removed)
+    })
+}
@@ -1,17 +1,52 @@
 use diesel::pg::PgConnection;
 use diesel::r2d2::{self, ConnectionManager};
 use std::env;
-use log::info;
+use log::{error, info};
 use std::error::Error as StdError;
 use std::time::Instant;
 use crate::logging::{log_performance_metrics, PerformanceMetric, MetricType};
 
+pub mod account_tokens;
+pub mod cancellations;
+pub mod carts;
+pub mod catalog;
+pub mod categories;
+pub mod documents;
+pub mod equipment;
+pub mod images;
+pub mod orders;
+pub mod reservations;
+pub mod search;
+pub mod spec_schemas;
 pub mod users;
 
 pub type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
 pub type DbPool = Pool;
 pub type BoxError = Box<dyn StdError + Send + Sync + 'static>;
 
+/// Logs a query failure with `context` and maps it to a database error
+pub(crate) fn db_error(context: &str) -> impl Fn(diesel::result::Error) -> crate::errors::ServiceError + '_ {
+    move |error| {
+        error!("{}: {:?}", context, error);
+        crate::errors::ServiceError::DatabaseError(error.to_string())
+    }
+}
+
+/// A `LIKE` pattern matching values that contain `text`. Wildcards in
+/// `text` are escaped with a backslash, so use it with `.escape('\\')`.
+pub(crate) fn contains_pattern(text: &str) -> String {
+    let mut pattern = String::with_capacity(text.len() + 2);
+    pattern.push('%');
+    for c in text.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
 #[derive(Debug)]
 pub enum Error {
     ConfigError(String),
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use bigdecimal::BigDecimal;
+use diesel::prelude::*;
+use crate::db::db_error;
+use crate::db::cancellations::cancel_lines;
+use crate::db::reservations::{active_holds, consume_holds, shortage};
+use crate::errors::{codes, FieldError, ServiceError};
+use crate::models::{
+    Equipment, NewOrder, NewOrderItem, NewOrderStatusChange, Order, OrderDetails, OrderItem, OrderStatus,
+    OrderStatusChange, OrderTransition, PlaceOrderRequest,
+};
+use crate::schema::{equipment, order_items, order_status_history, orders};
+use crate::validation::Validate;
+
+pub(crate) fn order_not_found(order_id: i32) -> ServiceError {
+    ServiceError::NotFound(format!("Order {} not found", order_id))
+        .with_code(codes::ORDER_NOT_FOUND)
+}
+
+pub(crate) fn load_items(conn: &mut PgConnection, order_id: i32) -> Result<Vec<OrderItem>, ServiceError> {
+    order_items::table
+        .filter(order_items::order_id.eq(order_id))
+        .order(order_items::id.asc())
+        .load(conn)
+        .map_err(db_error("Failed to load order items"))
+}
+
+/// The order, locked for the rest of the transaction
+pub(crate) fn lock_order(conn: &mut PgConnection, order_id: i32) -> Result<Order, ServiceError> {
+    orders::table
+        .find(order_id)
+        .for_update()
+        .first(conn)
+        .optional()
+        .map_err(db_error("Failed to lock order"))?
+        .ok_or_else(|| order_not_found(order_id))
+}
+
+pub(crate) fn ensure_transition(order: &Order, next: OrderStatus) -> Result<(), ServiceError> {
+    if order.status.can_transition_to(next) {
+        Ok(())
+    } else {
+        Err(ServiceError::Conflict(format!(
+            "Order {} is {} and cannot be {}",
+            order.id, order.status, next
+        ))
+        .with_code(codes::ORDER_INVALID_TRANSITION))
+    }
+}
+
+pub(crate) fn record_status_change(conn: &mut PgConnection, change: &NewOrderStatusChange) -> Result<(), ServiceError> {
+    diesel::insert_into(order_status_history::table)
+        .values(change)
+        .execute(conn)
+        .map(|_| ())
+        .map_err(db_error("Failed to record order status change"))
+}
+
+/// Places an order for `user_id` in one transaction: the listings are
+/// locked, their stock is checked and decremented, and each line records the
+/// listing's current price. The total is computed here from those prices.
+/// Stock other buyers hold is not available; the buyer's own holds on the
+/// listings are used up. Nothing is changed unless the request is valid and
+/// every line can be filled.
+pub fn place_order(
+    pool: &crate::db::DbPool,
+    user_id: i32,
+    request: &PlaceOrderRequest,
+) -> Result<OrderDetails, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| create_order(conn, user_id, request))
+}
+
+/// The body of `place_order`, run in the caller's transaction so cart
+/// checkout can place the order and empty the cart atomically
+pub(crate) fn create_order(
+    conn: &mut PgConnection,
+    user_id: i32,
+    request: &PlaceOrderRequest,
+) -> Result<OrderDetails, ServiceError> {
+    // Each line is checked against the whole stock of its listing, which
+    // only holds while listings appear once
+    request.validate()?;
+
+    // Locked in id order so concurrent orders cannot deadlock
+    let mut ids: Vec<i32> = request.items.iter().map(|line| line.equipment_id).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    let listings: HashMap<i32, Equipment> = equipment::table
+        .filter(equipment::id.eq_any(&ids))
+        .order(equipment::id.asc())
+        .for_update()
+        .load::<Equipment>(conn)
+        .map_err(db_error("Failed to lock equipment"))?
+        .into_iter()
+        .map(|item| (item.id, item))
+        .collect();
+    let held = active_holds(conn, &ids, Some(user_id)).map_err(db_error("Failed to load stock reservations"))?;
+
+    let mut missing = Vec::new();
+    let mut shortages = Vec::new();
+    for (i, line) in request.items.iter().enumerate() {
+        match listings.get(&line.equipment_id) {
+            None => missing.push(FieldError::new(
+                format!("items[{}].equipment_id", i),
+                format!("Equipment {} does not exist", line.equipment_id),
+            )),
+            Some(item) => shortages.extend(shortage(item, held.get(&item.id).copied().unwrap_or(0), line.quantity)),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(ServiceError::InvalidFields(missing));
+    }
+    if !shortages.is_empty() {
+        return Err(ServiceError::Conflict(format!("Insufficient stock: {}", shortages.join("; ")))
+            .with_code(codes::INSUFFICIENT_STOCK));
+    }
+
+    let total_amount = request
+        .items
+        .iter()
+        .map(|line| &listings[&line.equipment_id].price * BigDecimal::from(line.quantity))
+        .sum::<BigDecimal>()
+        .with_scale(2);
+    let now = chrono::Utc::now().naive_utc();
+    let new_order = NewOrder {
+        user_id,
+        status: OrderStatus::Pending,
+        total_amount,
+        shipping_address: request.shipping_address.trim().to_string(),
+        shipping_method: request.shipping_method.trim().to_string(),
+        tracking_number: None,
+        estimated_delivery_date: None,
+        special_instructions: request.special_instructions.clone(),
+        created_at: now,
+        updated_at: now,
+    };
+    // Catches totals too large to store
+    new_order.validate()?;
+
+    for line in &request.items {
+        diesel::update(equipment::table.find(line.equipment_id))
+            .set(equipment::stock_level.eq(equipment::stock_level - line.quantity))
+            .execute(conn)
+            .map_err(db_error("Failed to decrement stock"))?;
+    }
+    consume_holds(conn, user_id, &ids)?;
+
+    let order: Order = diesel::insert_into(orders::table)
+        .values(&new_order)
+        .get_result(conn)
+        .map_err(db_error("Failed to create order"))?;
+    let new_items: Vec<NewOrderItem> = request
+        .items
+        .iter()
+        .map(|line| NewOrderItem {
+            order_id: order.id,
+            equipment_id: line.equipment_id,
+            quantity: line.quantity,
+            price_at_time: listings[&line.equipment_id].price.clone(),
+            warranty_selected: line.warranty_selected,
+            special_requirements: line.special_requirements.clone(),
+        })
+        .collect();
+    let items: Vec<OrderItem> = diesel::insert_into(order_items::table)
+        .values(&new_items)
+        .get_results(conn)
+        .map_err(db_error("Failed to create order items"))?;
+    record_status_change(conn, &NewOrderStatusChange {
+        order_id: order.id,
+        from_status: None,
+        to_status: order.status,
+        changed_by: Some(user_id),
+        note: None,
+        created_at: now,
+    })?;
+
+    Ok(OrderDetails { order, items })
+}
+
+/// An order with its items
+pub fn get_order(pool: &crate::db::DbPool, order_id: i32) -> Result<OrderDetails, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    let order: Order = orders::table
+        .find(order_id)
+        .first(conn)
+        .optional()
+        .map_err(db_error("Failed to load order"))?
+        .ok_or_else(|| order_not_found(order_id))?;
+    let items = load_items(conn, order.id)?;
+    Ok(OrderDetails { order, items })
+}
+
+/// Suppliers of the listings an order contains, each once
+pub fn order_supplier_ids(pool: &crate::db::DbPool, order_id: i32) -> Result<Vec<i32>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    order_items::table
+        .inner_join(equipment::table)
+        .filter(order_items::order_id.eq(order_id))
+        .filter(equipment::supplier_id.is_not_null())
+        .select(equipment::supplier_id.assume_not_null())
+        .distinct()
+        .load(conn)
+        .map_err(db_error("Failed to load order suppliers"))
+}
+
+/// Supplier of the listing of each line of an order, by order item id
+pub fn line_supplier_ids(
+    pool: &crate::db::DbPool,
+    order_id: i32,
+) -> Result<HashMap<i32, Option<i32>>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    Ok(order_items::table
+        .inner_join(equipment::table)
+        .filter(order_items::order_id.eq(order_id))
+        .select((order_items::id, equipment::supplier_id))
+        .load::<(i32, Option<i32>)>(conn)
+        .map_err(db_error("Failed to load order suppliers"))?
+        .into_iter()
+        .collect())
+}
+
+/// Status changes of an order, oldest first
+pub fn order_history(pool: &crate::db::DbPool, order_id: i32) -> Result<Vec<OrderStatusChange>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    order_status_history::table
+        .filter(order_status_history::order_id.eq(order_id))
+        .order(order_status_history::id.asc())
+        .load(conn)
+        .map_err(db_error("Failed to load order history"))
+}
+
+/// Moves an order to the status of `transition` and records who did it.
+/// The order is locked so concurrent changes are applied one at a time,
+/// and moves the lifecycle does not allow fail with a conflict.
+/// Cancelling goes through `db::cancellations`, so the order's stock is
+/// returned and its refund recorded as well.
+pub fn transition_order(
+    pool: &crate::db::DbPool,
+    order_id: i32,
+    changed_by: i32,
+    transition: &OrderTransition,
+) -> Result<OrderDetails, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        if let OrderTransition::Cancel(request) = transition {
+            return cancel_lines(conn, order_id, None, changed_by, request).map(|outcome| outcome.order);
+        }
+
+        let current = lock_order(conn, order_id)?;
+        let next = transition.target();
+        ensure_transition(&current, next)?;
+
+        let now = chrono::Utc::now().naive_utc();
+        let update = diesel::update(orders::table.find(order_id));
+        let order: Order = match transition {
+            OrderTransition::Ship(shipment) => update
+                .set((
+                    orders::status.eq(next),
+                    orders::tracking_number.eq(shipment.tracking_number.trim()),
+                    orders::estimated_delivery_date.eq(shipment.estimated_delivery_date),
+                    orders::updated_at.eq(now),
+                ))
+                .get_result(conn),
+            _ => update
+                .set((orders::status.eq(next), orders::updated_at.eq(now)))
+                .get_result(conn),
+        }
+        .map_err(db_error("Failed to update order status"))?;
+
+        record_status_change(conn, &NewOrderStatusChange {
+            order_id,
+            from_status: Some(current.status),
+            to_status: next,
+            changed_by: Some(changed_by),
+            note: None,
+            created_at: now,
+        })?;
+
+        let items = load_items(conn, order_id)?;
+        Ok(OrderDetails { order, items })
+    })
+}
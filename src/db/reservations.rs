@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use diesel::prelude::*;
+use crate::db::carts::lock_cart_items;
+use crate::db::db_error;
+use crate::errors::{codes, ServiceError};
+use crate::models::{Equipment, NewStockReservation, StockReservation};
+use crate::schema::{equipment, stock_reservations};
+
+/// Quantities held by unexpired reservations per listing, leaving out the
+/// holds of `excluding_user`. Listings without holds are absent.
+pub(crate) fn active_holds(
+    conn: &mut PgConnection,
+    equipment_ids: &[i32],
+    excluding_user: Option<i32>,
+) -> QueryResult<HashMap<i32, i64>> {
+    let mut query = stock_reservations::table
+        .filter(stock_reservations::equipment_id.eq_any(equipment_ids))
+        .filter(stock_reservations::expires_at.gt(chrono::Utc::now().naive_utc()))
+        .group_by(stock_reservations::equipment_id)
+        .select((stock_reservations::equipment_id, diesel::dsl::sum(stock_reservations::quantity)))
+        .into_boxed();
+    if let Some(user_id) = excluding_user {
+        query = query.filter(stock_reservations::user_id.ne(user_id));
+    }
+
+    Ok(query
+        .load::<(i32, Option<i64>)>(conn)?
+        .into_iter()
+        .map(|(equipment_id, held)| (equipment_id, held.unwrap_or(0)))
+        .collect())
+}
+
+/// Describes why `requested` of a listing cannot be had when `held` of its
+/// stock is reserved for other buyers, or `None` when it can
+pub(crate) fn shortage(listing: &Equipment, held: i64, requested: i32) -> Option<String> {
+    if i64::from(listing.stock_level) - held >= i64::from(requested) {
+        None
+    } else if held == 0 {
+        Some(format!(
+            "equipment {} has {} in stock, {} requested",
+            listing.id, listing.stock_level, requested
+        ))
+    } else {
+        Some(format!(
+            "equipment {} has {} in stock of which {} is held for other buyers, {} requested",
+            listing.id, listing.stock_level, held, requested
+        ))
+    }
+}
+
+/// Removes the user's holds on the given listings, once an order has taken
+/// the stock they were holding
+pub(crate) fn consume_holds(conn: &mut PgConnection, user_id: i32, equipment_ids: &[i32]) -> Result<(), ServiceError> {
+    diesel::delete(
+        stock_reservations::table
+            .filter(stock_reservations::user_id.eq(user_id))
+            .filter(stock_reservations::equipment_id.eq_any(equipment_ids))
+    )
+    .execute(conn)
+    .map(|_| ())
+    .map_err(db_error("Failed to release stock reservations"))
+}
+
+/// Holds every line of the user's cart for `ttl`, replacing the user's
+/// previous holds. Nothing is held unless every line can be, counting what
+/// other buyers hold.
+pub fn reserve_cart(
+    pool: &crate::db::DbPool,
+    user_id: i32,
+    ttl: chrono::Duration,
+) -> Result<Vec<StockReservation>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        let items = lock_cart_items(conn, user_id)?;
+        if items.is_empty() {
+            return Err(ServiceError::Conflict("Cart is empty".into()).with_code(codes::CART_EMPTY));
+        }
+
+        // Locked in id order, as placing orders does, so holds and orders
+        // for the same listings are decided one at a time
+        let mut ids: Vec<i32> = items.iter().map(|item| item.equipment_id).collect();
+        ids.sort_unstable();
+        let listings: HashMap<i32, Equipment> = equipment::table
+            .filter(equipment::id.eq_any(&ids))
+            .order(equipment::id.asc())
+            .for_update()
+            .load::<Equipment>(conn)
+            .map_err(db_error("Failed to lock equipment"))?
+            .into_iter()
+            .map(|item| (item.id, item))
+            .collect();
+        let held = active_holds(conn, &ids, Some(user_id)).map_err(db_error("Failed to load stock reservations"))?;
+
+        let shortages: Vec<String> = items
+            .iter()
+            .filter_map(|item| {
+                let held = held.get(&item.equipment_id).copied().unwrap_or(0);
+                shortage(&listings[&item.equipment_id], held, item.quantity)
+            })
+            .collect();
+        if !shortages.is_empty() {
+            return Err(ServiceError::Conflict(format!("Insufficient stock: {}", shortages.join("; ")))
+                .with_code(codes::INSUFFICIENT_STOCK));
+        }
+
+        diesel::delete(stock_reservations::table.filter(stock_reservations::user_id.eq(user_id)))
+            .execute(conn)
+            .map_err(db_error("Failed to replace stock reservations"))?;
+        let now = chrono::Utc::now().naive_utc();
+        let new_reservations: Vec<NewStockReservation> = items
+            .iter()
+            .map(|item| NewStockReservation {
+                user_id,
+                equipment_id: item.equipment_id,
+                quantity: item.quantity,
+                expires_at: now + ttl,
+                created_at: now,
+            })
+            .collect();
+        diesel::insert_into(stock_reservations::table)
+            .values(&new_reservations)
+            .get_results(conn)
+            .map_err(db_error("Failed to reserve stock"))
+    })
+}
+
+/// The user's unexpired holds
+pub fn user_reservations(pool: &crate::db::DbPool, user_id: i32) -> Result<Vec<StockReservation>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    stock_reservations::table
+        .filter(stock_reservations::user_id.eq(user_id))
+        .filter(stock_reservations::expires_at.gt(chrono::Utc::now().naive_utc()))
+        .order(stock_reservations::equipment_id.asc())
+        .load(conn)
+        .map_err(db_error("Failed to load stock reservations"))
+}
+
+/// Gives up all of the user's holds
+pub fn release_reservations(pool: &crate::db::DbPool, user_id: i32) -> Result<usize, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    diesel::delete(stock_reservations::table.filter(stock_reservations::user_id.eq(user_id)))
+        .execute(conn)
+        .map_err(db_error("Failed to release stock reservations"))
+}
+
+/// Removes holds that have expired, returning how many there were
+pub fn release_expired(pool: &crate::db::DbPool) -> Result<usize, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    diesel::delete(
+        stock_reservations::table.filter(stock_reservations::expires_at.le(chrono::Utc::now().naive_utc()))
+    )
+    .execute(conn)
+    .map_err(db_error("Failed to release expired stock reservations"))
+}
@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use diesel::prelude::*;
+use diesel::dsl::{count_star, sql};
+use diesel::pg::Pg;
+use diesel::sql_types::{Array, Bool, Float4, Jsonb, Numeric, Text};
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use crate::db::categories::category_scope;
+use crate::db::equipment::with_details;
+use crate::errors::ServiceError;
+use crate::models::{EquipmentCondition, EquipmentResponse};
+use crate::pagination::{Page, Pagination};
+use crate::schema::equipment;
+use log::error;
+
+/// Most values returned for a single facet
+pub const MAX_FACET_VALUES: i64 = 50;
+
+/// Text search configuration, matching the one `search_vector` is built with
+const TS_CONFIG: &str = "english";
+
+/// Added to the text rank of listings whose model number appears verbatim in
+/// the query. Text ranks are normalised below 1, so these always come first.
+const MODEL_NUMBER_BOOST: f32 = 1.0;
+
+/// `ts_headline` options: matches wrapped in `<mark>`, up to two fragments
+const HEADLINE_OPTIONS: &str = "StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=25, MinWords=8";
+
+/// Result orderings for equipment search. Ties are broken by newest id so
+/// that paging is stable.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchSort {
+    /// Best text matches first; only meaningful with a text query and
+    /// otherwise the same as `Newest`
+    Relevance,
+    #[default]
+    Newest,
+    PriceAsc,
+    PriceDesc,
+    YearAsc,
+    YearDesc,
+    NameAsc,
+}
+
+/// Comparison in a specification filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl SpecOp {
+    /// Longest tokens first so that `>=` is not read as `>`
+    const TOKENS: [(&'static str, SpecOp); 6] = [
+        (">=", SpecOp::Ge),
+        ("<=", SpecOp::Le),
+        ("!=", SpecOp::Ne),
+        (">", SpecOp::Gt),
+        ("<", SpecOp::Lt),
+        ("=", SpecOp::Eq),
+    ];
+
+    fn sql(&self) -> &'static str {
+        match self {
+            SpecOp::Eq => "=",
+            SpecOp::Ne => "<>",
+            SpecOp::Gt => ">",
+            SpecOp::Ge => ">=",
+            SpecOp::Lt => "<",
+            SpecOp::Le => "<=",
+        }
+    }
+}
+
+/// A condition on one `specifications` attribute, written `payload_t>=300`
+/// or `drive=electric`. Ordering comparisons only match numeric values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpecFilter {
+    /// Equality (or inequality) with any of the JSON values a query string
+    /// value may stand for, so `300` matches both `300` and `"300"`
+    Matches { key: String, values: Vec<serde_json::Value>, negated: bool },
+    Compare { key: String, op: SpecOp, value: BigDecimal },
+}
+
+impl std::str::FromStr for SpecFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid specification filter '{}'", s);
+
+        let start = s.find(['<', '>', '=', '!']).ok_or_else(invalid)?;
+        let (key, rest) = (s[..start].trim(), &s[start..]);
+        let (token, op) = SpecOp::TOKENS
+            .into_iter()
+            .find(|(token, _)| rest.starts_with(token))
+            .ok_or_else(invalid)?;
+        let value = rest[token.len()..].trim();
+        if key.is_empty() || value.is_empty() || value.starts_with(['<', '>', '=', '!']) {
+            return Err(invalid());
+        }
+
+        match op {
+            SpecOp::Eq | SpecOp::Ne => {
+                let mut values = vec![serde_json::Value::String(value.to_string())];
+                if let Ok(typed @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) =
+                    serde_json::from_str::<serde_json::Value>(value)
+                {
+                    values.push(typed);
+                }
+                Ok(SpecFilter::Matches { key: key.to_string(), values, negated: op == SpecOp::Ne })
+            }
+            _ => {
+                let value = value
+                    .parse()
+                    .map_err(|_| format!("Specification filter '{}' needs a numeric value", s))?;
+                Ok(SpecFilter::Compare { key: key.to_string(), op, value })
+            }
+        }
+    }
+}
+
+/// Filters for `GET /equipment/search`. Multi-valued filters match any of
+/// their values; an empty list does not filter.
+#[derive(Debug, Default, Clone)]
+pub struct EquipmentSearch {
+    /// Free-text query in web search syntax (`"exact phrase"`, `-exclude`,
+    /// `or`) over name, description, manufacturer and model number
+    pub text: Option<String>,
+    pub manufacturers: Vec<String>,
+    pub conditions: Vec<EquipmentCondition>,
+    pub category_id: Option<i32>,
+    pub include_subcategories: bool,
+    pub min_price: Option<BigDecimal>,
+    pub max_price: Option<BigDecimal>,
+    pub min_year: Option<i32>,
+    pub max_year: Option<i32>,
+    /// Heaviest listing to match; listings without a weight never match
+    pub max_weight_kg: Option<BigDecimal>,
+    /// Size limits; listings without dimensions never match
+    pub max_length_cm: Option<BigDecimal>,
+    pub max_width_cm: Option<BigDecimal>,
+    pub max_height_cm: Option<BigDecimal>,
+    /// `true` matches listings with stock, `false` those without
+    pub in_stock: Option<bool>,
+    pub specs: Vec<SpecFilter>,
+    pub sort: SearchSort,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: i64,
+}
+
+/// Listing counts per facet value, most common first
+#[derive(Debug, Default, Serialize)]
+pub struct SearchFacets {
+    pub manufacturer: Vec<FacetCount>,
+    pub condition: Vec<FacetCount>,
+}
+
+/// A search result. Text queries add the match rank and an HTML snippet in
+/// which the source text is escaped and matches are wrapped in `<mark>`.
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub equipment: EquipmentResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rank: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResults {
+    #[serde(flatten)]
+    pub page: Page<SearchHit>,
+    pub facets: SearchFacets,
+}
+
+#[derive(Queryable)]
+struct TextMatch {
+    id: i32,
+    rank: f32,
+    snippet: String,
+}
+
+/// A computed column of `equipment` with its query bound
+type TextExpression<ST> = Box<dyn BoxableExpression<equipment::table, Pg, SqlType = ST>>;
+
+/// Lower-cased words of a text query, compared against model numbers
+fn query_tokens(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| word.trim_matches(|c: char| c == '"' || c == '-').to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Rank of a listing against a text query, including the model number
+/// boost. Both relevance ordering and the reported rank use this.
+fn rank(text: &str) -> TextExpression<Float4> {
+    Box::new(
+        sql::<Float4>(&format!("(ts_rank_cd(search_vector, websearch_to_tsquery('{}', ", TS_CONFIG))
+            .bind::<Text, _>(text.to_string())
+            .sql("), 32) + CASE WHEN lower(model_number) = ANY(")
+            .bind::<Array<Text>, _>(query_tokens(text))
+            .sql(&format!(") THEN {} ELSE 0 END)::real", MODEL_NUMBER_BOOST)),
+    )
+}
+
+/// Name and description with the query's matches highlighted. The source
+/// text is HTML-escaped first so that only the highlight markers are markup.
+fn snippet(text: &str) -> TextExpression<Text> {
+    Box::new(
+        sql::<Text>(&format!(
+            "ts_headline('{}', \
+                 replace(replace(replace(concat_ws('. ', name, description), \
+                     '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), \
+                 websearch_to_tsquery('{}', ",
+            TS_CONFIG, TS_CONFIG,
+        ))
+        .bind::<Text, _>(text.to_string())
+        .sql(&format!("), '{}')", HEADLINE_OPTIONS)),
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Facet {
+    Manufacturer,
+    Condition,
+}
+
+/// Applies every filter of `search` except the one for `skip`. Facet counts
+/// ignore their own filter so clients can offer the other values as
+/// alternatives rather than showing them as zero.
+fn filtered(
+    search: &EquipmentSearch,
+    categories: Option<&[i32]>,
+    skip: Option<Facet>,
+) -> equipment::BoxedQuery<'static, Pg> {
+    let mut query = equipment::table.into_boxed();
+
+    if let Some(text) = &search.text {
+        query = query.filter(
+            sql::<Bool>(&format!("(search_vector @@ websearch_to_tsquery('{}', ", TS_CONFIG))
+                .bind::<Text, _>(text.clone())
+                .sql(") OR lower(model_number) = ANY(")
+                .bind::<Array<Text>, _>(query_tokens(text))
+                .sql("))"),
+        );
+    }
+    if let Some(ids) = categories {
+        query = query.filter(equipment::category_id.eq_any(ids.to_vec()));
+    }
+    if !search.manufacturers.is_empty() && skip != Some(Facet::Manufacturer) {
+        query = query.filter(equipment::manufacturer.eq_any(search.manufacturers.clone()));
+    }
+    if !search.conditions.is_empty() && skip != Some(Facet::Condition) {
+        query = query.filter(equipment::condition.eq_any(search.conditions.clone()));
+    }
+    if let Some(min) = &search.min_price {
+        query = query.filter(equipment::price.ge(min.clone()));
+    }
+    if let Some(max) = &search.max_price {
+        query = query.filter(equipment::price.le(max.clone()));
+    }
+    if let Some(min) = search.min_year {
+        query = query.filter(equipment::year_manufactured.ge(min));
+    }
+    if let Some(max) = search.max_year {
+        query = query.filter(equipment::year_manufactured.le(max));
+    }
+    if let Some(max) = &search.max_weight_kg {
+        query = query.filter(equipment::weight_kg.le(max.clone()));
+    }
+    if let Some(max) = &search.max_length_cm {
+        query = query.filter(equipment::length_cm.le(max.clone()));
+    }
+    if let Some(max) = &search.max_width_cm {
+        query = query.filter(equipment::width_cm.le(max.clone()));
+    }
+    if let Some(max) = &search.max_height_cm {
+        query = query.filter(equipment::height_cm.le(max.clone()));
+    }
+    match search.in_stock {
+        Some(true) => query = query.filter(equipment::stock_level.gt(0)),
+        Some(false) => query = query.filter(equipment::stock_level.le(0)),
+        None => {}
+    }
+    for spec in &search.specs {
+        query = match spec {
+            // Containment can use the GIN index on `specifications`
+            SpecFilter::Matches { key, values, negated } => {
+                let documents: Vec<serde_json::Value> = values
+                    .iter()
+                    .map(|value| serde_json::json!({ key.as_str(): value }))
+                    .collect();
+                let (open, close) = if *negated { ("NOT coalesce(", ", false)") } else { ("(", ")") };
+                query.filter(
+                    sql::<Bool>(&format!("{}specifications @> ANY(", open))
+                        .bind::<Array<Jsonb>, _>(documents)
+                        .sql(&format!("){}", close)),
+                )
+            }
+            // CASE rather than AND so the cast is never attempted on
+            // non-numeric values
+            SpecFilter::Compare { key, op, value } => query.filter(
+                sql::<Bool>("CASE WHEN jsonb_typeof(specifications -> ")
+                    .bind::<Text, _>(key.clone())
+                    .sql(") = 'number' THEN (specifications ->> ")
+                    .bind::<Text, _>(key.clone())
+                    .sql(&format!(")::numeric {} ", op.sql()))
+                    .bind::<Numeric, _>(value.clone())
+                    .sql(" ELSE false END"),
+            ),
+        };
+    }
+
+    query
+}
+
+fn sorted(query: equipment::BoxedQuery<'static, Pg>, search: &EquipmentSearch) -> equipment::BoxedQuery<'static, Pg> {
+    match search.sort {
+        SearchSort::Relevance => match &search.text {
+            Some(text) => query.order((rank(text).desc(), equipment::id.desc())),
+            None => query.order((equipment::created_at.desc(), equipment::id.desc())),
+        },
+        SearchSort::Newest => query.order((equipment::created_at.desc(), equipment::id.desc())),
+        SearchSort::PriceAsc => query.order((equipment::price.asc(), equipment::id.desc())),
+        SearchSort::PriceDesc => query.order((equipment::price.desc(), equipment::id.desc())),
+        SearchSort::YearAsc => query.order((equipment::year_manufactured.asc().nulls_last(), equipment::id.desc())),
+        SearchSort::YearDesc => query.order((equipment::year_manufactured.desc().nulls_last(), equipment::id.desc())),
+        SearchSort::NameAsc => query.order((equipment::name.asc(), equipment::id.desc())),
+    }
+}
+
+/// Attaches rank and snippet to a page of results. Snippets are costly, so
+/// they are only computed for the rows being returned.
+fn with_text_matches(
+    conn: &mut PgConnection,
+    search: &EquipmentSearch,
+    items: Vec<EquipmentResponse>,
+) -> QueryResult<Vec<SearchHit>> {
+    let mut matches: HashMap<i32, TextMatch> = match &search.text {
+        Some(text) if !items.is_empty() => {
+            let ids: Vec<i32> = items.iter().map(|item| item.equipment.id).collect();
+            equipment::table
+                .filter(equipment::id.eq_any(ids))
+                .select((equipment::id, rank(text), snippet(text)))
+                .load::<TextMatch>(conn)?
+                .into_iter()
+                .map(|row| (row.id, row))
+                .collect()
+        }
+        _ => HashMap::new(),
+    };
+
+    Ok(items
+        .into_iter()
+        .map(|equipment| {
+            let text_match = matches.remove(&equipment.equipment.id);
+            SearchHit {
+                rank: text_match.as_ref().map(|m| m.rank),
+                snippet: text_match.map(|m| m.snippet),
+                equipment,
+            }
+        })
+        .collect())
+}
+
+/// One page of matching equipment plus facet counts over all matches
+pub fn search_equipment(
+    pool: &crate::db::DbPool,
+    search: &EquipmentSearch,
+    pagination: Pagination,
+) -> Result<SearchResults, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    let run = |conn: &mut PgConnection| -> QueryResult<SearchResults> {
+        let categories = category_scope(conn, search.category_id, search.include_subcategories)?;
+        let categories = categories.as_deref();
+
+        let total = filtered(search, categories, None).count().get_result(conn)?;
+        let items = sorted(filtered(search, categories, None), search)
+            .limit(pagination.per_page)
+            .offset(pagination.offset())
+            .load(conn)?;
+        let items = with_details(conn, items)?;
+        let items = with_text_matches(conn, search, items)?;
+
+        let manufacturer = equipment::table
+            .filter(equipment::id.eq_any(
+                filtered(search, categories, Some(Facet::Manufacturer)).select(equipment::id)
+            ))
+            .group_by(equipment::manufacturer)
+            .select((equipment::manufacturer, count_star()))
+            .order((count_star().desc(), equipment::manufacturer.asc()))
+            .limit(MAX_FACET_VALUES)
+            .load::<(String, i64)>(conn)?;
+        let condition = equipment::table
+            .filter(equipment::id.eq_any(
+                filtered(search, categories, Some(Facet::Condition)).select(equipment::id)
+            ))
+            .group_by(equipment::condition)
+            .select((equipment::condition, count_star()))
+            .order((count_star().desc(), equipment::condition.asc()))
+            .limit(MAX_FACET_VALUES)
+            .load::<(String, i64)>(conn)?;
+
+        let to_counts = |rows: Vec<(String, i64)>| {
+            rows.into_iter().map(|(value, count)| FacetCount { value, count }).collect()
+        };
+
+        Ok(SearchResults {
+            page: Page::new(items, pagination, total),
+            facets: SearchFacets {
+                manufacturer: to_counts(manufacturer),
+                condition: to_counts(condition),
+            },
+        })
+    };
+
+    // A single snapshot keeps the page, total and facets consistent
+    conn.build_transaction()
+        .read_only()
+        .repeatable_read()
+        .run(run)
+        .map_err(|error| {
+            error!("Failed to search equipment: {:?}", error);
+            ServiceError::DatabaseError(error.to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spec_filters() {
+        assert_eq!(
+            "payload_t>=300".parse::<SpecFilter>().unwrap(),
+            SpecFilter::Compare { key: "payload_t".into(), op: SpecOp::Ge, value: BigDecimal::from(300) }
+        );
+        assert_eq!(
+            "drive = electric".parse::<SpecFilter>().unwrap(),
+            SpecFilter::Matches { key: "drive".into(), values: vec!["electric".into()], negated: false }
+        );
+        assert_eq!(
+            "axles!=2".parse::<SpecFilter>().unwrap(),
+            SpecFilter::Matches { key: "axles".into(), values: vec!["2".into(), 2.into()], negated: true }
+        );
+
+        for invalid in ["payload_t", ">=300", "payload_t>=", "payload_t>heavy", "payload_t=>3"] {
+            assert!(invalid.parse::<SpecFilter>().is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashSet;
+use diesel::prelude::*;
+use crate::db::categories::ancestor_ids;
+use crate::errors::{codes, ServiceError};
+use crate::models::{NewSpecAttribute, SpecAttribute};
+use crate::schema::{category_spec_attributes, equipment_categories};
+use log::error;
+
+/// Attributes applying to a category: its own plus those inherited from its
+/// ancestors. A subcategory may redefine an inherited attribute by name.
+/// Ordered by name; empty for unknown categories.
+pub(crate) fn effective_schema(conn: &mut PgConnection, category_id: i32) -> QueryResult<Vec<SpecAttribute>> {
+    let path = ancestor_ids(conn, category_id)?;
+    let mut attributes: Vec<SpecAttribute> = category_spec_attributes::table
+        .filter(category_spec_attributes::category_id.eq_any(&path))
+        .load(conn)?;
+
+    // Nearest definition first, then keep one per name
+    let depth = |attribute: &SpecAttribute| path.iter().position(|id| *id == attribute.category_id);
+    attributes.sort_by_key(|attribute| depth(attribute));
+    let mut seen = HashSet::new();
+    attributes.retain(|attribute| seen.insert(attribute.name.clone()));
+    attributes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(attributes)
+}
+
+/// The schema listings in `category_id` are validated against. Unknown
+/// categories have an empty schema; the insert itself reports them.
+pub fn schema_for_listing(pool: &crate::db::DbPool, category_id: i32) -> Result<Vec<SpecAttribute>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    effective_schema(conn, category_id).map_err(|error| {
+        error!("Failed to load specification schema: {:?}", error);
+        ServiceError::DatabaseError(error.to_string())
+    })
+}
+
+pub fn get_spec_schema(pool: &crate::db::DbPool, category_id: i32) -> Result<Vec<SpecAttribute>, ServiceError> {
+    // Distinguishes an unknown category from one without attributes
+    crate::db::categories::get_category_by_id(pool, category_id)?;
+    schema_for_listing(pool, category_id)
+}
+
+/// Replaces the attributes a category defines itself and returns its new
+/// effective schema. Existing listings are not revalidated.
+pub fn replace_spec_schema(
+    pool: &crate::db::DbPool,
+    category_id: i32,
+    attributes: Vec<NewSpecAttribute>,
+) -> Result<Vec<SpecAttribute>, ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        // Locks the category so concurrent replacements apply one at a time
+        equipment_categories::table
+            .find(category_id)
+            .select(equipment_categories::id)
+            .for_update()
+            .first::<i32>(conn)
+            .optional()?
+            .ok_or_else(|| {
+                ServiceError::NotFound(format!("Category {} not found", category_id))
+                    .with_code(codes::CATEGORY_NOT_FOUND)
+            })?;
+
+        diesel::delete(
+            category_spec_attributes::table.filter(category_spec_attributes::category_id.eq(category_id))
+        )
+        .execute(conn)?;
+        diesel::insert_into(category_spec_attributes::table)
+            .values(&attributes)
+            .execute(conn)?;
+
+        Ok(effective_schema(conn, category_id)?)
+    })
+    .map_err(|error: ServiceError| {
+        if matches!(error.root(), ServiceError::DatabaseError(_)) {
+            error!("Failed to replace specification schema: {}", error);
+        }
+        error
+    })
+}
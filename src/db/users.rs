@@ -1,9 +1,63 @@
 use diesel::prelude::*;
+use diesel::pg::Pg;
 use chrono::Utc;
-use crate::models::{NewUser, User};
-use crate::errors::ServiceError;
+use crate::db::contains_pattern;
+use crate::models::{NewUser, User, UserChangeset, UserRole};
+use crate::errors::{codes, ServiceError};
+use crate::pagination::Pagination;
+use crate::schema::users;
 use log::error;
 
+/// Optional filters for listing users
+#[derive(Debug, Default, Clone)]
+pub struct UserFilter {
+    /// Case-insensitive substring match on username or email
+    pub search: Option<String>,
+    pub business_type: Option<UserRole>,
+    pub company_name: Option<String>,
+    /// Also return deactivated accounts
+    pub include_deleted: bool,
+}
+
+fn filtered_users(filter: &UserFilter) -> users::BoxedQuery<'static, Pg> {
+    let mut query = users::table.into_boxed();
+
+    if !filter.include_deleted {
+        query = query.filter(users::deleted_at.is_null());
+    }
+
+    if let Some(search) = &filter.search {
+        let pattern = contains_pattern(search);
+        query = query.filter(
+            users::username.ilike(pattern.clone()).escape('\\')
+                .or(users::email.ilike(pattern).escape('\\'))
+        );
+    }
+    if let Some(role) = filter.business_type {
+        query = query.filter(users::business_type.eq(role));
+    }
+    if let Some(company) = &filter.company_name {
+        query = query.filter(users::company_name.ilike(contains_pattern(company)).escape('\\'));
+    }
+
+    query
+}
+
+/// Maps a unique violation on `users` to a conflict naming the taken field
+fn taken(info: &dyn diesel::result::DatabaseErrorInformation) -> ServiceError {
+    match info.constraint_name() {
+        Some("users_email_key") => ServiceError::Conflict("Email is already taken".into())
+            .with_code(codes::USER_EMAIL_TAKEN),
+        Some("users_username_key") => ServiceError::Conflict("Username is already taken".into())
+            .with_code(codes::USER_USERNAME_TAKEN),
+        _ => ServiceError::Conflict("Username or email is already taken".into()),
+    }
+}
+
+fn user_not_found(user_id: i32) -> ServiceError {
+    ServiceError::NotFound(format!("User {} not found", user_id)).with_code(codes::USER_NOT_FOUND)
+}
+
 pub fn create_user(pool: &crate::db::DbPool, new_user: NewUser) -> Result<User, ServiceError> {
     use crate::schema::users::dsl::*;
     
@@ -20,17 +74,14 @@ pub fn create_user(pool: &crate::db::DbPool, new_user: NewUser) -> Result<User,
             match error {
                 diesel::result::Error::DatabaseError(
                     diesel::result::DatabaseErrorKind::UniqueViolation,
-                    _
-                ) => ServiceError::Conflict(format!(
-                    "User with username '{}' or email '{}' already exists",
-                    new_user.username,
-                    new_user.email
-                )),
+                    info
+                ) => taken(info.as_ref()),
                 _ => ServiceError::DatabaseError(error.to_string())
             }
         })
 }
 
+/// Looks up an active user; deactivated accounts are reported as not found
 pub fn get_user_by_id(pool: &crate::db::DbPool, user_id: i32) -> Result<User, ServiceError> {
     use crate::schema::users::dsl::*;
     
@@ -40,10 +91,16 @@ pub fn get_user_by_id(pool: &crate::db::DbPool, user_id: i32) -> Result<User, Se
     
     users
         .find(user_id)
+        .filter(deleted_at.is_null())
         .first(conn)
-        .map_err(|error| {
-            error!("Failed to get user by id: {:?}", error);
-            ServiceError::DatabaseError(error.to_string())
+        .map_err(|error| match error {
+            diesel::result::Error::NotFound => {
+                user_not_found(user_id)
+            }
+            _ => {
+                error!("Failed to get user by id: {:?}", error);
+                ServiceError::DatabaseError(error.to_string())
+            }
         })
 }
 
@@ -56,6 +113,7 @@ pub fn get_user_by_email(pool: &crate::db::DbPool, user_email: &str) -> Result<U
     
     users
         .filter(email.eq(user_email))
+        .filter(deleted_at.is_null())
         .first(conn)
         .map_err(|error| {
             error!("Failed to get user by email: {:?}", error);
@@ -63,27 +121,88 @@ pub fn get_user_by_email(pool: &crate::db::DbPool, user_email: &str) -> Result<U
         })
 }
 
-pub fn update_user(pool: &crate::db::DbPool, user_id: i32, updated_user: NewUser) -> Result<User, ServiceError> {
+pub fn find_user_by_email(pool: &crate::db::DbPool, user_email: &str) -> Result<Option<User>, ServiceError> {
     use crate::schema::users::dsl::*;
-    
+
     let conn = &mut pool.get().map_err(|e| {
         ServiceError::DatabaseError(format!("Connection error: {}", e))
     })?;
-    
+
+    users
+        .filter(email.eq(user_email))
+        .filter(deleted_at.is_null())
+        .first(conn)
+        .optional()
+        .map_err(|error| {
+            error!("Failed to find user by email: {:?}", error);
+            ServiceError::DatabaseError(error.to_string())
+        })
+}
+
+/// Stores a fresh hash of the user's current password, as when upgrading
+/// its parameters. The password is unchanged, so sessions stay valid.
+pub fn update_password_hash(pool: &crate::db::DbPool, user_id: i32, new_hash: &str) -> Result<User, ServiceError> {
+    use crate::schema::users::dsl::*;
+
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
     diesel::update(users.find(user_id))
         .set((
-            username.eq(updated_user.username),
-            email.eq(updated_user.email),
-            password_hash.eq(updated_user.password_hash),
+            password_hash.eq(new_hash),
             updated_at.eq(Utc::now().naive_utc()),
         ))
         .get_result(conn)
         .map_err(|error| {
-            error!("Failed to update user: {:?}", error);
+            error!("Failed to update password hash: {:?}", error);
             ServiceError::DatabaseError(error.to_string())
         })
 }
 
+/// Applies the changes to an active user. A new password invalidates the
+/// access tokens issued before it.
+pub fn update_user(pool: &crate::db::DbPool, user_id: i32, changes: &UserChangeset) -> Result<User, ServiceError> {
+    use crate::schema::users::dsl::*;
+    
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+    
+    conn.transaction(|conn| {
+        // A new address has to be verified again
+        if let Some(new_email) = &changes.email {
+            diesel::update(users.find(user_id).filter(email.ne(new_email)))
+                .set(email_verified_at.eq(None::<chrono::NaiveDateTime>))
+                .execute(conn)?;
+        }
+
+        let password_changed = i32::from(changes.password_hash.is_some());
+        diesel::update(users.find(user_id).filter(deleted_at.is_null()))
+            .set((
+                changes,
+                token_version.eq(token_version + password_changed),
+                updated_at.eq(Utc::now().naive_utc()),
+            ))
+            .get_result(conn)
+    })
+    .map_err(|error| {
+        error!("Failed to update user: {:?}", error);
+        match error {
+            diesel::result::Error::NotFound => {
+                user_not_found(user_id)
+            }
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                info
+            ) => taken(info.as_ref()),
+            _ => ServiceError::DatabaseError(error.to_string())
+        }
+    })
+}
+
+/// Deactivates an account. The row is kept so that orders and reviews
+/// referencing it stay intact; the user can no longer log in or be looked up.
 pub fn delete_user(pool: &crate::db::DbPool, user_id: i32) -> Result<(), ServiceError> {
     use crate::schema::users::dsl::*;
     
@@ -91,12 +210,132 @@ pub fn delete_user(pool: &crate::db::DbPool, user_id: i32) -> Result<(), Service
         ServiceError::DatabaseError(format!("Connection error: {}", e))
     })?;
     
-    diesel::delete(users.find(user_id))
+    let now = Utc::now().naive_utc();
+    let count = diesel::update(users.find(user_id).filter(deleted_at.is_null()))
+        .set((deleted_at.eq(now), updated_at.eq(now)))
         .execute(conn)
         .map_err(|error| {
             error!("Failed to delete user: {:?}", error);
             ServiceError::DatabaseError(error.to_string())
         })?;
-    
+
+    if count == 0 {
+        return Err(user_not_found(user_id));
+    }
     Ok(())
 }
+
+/// Irreversibly replaces the personal data of an account with placeholders
+/// and deactivates it. Outstanding email tokens are discarded.
+pub fn anonymize_user(pool: &crate::db::DbPool, user_id: i32) -> Result<User, ServiceError> {
+    use crate::schema::users::dsl::*;
+    use crate::schema::{email_verification_tokens, password_reset_tokens};
+
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    let now = Utc::now().naive_utc();
+    conn.transaction(|conn| {
+        diesel::delete(email_verification_tokens::table.filter(email_verification_tokens::user_id.eq(user_id)))
+            .execute(conn)?;
+        diesel::delete(password_reset_tokens::table.filter(password_reset_tokens::user_id.eq(user_id)))
+            .execute(conn)?;
+
+        diesel::update(users.find(user_id).filter(anonymized_at.is_null()))
+            .set((
+                username.eq(format!("deleted_user_{}", user_id)),
+                email.eq(format!("deleted_user_{}@deleted.invalid", user_id)),
+                // Not a valid PHC string, so no password can ever match it
+                password_hash.eq("!"),
+                company_name.eq(None::<String>),
+                contact_number.eq(None::<String>),
+                email_verified_at.eq(None::<chrono::NaiveDateTime>),
+                // Accounts deactivated earlier keep their deactivation time
+                deleted_at.eq(
+                    diesel::dsl::sql::<diesel::sql_types::Nullable<diesel::sql_types::Timestamp>>("COALESCE(deleted_at, ")
+                        .bind::<diesel::sql_types::Timestamp, _>(now)
+                        .sql(")")
+                ),
+                anonymized_at.eq(now),
+                updated_at.eq(now),
+            ))
+            .get_result(conn)
+    })
+    .map_err(|error| match error {
+        diesel::result::Error::NotFound => {
+            user_not_found(user_id)
+        }
+        _ => {
+            error!("Failed to anonymize user: {:?}", error);
+            ServiceError::DatabaseError(error.to_string())
+        }
+    })
+}
+
+/// Reactivates a deactivated account. Anonymized accounts cannot be restored.
+pub fn restore_user(pool: &crate::db::DbPool, user_id: i32) -> Result<User, ServiceError> {
+    use crate::schema::users::dsl::*;
+
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    conn.transaction(|conn| {
+        let user: User = users.find(user_id).for_update().first(conn)?;
+        if user.anonymized_at.is_some() {
+            return Ok(Err(ServiceError::Conflict("Anonymized accounts cannot be restored".into())
+                .with_code(codes::USER_ANONYMIZED)));
+        }
+        if user.is_active() {
+            return Ok(Err(ServiceError::Conflict(format!("User {} is not deactivated", user_id))
+                .with_code(codes::USER_NOT_DEACTIVATED)));
+        }
+
+        diesel::update(users.find(user_id))
+            .set((deleted_at.eq(None::<chrono::NaiveDateTime>), updated_at.eq(Utc::now().naive_utc())))
+            .get_result(conn)
+            .map(Ok)
+    })
+    .map_err(|error| match error {
+        diesel::result::Error::NotFound => {
+            user_not_found(user_id)
+        }
+        _ => {
+            error!("Failed to restore user: {:?}", error);
+            ServiceError::DatabaseError(error.to_string())
+        }
+    })?
+}
+
+/// Returns one page of users matching `filter`, ordered by id, along with
+/// the total number of matches
+pub fn list_users(
+    pool: &crate::db::DbPool,
+    filter: &UserFilter,
+    pagination: Pagination,
+) -> Result<(Vec<User>, i64), ServiceError> {
+    let conn = &mut pool.get().map_err(|e| {
+        ServiceError::DatabaseError(format!("Connection error: {}", e))
+    })?;
+
+    let total = filtered_users(filter)
+        .count()
+        .get_result(conn)
+        .map_err(|error| {
+            error!("Failed to count users: {:?}", error);
+            ServiceError::DatabaseError(error.to_string())
+        })?;
+
+    let page = filtered_users(filter)
+        .order(users::id.asc())
+        .limit(pagination.per_page)
+        .offset(pagination.offset())
+        .load(conn)
+        .map_err(|error| {
+            error!("Failed to list users: {:?}", error);
+            ServiceError::DatabaseError(error.to_string())
+        })?;
+
+    Ok((page, total))
+}
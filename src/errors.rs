@@ -1,7 +1,87 @@
 use thiserror::Error;
 use actix_web::{HttpResponse, ResponseError, http::StatusCode};
-use log::error;
+use log::{error, warn};
+use serde::Serialize;
 use serde_json::json;
+use crate::request_id;
+
+/// Stable, machine-readable error codes. Clients branch on these, so existing
+/// values must never change meaning.
+pub mod codes {
+    pub const DATABASE_ERROR: &str = "DATABASE_ERROR";
+    pub const SERVICE_UNAVAILABLE: &str = "SERVICE_UNAVAILABLE";
+    pub const VALIDATION_FAILED: &str = "VALIDATION_FAILED";
+    pub const NOT_FOUND: &str = "NOT_FOUND";
+    pub const UNAUTHORIZED: &str = "UNAUTHORIZED";
+    pub const FORBIDDEN: &str = "FORBIDDEN";
+    pub const BAD_REQUEST: &str = "BAD_REQUEST";
+    pub const INTERNAL_ERROR: &str = "INTERNAL_ERROR";
+    pub const CONFLICT: &str = "CONFLICT";
+
+    pub const INVALID_JSON: &str = "INVALID_JSON";
+    pub const INVALID_QUERY: &str = "INVALID_QUERY";
+    pub const INVALID_PATH: &str = "INVALID_PATH";
+
+    pub const AUTH_INVALID_CREDENTIALS: &str = "AUTH_INVALID_CREDENTIALS";
+    pub const AUTH_TOKEN_MISSING: &str = "AUTH_TOKEN_MISSING";
+    pub const AUTH_TOKEN_INVALID: &str = "AUTH_TOKEN_INVALID";
+    pub const ACCOUNT_TOKEN_INVALID: &str = "ACCOUNT_TOKEN_INVALID";
+    pub const EMAIL_ALREADY_VERIFIED: &str = "EMAIL_ALREADY_VERIFIED";
+
+    pub const USER_NOT_FOUND: &str = "USER_NOT_FOUND";
+    pub const USER_EMAIL_TAKEN: &str = "USER_EMAIL_TAKEN";
+    pub const USER_USERNAME_TAKEN: &str = "USER_USERNAME_TAKEN";
+    pub const USER_NOT_DEACTIVATED: &str = "USER_NOT_DEACTIVATED";
+    pub const USER_ANONYMIZED: &str = "USER_ANONYMIZED";
+
+    pub const CATEGORY_NOT_FOUND: &str = "CATEGORY_NOT_FOUND";
+    pub const CATEGORY_NAME_TAKEN: &str = "CATEGORY_NAME_TAKEN";
+    pub const CATEGORY_CYCLE: &str = "CATEGORY_CYCLE";
+    pub const CATEGORY_IN_USE: &str = "CATEGORY_IN_USE";
+
+    pub const EQUIPMENT_NOT_FOUND: &str = "EQUIPMENT_NOT_FOUND";
+    pub const EQUIPMENT_IN_USE: &str = "EQUIPMENT_IN_USE";
+    pub const CSV_INVALID: &str = "CSV_INVALID";
+
+    pub const IMAGE_NOT_FOUND: &str = "IMAGE_NOT_FOUND";
+    pub const IMAGE_INVALID: &str = "IMAGE_INVALID";
+    pub const IMAGE_TOO_LARGE: &str = "IMAGE_TOO_LARGE";
+    pub const IMAGE_UNSUPPORTED_TYPE: &str = "IMAGE_UNSUPPORTED_TYPE";
+    pub const IMAGE_LIMIT_REACHED: &str = "IMAGE_LIMIT_REACHED";
+
+    pub const DOCUMENT_NOT_FOUND: &str = "DOCUMENT_NOT_FOUND";
+    pub const DOCUMENT_INVALID: &str = "DOCUMENT_INVALID";
+    pub const DOCUMENT_TOO_LARGE: &str = "DOCUMENT_TOO_LARGE";
+    pub const DOCUMENT_UNSUPPORTED_TYPE: &str = "DOCUMENT_UNSUPPORTED_TYPE";
+
+    pub const ORDER_NOT_FOUND: &str = "ORDER_NOT_FOUND";
+    pub const ORDER_INVALID_TRANSITION: &str = "ORDER_INVALID_TRANSITION";
+    pub const ORDER_ITEM_NOT_FOUND: &str = "ORDER_ITEM_NOT_FOUND";
+    pub const ORDER_ITEM_CANCELLED: &str = "ORDER_ITEM_CANCELLED";
+    pub const INSUFFICIENT_STOCK: &str = "INSUFFICIENT_STOCK";
+
+    pub const CART_ITEM_NOT_FOUND: &str = "CART_ITEM_NOT_FOUND";
+    pub const CART_LIMIT_REACHED: &str = "CART_LIMIT_REACHED";
+    pub const CART_EMPTY: &str = "CART_EMPTY";
+    pub const CART_CHANGED: &str = "CART_CHANGED";
+}
+
+/// Sent instead of the real message for 5xx responses, which may contain
+/// SQL, constraint names or other internals
+const SANITIZED_MESSAGE: &str = "An unexpected error occurred";
+
+/// A validation failure tied to one input field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub reason: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self { field: field.into(), reason: reason.into() }
+    }
+}
 
 #[derive(Error, Debug)]
 pub enum ServiceError {
@@ -11,16 +91,114 @@ pub enum ServiceError {
     ConnectionError(#[from] diesel::r2d2::Error),
     #[error("Validation error: {0}")]
     ValidationError(String),
+    #[error("Validation error: {}", join_reasons(.0))]
+    InvalidFields(Vec<FieldError>),
     #[error("Not found: {0}")]
     NotFound(String),
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
     #[error("Bad request: {0}")]
     BadRequest(String),
     #[error("Internal server error: {0}")]
     InternalServerError(String),
     #[error("Conflict: {0}")]
     Conflict(String),
+    /// Another error with a more specific code; see `ServiceError::with_code`
+    #[error("{source}")]
+    Coded {
+        code: &'static str,
+        source: Box<ServiceError>,
+    },
+}
+
+fn join_reasons(fields: &[FieldError]) -> String {
+    fields
+        .iter()
+        .map(|f| f.reason.as_str())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl ServiceError {
+    /// Replaces the generic code of this error with a more specific one,
+    /// keeping its status and message
+    pub fn with_code(self, code: &'static str) -> Self {
+        match self {
+            ServiceError::Coded { source, .. } => ServiceError::Coded { code, source },
+            other => ServiceError::Coded { code, source: Box::new(other) },
+        }
+    }
+
+    /// A validation error for a single field
+    pub fn invalid_field(field: impl Into<String>, reason: impl Into<String>) -> Self {
+        ServiceError::InvalidFields(vec![FieldError::new(field, reason)])
+    }
+
+    /// The underlying error, without any code override
+    pub fn root(&self) -> &ServiceError {
+        match self {
+            ServiceError::Coded { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ServiceError::DatabaseError(_) => codes::DATABASE_ERROR,
+            ServiceError::ConnectionError(_) => codes::SERVICE_UNAVAILABLE,
+            ServiceError::ValidationError(_) | ServiceError::InvalidFields(_) => codes::VALIDATION_FAILED,
+            ServiceError::NotFound(_) => codes::NOT_FOUND,
+            ServiceError::Unauthorized(_) => codes::UNAUTHORIZED,
+            ServiceError::Forbidden(_) => codes::FORBIDDEN,
+            ServiceError::BadRequest(_) => codes::BAD_REQUEST,
+            ServiceError::InternalServerError(_) => codes::INTERNAL_ERROR,
+            ServiceError::Conflict(_) => codes::CONFLICT,
+            ServiceError::Coded { code, .. } => code,
+        }
+    }
+
+    /// Field-level details, empty unless this is an `InvalidFields` error
+    pub fn details(&self) -> &[FieldError] {
+        match self.root() {
+            ServiceError::InvalidFields(fields) => fields,
+            _ => &[],
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self.root() {
+            ServiceError::DatabaseError(_) => "Database Error",
+            ServiceError::ConnectionError(_) => "Service Unavailable",
+            ServiceError::ValidationError(_) | ServiceError::InvalidFields(_) => "Validation Error",
+            ServiceError::NotFound(_) => "Not Found",
+            ServiceError::Unauthorized(_) => "Unauthorized",
+            ServiceError::Forbidden(_) => "Forbidden",
+            ServiceError::BadRequest(_) => "Bad Request",
+            ServiceError::InternalServerError(_) => "Internal Server Error",
+            ServiceError::Conflict(_) => "Conflict",
+            ServiceError::Coded { .. } => unreachable!("root() never returns Coded"),
+        }
+    }
+
+    /// The message shown to clients; server errors are never passed through
+    pub fn public_message(&self) -> String {
+        match self.root() {
+            ServiceError::DatabaseError(_) | ServiceError::InternalServerError(_) => {
+                SANITIZED_MESSAGE.to_string()
+            }
+            ServiceError::ConnectionError(_) => "Database connection error".to_string(),
+            ServiceError::InvalidFields(fields) => join_reasons(fields),
+            ServiceError::ValidationError(msg)
+            | ServiceError::NotFound(msg)
+            | ServiceError::Unauthorized(msg)
+            | ServiceError::Forbidden(msg)
+            | ServiceError::BadRequest(msg)
+            | ServiceError::Conflict(msg) => msg.clone(),
+            ServiceError::Coded { .. } => unreachable!("root() never returns Coded"),
+        }
+    }
 }
 
 impl From<diesel::result::Error> for ServiceError {
@@ -29,71 +207,57 @@ impl From<diesel::result::Error> for ServiceError {
             diesel::result::Error::DatabaseError(
                 diesel::result::DatabaseErrorKind::UniqueViolation,
                 info
-            ) => ServiceError::Conflict(info.message().to_string()),
+            ) => {
+                // The constraint text names tables and columns, so it stays in the log
+                warn!("Unique violation: {}", info.message());
+                ServiceError::Conflict("Resource already exists".into()).with_code(codes::CONFLICT)
+            }
             _ => ServiceError::DatabaseError(error.to_string()),
         }
     }
 }
 
 impl ResponseError for ServiceError {
-    fn error_response(&self) -> HttpResponse {
-        match self {
-            ServiceError::DatabaseError(msg) => {
-                error!("Database error: {}", msg);
-                HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR).json(json!({
-                    "error": "Database Error",
-                    "message": msg
-                }))
-            }
-            ServiceError::ConnectionError(err) => {
-                error!("Database connection error: {}", err);
-                HttpResponse::build(StatusCode::SERVICE_UNAVAILABLE).json(json!({
-                    "error": "Service Unavailable",
-                    "message": "Database connection error"
-                }))
-            }
-            ServiceError::ValidationError(msg) => {
-                error!("Validation error: {}", msg);
-                HttpResponse::build(StatusCode::BAD_REQUEST).json(json!({
-                    "error": "Validation Error",
-                    "message": msg
-                }))
-            }
-            ServiceError::NotFound(msg) => {
-                error!("Not found: {}", msg);
-                HttpResponse::build(StatusCode::NOT_FOUND).json(json!({
-                    "error": "Not Found",
-                    "message": msg
-                }))
-            }
-            ServiceError::Unauthorized(msg) => {
-                error!("Unauthorized: {}", msg);
-                HttpResponse::build(StatusCode::UNAUTHORIZED).json(json!({
-                    "error": "Unauthorized",
-                    "message": msg
-                }))
-            }
-            ServiceError::BadRequest(msg) => {
-                error!("Bad request: {}", msg);
-                HttpResponse::build(StatusCode::BAD_REQUEST).json(json!({
-                    "error": "Bad Request",
-                    "message": msg
-                }))
-            }
-            ServiceError::InternalServerError(msg) => {
-                error!("Internal server error: {}", msg);
-                HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR).json(json!({
-                    "error": "Internal Server Error",
-                    "message": msg
-                }))
-            }
-            ServiceError::Conflict(msg) => {
-                error!("Conflict: {}", msg);
-                HttpResponse::build(StatusCode::CONFLICT).json(json!({
-                    "error": "Conflict",
-                    "message": msg
-                }))
+    fn status_code(&self) -> StatusCode {
+        match self.root() {
+            ServiceError::DatabaseError(_) | ServiceError::InternalServerError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
             }
+            ServiceError::ConnectionError(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ServiceError::ValidationError(_)
+            | ServiceError::InvalidFields(_)
+            | ServiceError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ServiceError::NotFound(_) => StatusCode::NOT_FOUND,
+            ServiceError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ServiceError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ServiceError::Conflict(_) => StatusCode::CONFLICT,
+            ServiceError::Coded { .. } => unreachable!("root() never returns Coded"),
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        let request_id = request_id::current();
+        let request_label = request_id.as_deref().unwrap_or("-");
+
+        if status.is_server_error() {
+            error!("[{}] {} ({})", request_label, self, self.code());
+        } else {
+            warn!("[{}] {} ({})", request_label, self, self.code());
+        }
+
+        let mut body = json!({
+            "error": self.title(),
+            "code": self.code(),
+            "message": self.public_message(),
+        });
+        if !self.details().is_empty() {
+            body["details"] = json!(self.details());
+        }
+        if let Some(id) = request_id {
+            body["request_id"] = json!(id);
+        }
+
+        HttpResponse::build(status).json(body)
+    }
 }
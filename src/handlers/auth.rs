@@ -0,0 +1,97 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use crate::auth::{self, account, AccountMailer, AuthenticatedUser, TokenConfig};
+use crate::db;
+use crate::errors::ServiceError;
+use crate::models::UserResponse;
+
+#[derive(Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+pub async fn login(
+    pool: web::Data<db::DbPool>,
+    tokens: web::Data<TokenConfig>,
+    credentials: web::Json<LoginRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let LoginRequest { email, password } = credentials.into_inner();
+
+    // Password verification (and a possible rehash) is CPU-bound
+    let user = web::block(move || auth::authenticate(&pool, &email, &password))
+        .await
+        .map_err(|e| ServiceError::InternalServerError(e.to_string()))??;
+
+    let token = tokens.issue(user.id, user.token_version)?;
+    Ok(HttpResponse::Ok().json(token))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ConfirmEmailRequest {
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ConfirmPasswordResetRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+/// Emails a fresh verification link to the current user
+pub async fn request_email_verification(
+    pool: web::Data<db::DbPool>,
+    mailer: web::Data<AccountMailer>,
+    user: AuthenticatedUser,
+) -> Result<impl Responder, ServiceError> {
+    let user = user.into_inner();
+    web::block(move || account::send_email_verification(&pool, &mailer, &user))
+        .await
+        .map_err(|e| ServiceError::InternalServerError(e.to_string()))??;
+
+    Ok(HttpResponse::Accepted().finish())
+}
+
+pub async fn confirm_email_verification(
+    pool: web::Data<db::DbPool>,
+    request: web::Json<ConfirmEmailRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let ConfirmEmailRequest { token } = request.into_inner();
+    let user = web::block(move || account::confirm_email_verification(&pool, &token))
+        .await
+        .map_err(|e| ServiceError::InternalServerError(e.to_string()))??;
+
+    Ok(HttpResponse::Ok().json(UserResponse::from(user)))
+}
+
+/// Always answers 202 so callers cannot probe which emails are registered
+pub async fn request_password_reset(
+    pool: web::Data<db::DbPool>,
+    mailer: web::Data<AccountMailer>,
+    request: web::Json<PasswordResetRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let PasswordResetRequest { email } = request.into_inner();
+    web::block(move || account::request_password_reset(&pool, &mailer, &email))
+        .await
+        .map_err(|e| ServiceError::InternalServerError(e.to_string()))??;
+
+    Ok(HttpResponse::Accepted().finish())
+}
+
+pub async fn confirm_password_reset(
+    pool: web::Data<db::DbPool>,
+    request: web::Json<ConfirmPasswordResetRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let ConfirmPasswordResetRequest { token, new_password } = request.into_inner();
+    // Hashing the new password is CPU-bound
+    web::block(move || account::confirm_password_reset(&pool, &token, &new_password))
+        .await
+        .map_err(|e| ServiceError::InternalServerError(e.to_string()))??;
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,82 @@
+use actix_web::{web, HttpResponse, Responder};
+use crate::auth::{Authorized, CanBuy, VerifiedBuyer};
+use crate::db;
+use crate::errors::ServiceError;
+use crate::models::{AddCartItemRequest, CheckoutRequest, UpdateCartItem};
+use crate::reservations::ReservationConfig;
+use crate::validation::Validated;
+
+/// The caller's cart, revalidated against current prices and stock
+pub async fn get_cart(
+    pool: web::Data<db::DbPool>,
+    buyer: Authorized<CanBuy>,
+) -> Result<impl Responder, ServiceError> {
+    let cart = db::carts::get_cart(&pool, buyer.id)?;
+    Ok(HttpResponse::Ok().json(cart))
+}
+
+pub async fn clear_cart(
+    pool: web::Data<db::DbPool>,
+    buyer: Authorized<CanBuy>,
+) -> Result<impl Responder, ServiceError> {
+    db::carts::clear_cart(&pool, buyer.id)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn add_cart_item(
+    pool: web::Data<db::DbPool>,
+    buyer: Authorized<CanBuy>,
+    payload: Validated<AddCartItemRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let cart = db::carts::add_item(&pool, buyer.id, &payload)?;
+    Ok(HttpResponse::Ok().json(cart))
+}
+
+pub async fn update_cart_item(
+    pool: web::Data<db::DbPool>,
+    buyer: Authorized<CanBuy>,
+    item_id: web::Path<i32>,
+    payload: Validated<UpdateCartItem>,
+) -> Result<impl Responder, ServiceError> {
+    let cart = db::carts::update_item(&pool, buyer.id, item_id.into_inner(), payload.into_inner())?;
+    Ok(HttpResponse::Ok().json(cart))
+}
+
+pub async fn remove_cart_item(
+    pool: web::Data<db::DbPool>,
+    buyer: Authorized<CanBuy>,
+    item_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    db::carts::remove_item(&pool, buyer.id, item_id.into_inner())?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Turns the caller's cart into an order, as `POST /orders` would place
+/// it, and empties the cart
+pub async fn checkout(
+    pool: web::Data<db::DbPool>,
+    buyer: Authorized<VerifiedBuyer>,
+    payload: Validated<CheckoutRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let order = db::carts::checkout(&pool, buyer.id, &payload)?;
+    Ok(HttpResponse::Created().json(order))
+}
+
+/// Holds the stock for every line of the caller's cart for the configured
+/// time, replacing any holds the caller already had
+pub async fn reserve_cart(
+    pool: web::Data<db::DbPool>,
+    buyer: Authorized<VerifiedBuyer>,
+    config: web::Data<ReservationConfig>,
+) -> Result<impl Responder, ServiceError> {
+    let reservations = db::reservations::reserve_cart(&pool, buyer.id, config.ttl)?;
+    Ok(HttpResponse::Ok().json(reservations))
+}
+
+pub async fn release_reservations(
+    pool: web::Data<db::DbPool>,
+    buyer: Authorized<CanBuy>,
+) -> Result<impl Responder, ServiceError> {
+    db::reservations::release_reservations(&pool, buyer.id)?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,144 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+use crate::auth::{AdminOnly, Authorized};
+use crate::db::{self, equipment::EquipmentFilter};
+use crate::errors::ServiceError;
+use crate::models::{CategoryChangeset, CategoryRequest, SpecSchemaRequest, UpdateCategory};
+use crate::pagination::{Page, Pagination};
+use crate::units::UnitSystem;
+use crate::validation::{Validate, Validated};
+
+#[derive(Debug, Deserialize)]
+pub struct CategoryEquipmentQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub units: Option<UnitSystem>,
+}
+
+pub async fn list_categories(pool: web::Data<db::DbPool>) -> Result<impl Responder, ServiceError> {
+    let categories = db::categories::list_categories(&pool)?;
+    Ok(HttpResponse::Ok().json(categories))
+}
+
+pub async fn category_tree(pool: web::Data<db::DbPool>) -> Result<impl Responder, ServiceError> {
+    let tree = db::categories::category_tree(&pool)?;
+    Ok(HttpResponse::Ok().json(tree))
+}
+
+pub async fn get_category(
+    pool: web::Data<db::DbPool>,
+    category_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    let category = db::categories::get_category_by_id(&pool, category_id.into_inner())?;
+    Ok(HttpResponse::Ok().json(category))
+}
+
+pub async fn get_breadcrumbs(
+    pool: web::Data<db::DbPool>,
+    category_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    let path = db::categories::breadcrumbs(&pool, category_id.into_inner())?;
+    Ok(HttpResponse::Ok().json(path))
+}
+
+/// Equipment in a category and all of its subcategories
+pub async fn list_category_equipment(
+    pool: web::Data<db::DbPool>,
+    category_id: web::Path<i32>,
+    query: web::Query<CategoryEquipmentQuery>,
+) -> Result<impl Responder, ServiceError> {
+    let category = db::categories::get_category_by_id(&pool, category_id.into_inner())?;
+    let pagination = Pagination::new(query.page, query.per_page);
+    let filter = EquipmentFilter {
+        category_id: Some(category.id),
+        include_subcategories: true,
+        ..Default::default()
+    };
+
+    let units = query.units.unwrap_or_default();
+
+    let (items, total) = db::equipment::list_equipment(&pool, &filter, pagination)?;
+    let page = Page::new(items, pagination, total).map(|item| item.in_units(units));
+    Ok(HttpResponse::Ok().json(page))
+}
+
+pub async fn create_category(
+    pool: web::Data<db::DbPool>,
+    _admin: Authorized<AdminOnly>,
+    payload: web::Json<CategoryRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let new_category = payload.into_inner().into_new_category();
+    new_category.validate()?;
+
+    let category = db::categories::create_category(&pool, new_category)?;
+    Ok(HttpResponse::Created().json(category))
+}
+
+pub async fn update_category(
+    pool: web::Data<db::DbPool>,
+    _admin: Authorized<AdminOnly>,
+    category_id: web::Path<i32>,
+    payload: web::Json<CategoryRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let replacement = payload.into_inner().into_new_category();
+    replacement.validate()?;
+
+    let category = db::categories::update_category(
+        &pool,
+        category_id.into_inner(),
+        &CategoryChangeset::from(replacement),
+    )?;
+    Ok(HttpResponse::Ok().json(category))
+}
+
+pub async fn patch_category(
+    pool: web::Data<db::DbPool>,
+    _admin: Authorized<AdminOnly>,
+    category_id: web::Path<i32>,
+    changes: web::Json<UpdateCategory>,
+) -> Result<impl Responder, ServiceError> {
+    let category_id = category_id.into_inner();
+    let existing = db::categories::get_category_by_id(&pool, category_id)?;
+
+    let updated = changes.into_inner().apply_to(existing);
+    updated.validate()?;
+
+    let category = db::categories::update_category(&pool, category_id, &CategoryChangeset::from(updated))?;
+    Ok(HttpResponse::Ok().json(category))
+}
+
+pub async fn delete_category(
+    pool: web::Data<db::DbPool>,
+    _admin: Authorized<AdminOnly>,
+    category_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    db::categories::delete_category(&pool, category_id.into_inner())?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// The effective specification schema, including inherited attributes
+pub async fn get_spec_schema(
+    pool: web::Data<db::DbPool>,
+    category_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    let schema = db::spec_schemas::get_spec_schema(&pool, category_id.into_inner())?;
+    Ok(HttpResponse::Ok().json(schema))
+}
+
+pub async fn replace_spec_schema(
+    pool: web::Data<db::DbPool>,
+    _admin: Authorized<AdminOnly>,
+    category_id: web::Path<i32>,
+    payload: Validated<SpecSchemaRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let category_id = category_id.into_inner();
+    let attributes = payload
+        .into_inner()
+        .attributes
+        .into_iter()
+        .map(|attribute| attribute.into_new_attribute(category_id))
+        .collect();
+
+    let schema = db::spec_schemas::replace_spec_schema(&pool, category_id, attributes)?;
+    Ok(HttpResponse::Ok().json(schema))
+}
@@ -0,0 +1,181 @@
+use actix_multipart::Multipart;
+use actix_web::{web, HttpResponse, Responder};
+use futures_util::TryStreamExt;
+use serde::Deserialize;
+use crate::auth::{Authorized, CanSell};
+use crate::db;
+use crate::errors::ServiceError;
+use crate::handlers::equipment::ensure_owner_or_admin;
+use crate::handlers::uploads::{multipart_error, read_file, read_text, remove_files, remove_files_now, FilePart};
+use crate::media::{self, MediaStore};
+use crate::models::{DocumentType, DocumentUpload, NewTechnicalDocument, TechnicalDocument};
+use crate::validation::Validate;
+
+/// Longest value accepted for the text fields of an upload; the fields
+/// themselves are validated by character count afterwards
+const MAX_FIELD_BYTES: usize = 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct DocumentListQuery {
+    pub document_type: Option<DocumentType>,
+}
+
+/// The parts of a `multipart/form-data` document upload: a `file`, its
+/// `document_type` and `title`, and an optional `version` label
+async fn read_upload(mut payload: Multipart) -> Result<(DocumentUpload, FilePart), ServiceError> {
+    let mut file: Option<FilePart> = None;
+    let mut document_type = None;
+    let mut title = None;
+    let mut version = None;
+
+    while let Some(mut field) = payload.try_next().await.map_err(multipart_error)? {
+        let name = field.name().unwrap_or_default().to_string();
+        match name.as_str() {
+            "file" if file.is_some() => {
+                return Err(ServiceError::invalid_field("file", "Upload one file per request"));
+            }
+            "file" => file = Some(read_file(&mut field, media::check_document_size).await?),
+            "document_type" => {
+                let value = read_text(&mut field, "document_type", MAX_FIELD_BYTES).await?;
+                document_type = Some(
+                    value
+                        .trim()
+                        .parse::<DocumentType>()
+                        .map_err(|e| ServiceError::invalid_field("document_type", e))?,
+                );
+            }
+            "title" => title = Some(read_text(&mut field, "title", MAX_FIELD_BYTES).await?.trim().to_string()),
+            "version" => {
+                let value = read_text(&mut field, "version", MAX_FIELD_BYTES).await?;
+                version = Some(value.trim().to_string()).filter(|value| !value.is_empty());
+            }
+            other => {
+                return Err(ServiceError::invalid_field(other, "Unknown upload field"));
+            }
+        }
+    }
+
+    let document_type = document_type
+        .ok_or_else(|| ServiceError::invalid_field("document_type", "A document type is required"))?;
+    let upload = DocumentUpload { document_type, title: title.unwrap_or_default(), version };
+    upload.validate()?;
+
+    let file = file
+        .filter(|file| !file.data.is_empty())
+        .ok_or_else(|| ServiceError::invalid_field("file", "A document file is required"))?;
+    Ok((upload, file))
+}
+
+/// Writes the file, then records the version. The file is removed again if
+/// recording it fails.
+fn store_document(
+    pool: &db::DbPool,
+    store: &dyn MediaStore,
+    equipment_id: i32,
+    uploaded_by: i32,
+    upload: DocumentUpload,
+    file: FilePart,
+) -> Result<TechnicalDocument, ServiceError> {
+    let (content_type, extension) = media::check_document(&file.data, &file.content_type)?;
+    let key = media::document_key(equipment_id, extension);
+    store.put(&key, &file.data)?;
+
+    let result = db::documents::add_document_version(pool, NewTechnicalDocument {
+        equipment_id,
+        document_type: upload.document_type,
+        document_url: store.url(&key),
+        version: upload.version,
+        title: upload.title,
+        revision: 0,
+        storage_key: Some(key.clone()),
+        content_type: Some(content_type.to_string()),
+        byte_size: Some(file.data.len() as i32),
+        file_name: file.file_name,
+        uploaded_by: Some(uploaded_by),
+    });
+    if result.is_err() {
+        remove_files_now(store, &[key]);
+    }
+    result
+}
+
+/// Removes the stored files of deleted document versions
+pub(crate) async fn remove_stored_documents(store: web::Data<dyn MediaStore>, documents: Vec<TechnicalDocument>) {
+    let keys = documents.into_iter().filter_map(|document| document.storage_key).collect();
+    remove_files(store, keys).await;
+}
+
+/// The latest version of each document of a listing
+pub async fn list_documents(
+    pool: web::Data<db::DbPool>,
+    equipment_id: web::Path<i32>,
+    query: web::Query<DocumentListQuery>,
+) -> Result<impl Responder, ServiceError> {
+    let equipment_id = equipment_id.into_inner();
+    // Distinguishes an unknown listing from one without documents
+    db::equipment::get_equipment_by_id(&pool, equipment_id)?;
+
+    let documents = db::documents::list_latest_documents(&pool, equipment_id, query.document_type)?;
+    Ok(HttpResponse::Ok().json(documents))
+}
+
+/// Uploads a document version as `multipart/form-data`. An upload with the
+/// type and title of an existing document becomes its next version; earlier
+/// versions are kept. PDF, JPEG and PNG files are accepted.
+pub async fn upload_document(
+    pool: web::Data<db::DbPool>,
+    store: web::Data<dyn MediaStore>,
+    current: Authorized<CanSell>,
+    equipment_id: web::Path<i32>,
+    payload: Multipart,
+) -> Result<impl Responder, ServiceError> {
+    let equipment_id = equipment_id.into_inner();
+    let existing = db::equipment::get_equipment_by_id(&pool, equipment_id)?;
+    ensure_owner_or_admin(&current, &existing)?;
+
+    let (upload, file) = read_upload(payload).await?;
+    let uploaded_by = current.id;
+
+    let document = web::block(move || {
+        store_document(&pool, store.as_ref(), equipment_id, uploaded_by, upload, file)
+    })
+    .await
+    .map_err(|e| ServiceError::InternalServerError(e.to_string()))??;
+
+    Ok(HttpResponse::Created().json(document))
+}
+
+pub async fn get_document(
+    pool: web::Data<db::DbPool>,
+    path: web::Path<(i32, i32)>,
+) -> Result<impl Responder, ServiceError> {
+    let (equipment_id, document_id) = path.into_inner();
+    let document = db::documents::get_document(&pool, equipment_id, document_id)?;
+    Ok(HttpResponse::Ok().json(document))
+}
+
+/// The version history of a document, newest first
+pub async fn list_document_versions(
+    pool: web::Data<db::DbPool>,
+    path: web::Path<(i32, i32)>,
+) -> Result<impl Responder, ServiceError> {
+    let (equipment_id, document_id) = path.into_inner();
+    let versions = db::documents::document_versions(&pool, equipment_id, document_id)?;
+    Ok(HttpResponse::Ok().json(versions))
+}
+
+/// Deletes one version of a document
+pub async fn delete_document(
+    pool: web::Data<db::DbPool>,
+    store: web::Data<dyn MediaStore>,
+    current: Authorized<CanSell>,
+    path: web::Path<(i32, i32)>,
+) -> Result<impl Responder, ServiceError> {
+    let (equipment_id, document_id) = path.into_inner();
+    let existing = db::equipment::get_equipment_by_id(&pool, equipment_id)?;
+    ensure_owner_or_admin(&current, &existing)?;
+
+    let removed = db::documents::delete_document(&pool, equipment_id, document_id)?;
+    remove_stored_documents(store, vec![removed]).await;
+    Ok(HttpResponse::NoContent().finish())
+}
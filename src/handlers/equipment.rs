@@ -0,0 +1,318 @@
+use actix_web::{http::header, web, HttpResponse, Responder};
+use bigdecimal::BigDecimal;
+use serde::Deserialize;
+use crate::auth::{Authorized, CanSell};
+use crate::catalog::{self, CatalogRow};
+use crate::db::{self, equipment::EquipmentFilter, search::{EquipmentSearch, SearchHit, SearchSort, SpecFilter}};
+use crate::errors::ServiceError;
+use crate::handlers::documents::remove_stored_documents;
+use crate::handlers::images::remove_stored_images;
+use crate::media::MediaStore;
+use crate::models::{Equipment, EquipmentChangeset, EquipmentCondition, EquipmentRequest, NewEquipment, UpdateEquipment, User};
+use crate::pagination::{Page, Pagination};
+use crate::units::UnitSystem;
+use crate::validation::{self, Validate};
+
+/// Query string of `GET /equipment/{id}`
+#[derive(Debug, Deserialize)]
+pub struct GetEquipmentQuery {
+    pub units: Option<UnitSystem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListEquipmentQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub category_id: Option<i32>,
+    pub include_subcategories: Option<bool>,
+    pub supplier_id: Option<i32>,
+    pub manufacturer: Option<String>,
+    pub units: Option<UnitSystem>,
+}
+
+/// Query string of `GET /equipment/export`: the listing filters, without
+/// pagination
+#[derive(Debug, Deserialize)]
+pub struct ExportEquipmentQuery {
+    pub category_id: Option<i32>,
+    pub include_subcategories: Option<bool>,
+    pub supplier_id: Option<i32>,
+    pub manufacturer: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportEquipmentQuery {
+    pub dry_run: Option<bool>,
+}
+
+/// Rows read from the database per chunk of an export
+const EXPORT_BATCH_SIZE: i64 = 500;
+
+/// Query string of `GET /equipment/search`. `manufacturer` and `condition`
+/// take comma-separated lists of values, `spec` comma-separated conditions
+/// such as `payload_t>=300,drive=electric` that must all hold. Weight and
+/// size limits are metric whatever `units` results are presented in.
+#[derive(Debug, Deserialize)]
+pub struct SearchEquipmentQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub q: Option<String>,
+    pub manufacturer: Option<String>,
+    pub condition: Option<String>,
+    pub category_id: Option<i32>,
+    pub include_subcategories: Option<bool>,
+    pub min_price: Option<BigDecimal>,
+    pub max_price: Option<BigDecimal>,
+    pub min_year: Option<i32>,
+    pub max_year: Option<i32>,
+    pub max_weight_kg: Option<BigDecimal>,
+    pub max_length_cm: Option<BigDecimal>,
+    pub max_width_cm: Option<BigDecimal>,
+    pub max_height_cm: Option<BigDecimal>,
+    pub in_stock: Option<bool>,
+    pub spec: Option<String>,
+    pub sort: Option<SearchSort>,
+    pub units: Option<UnitSystem>,
+}
+
+fn split_list(value: Option<String>) -> Vec<String> {
+    value
+        .map(|list| {
+            list.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Suppliers manage their own listings; admins manage all of them
+pub(crate) fn ensure_owner_or_admin(current: &User, item: &Equipment) -> Result<(), ServiceError> {
+    if item.supplier_id == Some(current.id) || current.is_admin() {
+        Ok(())
+    } else {
+        Err(ServiceError::Forbidden("You can only manage your own listings".into()))
+    }
+}
+
+/// Validates a listing, including its specifications against the schema
+/// of its category
+fn validate_listing(pool: &db::DbPool, item: &NewEquipment) -> Result<(), ServiceError> {
+    let schema = db::spec_schemas::schema_for_listing(pool, item.category_id)?;
+    validation::validate_listing(item, &schema)
+}
+
+pub async fn create_equipment(
+    pool: web::Data<db::DbPool>,
+    supplier: Authorized<CanSell>,
+    payload: web::Json<EquipmentRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let new_item = payload.into_inner().into_new_equipment(Some(supplier.id));
+    validate_listing(&pool, &new_item)?;
+
+    let item = db::equipment::create_equipment(&pool, new_item)?;
+    let response = db::equipment::get_equipment_details(&pool, item.id)?;
+    Ok(HttpResponse::Created().json(response))
+}
+
+/// A listing; `units=imperial` adds its weight and dimensions in pounds and
+/// inches
+pub async fn get_equipment(
+    pool: web::Data<db::DbPool>,
+    equipment_id: web::Path<i32>,
+    query: web::Query<GetEquipmentQuery>,
+) -> Result<impl Responder, ServiceError> {
+    let response = db::equipment::get_equipment_details(&pool, equipment_id.into_inner())?;
+    Ok(HttpResponse::Ok().json(response.in_units(query.units.unwrap_or_default())))
+}
+
+pub async fn list_equipment(
+    pool: web::Data<db::DbPool>,
+    query: web::Query<ListEquipmentQuery>,
+) -> Result<impl Responder, ServiceError> {
+    let query = query.into_inner();
+    let pagination = Pagination::new(query.page, query.per_page);
+    let filter = EquipmentFilter {
+        category_id: query.category_id,
+        include_subcategories: query.include_subcategories.unwrap_or(false),
+        supplier_id: query.supplier_id,
+        manufacturer: query.manufacturer,
+    };
+
+    let units = query.units.unwrap_or_default();
+
+    let (items, total) = db::equipment::list_equipment(&pool, &filter, pagination)?;
+    let page = Page::new(items, pagination, total).map(|item| item.in_units(units));
+    Ok(HttpResponse::Ok().json(page))
+}
+
+/// Filtered, sorted search with facet counts, optionally ranked by a
+/// free-text `q`. Category filters include subcategories unless
+/// `include_subcategories=false`.
+pub async fn search_equipment(
+    pool: web::Data<db::DbPool>,
+    query: web::Query<SearchEquipmentQuery>,
+) -> Result<impl Responder, ServiceError> {
+    let query = query.into_inner();
+    let pagination = Pagination::new(query.page, query.per_page);
+    let text = query.q.map(|q| q.trim().to_string()).filter(|q| !q.is_empty());
+    // Text queries are ranked by relevance unless another order is asked for
+    let sort = query.sort.unwrap_or(if text.is_some() { SearchSort::Relevance } else { SearchSort::Newest });
+    let conditions = split_list(query.condition)
+        .iter()
+        .map(|condition| condition.parse::<EquipmentCondition>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|reason| ServiceError::invalid_field("condition", reason))?;
+    let specs = split_list(query.spec)
+        .iter()
+        .map(|condition| condition.parse::<SpecFilter>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|reason| ServiceError::invalid_field("spec", reason))?;
+    let search = EquipmentSearch {
+        text,
+        manufacturers: split_list(query.manufacturer),
+        conditions,
+        category_id: query.category_id,
+        include_subcategories: query.include_subcategories.unwrap_or(true),
+        min_price: query.min_price,
+        max_price: query.max_price,
+        min_year: query.min_year,
+        max_year: query.max_year,
+        max_weight_kg: query.max_weight_kg,
+        max_length_cm: query.max_length_cm,
+        max_width_cm: query.max_width_cm,
+        max_height_cm: query.max_height_cm,
+        in_stock: query.in_stock,
+        specs,
+        sort,
+    };
+    search.validate()?;
+
+    let units = query.units.unwrap_or_default();
+
+    let mut results = db::search::search_equipment(&pool, &search, pagination)?;
+    results.page = results.page.map(|hit| SearchHit { equipment: hit.equipment.in_units(units), ..hit });
+    Ok(HttpResponse::Ok().json(results))
+}
+
+pub async fn update_equipment(
+    pool: web::Data<db::DbPool>,
+    current: Authorized<CanSell>,
+    equipment_id: web::Path<i32>,
+    payload: web::Json<EquipmentRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let equipment_id = equipment_id.into_inner();
+    let existing = db::equipment::get_equipment_by_id(&pool, equipment_id)?;
+    ensure_owner_or_admin(&current, &existing)?;
+
+    let replacement = payload.into_inner().into_new_equipment(existing.supplier_id);
+    validate_listing(&pool, &replacement)?;
+
+    db::equipment::update_equipment(&pool, equipment_id, &EquipmentChangeset::from(replacement))?;
+    let response = db::equipment::get_equipment_details(&pool, equipment_id)?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn patch_equipment(
+    pool: web::Data<db::DbPool>,
+    current: Authorized<CanSell>,
+    equipment_id: web::Path<i32>,
+    changes: web::Json<UpdateEquipment>,
+) -> Result<impl Responder, ServiceError> {
+    let equipment_id = equipment_id.into_inner();
+    let existing = db::equipment::get_equipment_by_id(&pool, equipment_id)?;
+    ensure_owner_or_admin(&current, &existing)?;
+
+    // Validate the listing as it will look after the update
+    let updated = changes.into_inner().apply_to(existing);
+    validate_listing(&pool, &updated)?;
+
+    db::equipment::update_equipment(&pool, equipment_id, &EquipmentChangeset::from(updated))?;
+    let response = db::equipment::get_equipment_details(&pool, equipment_id)?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Deletes a listing and, when a media store is configured, the files of
+/// its uploaded images
+pub async fn delete_equipment(
+    pool: web::Data<db::DbPool>,
+    store: Option<web::Data<dyn MediaStore>>,
+    current: Authorized<CanSell>,
+    equipment_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    let equipment_id = equipment_id.into_inner();
+    let existing = db::equipment::get_equipment_by_id(&pool, equipment_id)?;
+    ensure_owner_or_admin(&current, &existing)?;
+
+    let images = db::images::list_images(&pool, equipment_id)?;
+    let documents = db::documents::all_documents(&pool, equipment_id)?;
+    db::equipment::delete_equipment(&pool, equipment_id)?;
+    if let Some(store) = store {
+        remove_stored_images(store.clone(), images).await;
+        remove_stored_documents(store, documents).await;
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Creates or updates the caller's listings from a CSV file in the export
+/// format. Rows that fail are reported by line without stopping the others;
+/// with `dry_run=true` the report is produced and nothing is saved.
+pub async fn import_equipment(
+    pool: web::Data<db::DbPool>,
+    supplier: Authorized<CanSell>,
+    query: web::Query<ImportEquipmentQuery>,
+    body: web::Bytes,
+) -> Result<impl Responder, ServiceError> {
+    let file = catalog::parse_import(&body, supplier.id)?;
+    let report = db::catalog::import_catalog(&pool, supplier.id, file, query.dry_run.unwrap_or(false))?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Streams the equipment matching the listing filters as CSV, reading the
+/// catalog in batches rather than all at once
+pub async fn export_equipment(
+    pool: web::Data<db::DbPool>,
+    query: web::Query<ExportEquipmentQuery>,
+) -> Result<impl Responder, ServiceError> {
+    let query = query.into_inner();
+    let filter = EquipmentFilter {
+        category_id: query.category_id,
+        include_subcategories: query.include_subcategories.unwrap_or(false),
+        supplier_id: query.supplier_id,
+        manufacturer: query.manufacturer,
+    };
+
+    // Read up front so a failure is still an error response rather than a
+    // truncated file
+    let first = db::catalog::export_batch(&pool, &filter, 0, EXPORT_BATCH_SIZE)?;
+
+    let chunks = futures_util::stream::try_unfold(Some((first, true)), move |state| {
+        let pool = pool.clone();
+        let filter = filter.clone();
+        async move {
+            let Some((batch, with_header)) = state else {
+                return Ok(None);
+            };
+            let next_after = match batch.last() {
+                Some(last) if batch.len() as i64 == EXPORT_BATCH_SIZE => Some(last.id),
+                _ => None,
+            };
+            let chunk = catalog::write_rows(batch.into_iter().map(CatalogRow::from), with_header)?;
+
+            let next = match next_after {
+                Some(after_id) => {
+                    let batch = db::catalog::export_batch(&pool, &filter, after_id, EXPORT_BATCH_SIZE)?;
+                    (!batch.is_empty()).then_some((batch, false))
+                }
+                None => None,
+            };
+            Ok::<_, ServiceError>(Some((web::Bytes::from(chunk), next)))
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header((header::CONTENT_DISPOSITION, "attachment; filename=\"equipment.csv\""))
+        .streaming(chunks))
+}
@@ -0,0 +1,191 @@
+use actix_multipart::Multipart;
+use actix_web::{web, HttpResponse, Responder};
+use futures_util::TryStreamExt;
+use crate::auth::{Authorized, CanSell};
+use crate::db;
+use crate::errors::ServiceError;
+use crate::handlers::equipment::ensure_owner_or_admin;
+use crate::handlers::uploads::{multipart_error, read_file, read_text, remove_files, remove_files_now, FilePart};
+use crate::media::{self, MediaStore, ProcessedImage};
+use crate::models::{EquipmentImage, ImageOrderRequest, NewEquipmentImage, Thumbnail};
+
+/// Longest value accepted for the `primary` field of an upload
+const MAX_FLAG_BYTES: usize = 16;
+
+/// The parts of a `multipart/form-data` image upload: an `image` file and an
+/// optional `primary` flag
+struct Upload {
+    data: Vec<u8>,
+    content_type: String,
+    primary: bool,
+}
+
+async fn read_upload(mut payload: Multipart) -> Result<Upload, ServiceError> {
+    let mut file: Option<FilePart> = None;
+    let mut primary = false;
+
+    while let Some(mut field) = payload.try_next().await.map_err(multipart_error)? {
+        let name = field.name().unwrap_or_default().to_string();
+        match name.as_str() {
+            "image" if file.is_some() => {
+                return Err(ServiceError::invalid_field("image", "Upload one image per request"));
+            }
+            "image" => file = Some(read_file(&mut field, media::check_image_size).await?),
+            "primary" => {
+                primary = read_text(&mut field, "primary", MAX_FLAG_BYTES)
+                    .await?
+                    .trim()
+                    .parse()
+                    .map_err(|_| ServiceError::invalid_field("primary", "Must be true or false"))?;
+            }
+            other => {
+                return Err(ServiceError::invalid_field(other, "Unknown upload field"));
+            }
+        }
+    }
+
+    let file = file
+        .filter(|file| !file.data.is_empty())
+        .ok_or_else(|| ServiceError::invalid_field("image", "An image file is required"))?;
+    Ok(Upload { data: file.data, content_type: file.content_type, primary })
+}
+
+/// Writes the original and its thumbnails, then records the image. Files
+/// already written are removed again if a later step fails.
+fn store_image(
+    pool: &db::DbPool,
+    store: &dyn MediaStore,
+    equipment_id: i32,
+    upload: &Upload,
+    processed: ProcessedImage,
+) -> Result<EquipmentImage, ServiceError> {
+    let key = media::image_key(equipment_id, processed.extension);
+    let mut written = Vec::new();
+
+    let result = (|| {
+        store.put(&key, &upload.data)?;
+        written.push(key.clone());
+
+        let mut thumbnails = Vec::new();
+        for thumbnail in &processed.thumbnails {
+            let thumbnail_key = media::thumbnail_key(&key, thumbnail.size);
+            store.put(&thumbnail_key, &thumbnail.data)?;
+            thumbnails.push(Thumbnail {
+                size: thumbnail.size.to_string(),
+                width: thumbnail.width,
+                height: thumbnail.height,
+                url: store.url(&thumbnail_key),
+            });
+            written.push(thumbnail_key);
+        }
+
+        db::images::add_image(pool, NewEquipmentImage {
+            equipment_id,
+            image_url: store.url(&key),
+            is_primary: upload.primary,
+            position: 0,
+            storage_key: Some(key.clone()),
+            content_type: Some(processed.content_type.to_string()),
+            byte_size: Some(upload.data.len() as i32),
+            width: Some(processed.width as i32),
+            height: Some(processed.height as i32),
+            thumbnails: serde_json::to_value(&thumbnails)
+                .map_err(|e| ServiceError::InternalServerError(e.to_string()))?,
+        })
+    })();
+
+    if result.is_err() {
+        remove_files_now(store, &written);
+    }
+    result
+}
+
+/// Removes the stored files of deleted images, thumbnails included
+pub(crate) async fn remove_stored_images(store: web::Data<dyn MediaStore>, images: Vec<EquipmentImage>) {
+    let keys = images
+        .iter()
+        .filter_map(|image| image.storage_key.as_deref())
+        .flat_map(media::stored_keys)
+        .collect();
+    remove_files(store, keys).await;
+}
+
+pub async fn list_images(
+    pool: web::Data<db::DbPool>,
+    equipment_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    let equipment_id = equipment_id.into_inner();
+    // Distinguishes an unknown listing from one without images
+    db::equipment::get_equipment_by_id(&pool, equipment_id)?;
+
+    let images = db::images::list_images(&pool, equipment_id)?;
+    Ok(HttpResponse::Ok().json(images))
+}
+
+/// Uploads an image as `multipart/form-data`. JPEG, PNG and WebP are
+/// accepted; thumbnails are generated for each upload.
+pub async fn upload_image(
+    pool: web::Data<db::DbPool>,
+    store: web::Data<dyn MediaStore>,
+    current: Authorized<CanSell>,
+    equipment_id: web::Path<i32>,
+    payload: Multipart,
+) -> Result<impl Responder, ServiceError> {
+    let equipment_id = equipment_id.into_inner();
+    let existing = db::equipment::get_equipment_by_id(&pool, equipment_id)?;
+    ensure_owner_or_admin(&current, &existing)?;
+
+    let upload = read_upload(payload).await?;
+
+    // Decoding and resizing are CPU-bound
+    let image = web::block(move || {
+        let processed = media::process_image(&upload.data, &upload.content_type)?;
+        store_image(&pool, store.as_ref(), equipment_id, &upload, processed)
+    })
+    .await
+    .map_err(|e| ServiceError::InternalServerError(e.to_string()))??;
+
+    Ok(HttpResponse::Created().json(image))
+}
+
+pub async fn reorder_images(
+    pool: web::Data<db::DbPool>,
+    current: Authorized<CanSell>,
+    equipment_id: web::Path<i32>,
+    order: web::Json<ImageOrderRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let equipment_id = equipment_id.into_inner();
+    let existing = db::equipment::get_equipment_by_id(&pool, equipment_id)?;
+    ensure_owner_or_admin(&current, &existing)?;
+
+    let images = db::images::reorder_images(&pool, equipment_id, &order.image_ids)?;
+    Ok(HttpResponse::Ok().json(images))
+}
+
+pub async fn set_primary_image(
+    pool: web::Data<db::DbPool>,
+    current: Authorized<CanSell>,
+    path: web::Path<(i32, i32)>,
+) -> Result<impl Responder, ServiceError> {
+    let (equipment_id, image_id) = path.into_inner();
+    let existing = db::equipment::get_equipment_by_id(&pool, equipment_id)?;
+    ensure_owner_or_admin(&current, &existing)?;
+
+    let images = db::images::set_primary_image(&pool, equipment_id, image_id)?;
+    Ok(HttpResponse::Ok().json(images))
+}
+
+pub async fn delete_image(
+    pool: web::Data<db::DbPool>,
+    store: web::Data<dyn MediaStore>,
+    current: Authorized<CanSell>,
+    path: web::Path<(i32, i32)>,
+) -> Result<impl Responder, ServiceError> {
+    let (equipment_id, image_id) = path.into_inner();
+    let existing = db::equipment::get_equipment_by_id(&pool, equipment_id)?;
+    ensure_owner_or_admin(&current, &existing)?;
+
+    let removed = db::images::delete_image(&pool, equipment_id, image_id)?;
+    remove_stored_images(store, vec![removed]).await;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,39 +1,19 @@
-use actix_web::{web, HttpResponse, Responder};
-use crate::models::NewUser;
-use crate::db;
-use crate::errors::ServiceError;
+pub mod auth;
+pub mod carts;
+pub mod categories;
+pub mod documents;
+pub mod equipment;
+pub mod images;
+pub mod orders;
+pub mod uploads;
+pub mod users;
 
-pub async fn health_check() -> impl Responder {
-    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
-}
-
-pub async fn create_user(
-    pool: web::Data<db::DbPool>,
-    user_data: web::Json<NewUser>,
-) -> Result<impl Responder, ServiceError> {
-    // Validate username
-    if user_data.username.is_empty() || user_data.username.len() > 50 {
-        return Err(ServiceError::ValidationError(
-            "Username must be between 1 and 50 characters".into()
-        ));
-    }
-
-    // Validate email
-    if !user_data.email.contains('@') || user_data.email.len() > 100 {
-        return Err(ServiceError::ValidationError(
-            "Invalid email format or length".into()
-        ));
-    }
+use actix_web::{HttpResponse, Responder};
 
-    // Validate password hash
-    if user_data.password_hash.len() < 8 {
-        return Err(ServiceError::ValidationError(
-            "Password hash must be at least 8 characters".into()
-        ));
-    }
+pub use users::create_user;
 
-    let user = db::users::create_user(&pool, user_data.into_inner())?;
-    Ok(HttpResponse::Created().json(user))
+pub async fn health_check() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
 }
 
 #[cfg(test)]
@@ -42,8 +22,9 @@ mod tests {
     use actix_web::{test, App, web};
     use actix_web::http::StatusCode;
     use crate::test_helpers;
-    use crate::models::User;
-    use crate::db::establish_connection_pool;
+    use crate::auth::password;
+    use crate::db::{self, establish_connection_pool};
+    use crate::models::{RegisterUser, UserResponse};
 
     #[actix_web::test]
     async fn test_health_check() {
@@ -78,10 +59,10 @@ mod tests {
                 .service(web::resource("/users").route(web::post().to(create_user)))
         ).await;
         
-        let user_data = NewUser::new(
+        let user_data = RegisterUser::new(
             "testuser".to_string(),
             "test@example.com".to_string(),
-            "hashedpassword123".to_string(),
+            "plaintextpassword123".to_string(),
         );
         
         // First creation should succeed
@@ -93,9 +74,15 @@ mod tests {
         let resp = test::call_service(&app, req).await;
         assert_eq!(resp.status(), StatusCode::CREATED);
         
-        let user: User = test::read_body_json(resp).await;
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert!(body.get("password_hash").is_none(), "Password hash must not be exposed");
+        let user: UserResponse = serde_json::from_value(body).unwrap();
         assert_eq!(user.username, "testuser");
         assert_eq!(user.email, "test@example.com");
+
+        let stored = db::users::get_user_by_id(&pool, user.id).unwrap();
+        assert!(stored.password_hash.starts_with("$argon2id$"));
+        assert!(password::verify_password("plaintextpassword123", &stored.password_hash));
         
         // Second creation with same username should fail with conflict
         let req = test::TestRequest::post()
@@ -0,0 +1,195 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use crate::auth::{AdminOnly, AuthenticatedUser, Authorized, CanSell, VerifiedBuyer};
+use crate::db;
+use crate::errors::ServiceError;
+use crate::models::{
+    CancelOrderRequest, OrderDetails, OrderItem, OrderTransition, PlaceOrderRequest, ShipOrderRequest, User,
+};
+use crate::validation::Validated;
+
+/// Query string of `GET /orders/cancellations/summary`; both dates are
+/// inclusive
+#[derive(Debug, Deserialize)]
+pub struct CancellationSummaryQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+/// Places an order for the caller. Stock is reserved by decrementing it as
+/// the order is placed; prices are those of the listings at that moment.
+pub async fn place_order(
+    pool: web::Data<db::DbPool>,
+    buyer: Authorized<VerifiedBuyer>,
+    payload: Validated<PlaceOrderRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let order = db::orders::place_order(&pool, buyer.id, &payload)?;
+    Ok(HttpResponse::Created().json(order))
+}
+
+/// Buyers see their own orders, suppliers the orders containing their
+/// listings, and admins every order
+fn ensure_can_view(pool: &db::DbPool, current: &User, order: &OrderDetails) -> Result<(), ServiceError> {
+    if order.order.user_id == current.id || current.is_admin() {
+        return Ok(());
+    }
+    if db::orders::order_supplier_ids(pool, order.order.id)?.contains(&current.id) {
+        return Ok(());
+    }
+    Err(ServiceError::Forbidden("You can only view orders you placed or supply".into()))
+}
+
+/// Orders are fulfilled by admins, or by a supplier when every remaining
+/// line is of their own listings, since shipping details are order-wide
+fn ensure_can_fulfil(pool: &db::DbPool, current: &User, order_id: i32) -> Result<(), ServiceError> {
+    if current.is_admin() {
+        return Ok(());
+    }
+    let order = db::orders::get_order(pool, order_id)?;
+    let suppliers = db::orders::line_supplier_ids(pool, order_id)?;
+    let mut lines = order.items.iter().filter(|item| item.cancelled_at.is_none()).peekable();
+    if lines.peek().is_some() && lines.all(|item| suppliers.get(&item.id).copied().flatten() == Some(current.id)) {
+        Ok(())
+    } else {
+        Err(ServiceError::Forbidden("You can only fulfil orders made up of your own listings".into()))
+    }
+}
+
+pub async fn get_order(
+    pool: web::Data<db::DbPool>,
+    current: AuthenticatedUser,
+    order_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    let order = db::orders::get_order(&pool, order_id.into_inner())?;
+    ensure_can_view(&pool, &current, &order)?;
+    Ok(HttpResponse::Ok().json(order))
+}
+
+/// The order's status changes, oldest first
+pub async fn get_order_history(
+    pool: web::Data<db::DbPool>,
+    current: AuthenticatedUser,
+    order_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    let order = db::orders::get_order(&pool, order_id.into_inner())?;
+    ensure_can_view(&pool, &current, &order)?;
+    let history = db::orders::order_history(&pool, order.order.id)?;
+    Ok(HttpResponse::Ok().json(history))
+}
+
+async fn fulfil(
+    pool: web::Data<db::DbPool>,
+    supplier: Authorized<CanSell>,
+    order_id: i32,
+    transition: OrderTransition,
+) -> Result<HttpResponse, ServiceError> {
+    ensure_can_fulfil(&pool, &supplier, order_id)?;
+    let order = db::orders::transition_order(&pool, order_id, supplier.id, &transition)?;
+    Ok(HttpResponse::Ok().json(order))
+}
+
+pub async fn confirm_order(
+    pool: web::Data<db::DbPool>,
+    supplier: Authorized<CanSell>,
+    order_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    fulfil(pool, supplier, order_id.into_inner(), OrderTransition::Confirm).await
+}
+
+/// Marks a confirmed order as shipped with its tracking number and
+/// estimated delivery date
+pub async fn ship_order(
+    pool: web::Data<db::DbPool>,
+    supplier: Authorized<CanSell>,
+    order_id: web::Path<i32>,
+    payload: Validated<ShipOrderRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let shipment = payload.into_inner();
+    fulfil(pool, supplier, order_id.into_inner(), OrderTransition::Ship(shipment)).await
+}
+
+pub async fn deliver_order(
+    pool: web::Data<db::DbPool>,
+    supplier: Authorized<CanSell>,
+    order_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    fulfil(pool, supplier, order_id.into_inner(), OrderTransition::Deliver).await
+}
+
+/// Buyers may cancel their own orders and any of their lines. Suppliers may
+/// cancel lines of their own listings, and whole orders when every
+/// remaining line is theirs. Admins may cancel anything.
+fn ensure_can_cancel(
+    pool: &db::DbPool,
+    current: &User,
+    order: &OrderDetails,
+    item_id: Option<i32>,
+) -> Result<(), ServiceError> {
+    if order.order.user_id == current.id || current.is_admin() {
+        return Ok(());
+    }
+    let suppliers = db::orders::line_supplier_ids(pool, order.order.id)?;
+    let supplies = |item: &OrderItem| suppliers.get(&item.id).copied().flatten() == Some(current.id);
+    let involved = order.items.iter().any(supplies);
+    let mut lines = order
+        .items
+        .iter()
+        .filter(|item| item_id.map_or(item.cancelled_at.is_none(), |item_id| item.id == item_id));
+    if involved && lines.all(supplies) {
+        Ok(())
+    } else {
+        Err(ServiceError::Forbidden("You can only cancel your own orders or lines of your own listings".into()))
+    }
+}
+
+/// Cancels every remaining line of an order, returning their stock and
+/// recording the refund owed
+pub async fn cancel_order(
+    pool: web::Data<db::DbPool>,
+    current: AuthenticatedUser,
+    order_id: web::Path<i32>,
+    payload: Validated<CancelOrderRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let order = db::orders::get_order(&pool, order_id.into_inner())?;
+    ensure_can_cancel(&pool, &current, &order, None)?;
+    let outcome = db::cancellations::cancel_order(&pool, order.order.id, current.id, &payload)?;
+    Ok(HttpResponse::Ok().json(outcome))
+}
+
+/// Cancels one line of an order; cancelling the last remaining line
+/// cancels the order
+pub async fn cancel_order_item(
+    pool: web::Data<db::DbPool>,
+    current: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+    payload: Validated<CancelOrderRequest>,
+) -> Result<impl Responder, ServiceError> {
+    let (order_id, item_id) = path.into_inner();
+    let order = db::orders::get_order(&pool, order_id)?;
+    ensure_can_cancel(&pool, &current, &order, Some(item_id))?;
+    let outcome = db::cancellations::cancel_order_item(&pool, order_id, item_id, current.id, &payload)?;
+    Ok(HttpResponse::Ok().json(outcome))
+}
+
+/// The order's cancellations with their refunds, oldest first
+pub async fn list_order_cancellations(
+    pool: web::Data<db::DbPool>,
+    current: AuthenticatedUser,
+    order_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    let order = db::orders::get_order(&pool, order_id.into_inner())?;
+    ensure_can_view(&pool, &current, &order)?;
+    let cancellations = db::cancellations::order_cancellations(&pool, order.order.id)?;
+    Ok(HttpResponse::Ok().json(cancellations))
+}
+
+/// Cancellations and refunded amounts per reason, for reporting
+pub async fn cancellation_summary(
+    pool: web::Data<db::DbPool>,
+    _admin: Authorized<AdminOnly>,
+    query: web::Query<CancellationSummaryQuery>,
+) -> Result<impl Responder, ServiceError> {
+    let summary = db::cancellations::cancellation_summary(&pool, query.from, query.to)?;
+    Ok(HttpResponse::Ok().json(summary))
+}
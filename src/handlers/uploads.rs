@@ -0,0 +1,80 @@
+//! Helpers shared by the `multipart/form-data` upload endpoints
+
+use actix_multipart::Field;
+use actix_web::web;
+use futures_util::TryStreamExt;
+use log::warn;
+use crate::errors::ServiceError;
+use crate::media::MediaStore;
+
+/// Longest file name kept from an upload
+const MAX_FILE_NAME_LENGTH: usize = 255;
+
+/// A file part of an upload
+pub(crate) struct FilePart {
+    /// Declared type, without parameters; empty if none was sent
+    pub content_type: String,
+    /// The client's file name without any directory part
+    pub file_name: Option<String>,
+    pub data: Vec<u8>,
+}
+
+pub(crate) fn multipart_error(error: actix_multipart::MultipartError) -> ServiceError {
+    ServiceError::BadRequest(format!("Invalid multipart upload: {}", error))
+}
+
+/// Reads a file part, applying `check_size` as chunks arrive so oversized
+/// files are refused before they are fully buffered
+pub(crate) async fn read_file(
+    field: &mut Field,
+    check_size: impl Fn(usize) -> Result<(), ServiceError>,
+) -> Result<FilePart, ServiceError> {
+    let content_type = field
+        .content_type()
+        .map(|mime| mime.essence_str().to_string())
+        .unwrap_or_default();
+    let file_name = field
+        .content_disposition()
+        .and_then(|disposition| disposition.get_filename())
+        .and_then(|name| name.rsplit(['/', '\\']).next())
+        .map(|name| name.trim().chars().take(MAX_FILE_NAME_LENGTH).collect::<String>())
+        .filter(|name| !name.is_empty());
+
+    let mut data = Vec::new();
+    while let Some(chunk) = field.try_next().await.map_err(multipart_error)? {
+        check_size(data.len() + chunk.len())?;
+        data.extend_from_slice(&chunk);
+    }
+    Ok(FilePart { content_type, file_name, data })
+}
+
+/// Reads a text part of at most `max_bytes`
+pub(crate) async fn read_text(field: &mut Field, name: &str, max_bytes: usize) -> Result<String, ServiceError> {
+    let mut value = Vec::new();
+    while let Some(chunk) = field.try_next().await.map_err(multipart_error)? {
+        value.extend_from_slice(&chunk);
+        if value.len() > max_bytes {
+            return Err(ServiceError::invalid_field(name, format!("Must be at most {} bytes", max_bytes)));
+        }
+    }
+    String::from_utf8(value).map_err(|_| ServiceError::invalid_field(name, "Must be valid UTF-8 text"))
+}
+
+/// Best effort: a file left behind is only wasted space
+pub(crate) fn remove_files_now(store: &dyn MediaStore, keys: &[String]) {
+    for key in keys {
+        if let Err(e) = store.delete(key) {
+            warn!("Failed to remove media '{}': {}", key, e);
+        }
+    }
+}
+
+/// Removes stored files off the async workers
+pub(crate) async fn remove_files(store: web::Data<dyn MediaStore>, keys: Vec<String>) {
+    if keys.is_empty() {
+        return;
+    }
+    if let Err(e) = web::block(move || remove_files_now(store.as_ref(), &keys)).await {
+        warn!("Failed to remove media: {}", e);
+    }
+}
@@ -0,0 +1,194 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+use log::error;
+use crate::auth::{account, password, AccountMailer, AdminOnly, AuthenticatedUser, Authorized};
+use crate::db::{self, users::UserFilter};
+use crate::errors::ServiceError;
+use crate::models::{RegisterUser, UpdateUser, User, UserChangeset, UserResponse, UserRole};
+use crate::pagination::{Page, Pagination};
+use crate::validation::Validated;
+
+#[derive(Debug, Deserialize)]
+pub struct ListUsersQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub search: Option<String>,
+    pub business_type: Option<UserRole>,
+    pub company_name: Option<String>,
+    pub include_deleted: Option<bool>,
+}
+
+/// Users may act on their own account; admins may act on any account
+fn ensure_self_or_admin(current: &User, user_id: i32) -> Result<(), ServiceError> {
+    if current.id == user_id || current.is_admin() {
+        Ok(())
+    } else {
+        Err(ServiceError::Forbidden("You can only access your own account".into()))
+    }
+}
+
+fn ensure_can_assign_role(current: &User, role: Option<UserRole>) -> Result<(), ServiceError> {
+    if role == Some(UserRole::Admin) && !current.is_admin() {
+        return Err(ServiceError::Forbidden(
+            "Only admins can grant the admin role".into()
+        ));
+    }
+    Ok(())
+}
+
+/// Argon2 is deliberately slow, so keep it off the async workers
+async fn hash_password(plaintext: String) -> Result<String, ServiceError> {
+    web::block(move || password::hash_password(&plaintext))
+        .await
+        .map_err(|e| ServiceError::InternalServerError(e.to_string()))?
+}
+
+/// Registers an account and, when a mailer is configured, emails a
+/// verification link. A failed email does not fail the registration; the
+/// user can ask for another link via `POST /auth/verify-email/request`.
+pub async fn create_user(
+    pool: web::Data<db::DbPool>,
+    mailer: Option<web::Data<AccountMailer>>,
+    user_data: Validated<RegisterUser>,
+) -> Result<impl Responder, ServiceError> {
+    let user_data = user_data.into_inner();
+
+    // Admin accounts are provisioned by other admins, never self-registered
+    if user_data.business_type == Some(UserRole::Admin) {
+        return Err(ServiceError::Forbidden(
+            "Cannot register an admin account".into()
+        ));
+    }
+
+    let password_hash = hash_password(user_data.password.clone()).await?;
+    let user = db::users::create_user(&pool, user_data.into_new_user(password_hash))?;
+
+    if let Some(mailer) = mailer {
+        let (pool, recipient) = (pool.clone(), user.clone());
+        let sent = web::block(move || account::send_email_verification(&pool, &mailer, &recipient)).await;
+        match sent {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Failed to send verification email to user {}: {}", user.id, e),
+            Err(e) => error!("Failed to send verification email to user {}: {}", user.id, e),
+        }
+    }
+
+    Ok(HttpResponse::Created().json(UserResponse::from(user)))
+}
+
+pub async fn get_current_user(user: AuthenticatedUser) -> impl Responder {
+    HttpResponse::Ok().json(UserResponse::from(user.into_inner()))
+}
+
+pub async fn get_user(
+    pool: web::Data<db::DbPool>,
+    current: AuthenticatedUser,
+    user_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    let user_id = user_id.into_inner();
+    ensure_self_or_admin(&current, user_id)?;
+
+    let user = db::users::get_user_by_id(&pool, user_id)?;
+    Ok(HttpResponse::Ok().json(UserResponse::from(user)))
+}
+
+pub async fn update_user(
+    pool: web::Data<db::DbPool>,
+    current: AuthenticatedUser,
+    user_id: web::Path<i32>,
+    user_data: Validated<RegisterUser>,
+) -> Result<impl Responder, ServiceError> {
+    let user_id = user_id.into_inner();
+    ensure_self_or_admin(&current, user_id)?;
+
+    let user_data = user_data.into_inner();
+    ensure_can_assign_role(&current, user_data.business_type)?;
+
+    let password_hash = hash_password(user_data.password.clone()).await?;
+    let changes = UserChangeset::from(user_data.into_new_user(password_hash));
+    let user = db::users::update_user(&pool, user_id, &changes)?;
+    Ok(HttpResponse::Ok().json(UserResponse::from(user)))
+}
+
+pub async fn patch_user(
+    pool: web::Data<db::DbPool>,
+    current: AuthenticatedUser,
+    user_id: web::Path<i32>,
+    changes: Validated<UpdateUser>,
+) -> Result<impl Responder, ServiceError> {
+    let user_id = user_id.into_inner();
+    ensure_self_or_admin(&current, user_id)?;
+
+    let changes = changes.into_inner();
+    ensure_can_assign_role(&current, changes.business_type.flatten())?;
+
+    let password_hash = match &changes.password {
+        Some(plaintext) => {
+            // A new username was already checked by `Validate`; otherwise
+            // check against the username the password will live alongside
+            if changes.username.is_none() {
+                let username = db::users::get_user_by_id(&pool, user_id)?.username;
+                password::validate_password_strength(plaintext, &username)?;
+            }
+            Some(hash_password(plaintext.clone()).await?)
+        }
+        None => None,
+    };
+
+    let user = db::users::update_user(&pool, user_id, &changes.into_changeset(password_hash))?;
+    Ok(HttpResponse::Ok().json(UserResponse::from(user)))
+}
+
+/// Deactivates the account; see `db::users::delete_user`
+pub async fn delete_user(
+    pool: web::Data<db::DbPool>,
+    current: AuthenticatedUser,
+    user_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    let user_id = user_id.into_inner();
+    ensure_self_or_admin(&current, user_id)?;
+
+    db::users::delete_user(&pool, user_id)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Erases the personal data of an account and deactivates it. This cannot
+/// be undone.
+pub async fn anonymize_user(
+    pool: web::Data<db::DbPool>,
+    current: AuthenticatedUser,
+    user_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    let user_id = user_id.into_inner();
+    ensure_self_or_admin(&current, user_id)?;
+
+    db::users::anonymize_user(&pool, user_id)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn restore_user(
+    pool: web::Data<db::DbPool>,
+    _admin: Authorized<AdminOnly>,
+    user_id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    let user = db::users::restore_user(&pool, user_id.into_inner())?;
+    Ok(HttpResponse::Ok().json(UserResponse::from(user)))
+}
+
+pub async fn list_users(
+    pool: web::Data<db::DbPool>,
+    _admin: Authorized<AdminOnly>,
+    query: web::Query<ListUsersQuery>,
+) -> Result<impl Responder, ServiceError> {
+    let query = query.into_inner();
+    let pagination = Pagination::new(query.page, query.per_page);
+    let filter = UserFilter {
+        search: query.search,
+        business_type: query.business_type,
+        company_name: query.company_name,
+        include_deleted: query.include_deleted.unwrap_or(false),
+    };
+
+    let (users, total) = db::users::list_users(&pool, &filter, pagination)?;
+    Ok(HttpResponse::Ok().json(Page::new(users, pagination, total).map(UserResponse::from)))
+}
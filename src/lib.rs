@@ -1,11 +1,24 @@
 pub mod models;
+pub mod catalog;
 pub mod schema;
+pub mod config;
+pub mod mailer;
+pub mod media;
+pub mod auth;
 pub mod db;
 pub mod errors;
 pub mod handlers;
+pub mod pagination;
+pub mod request_id;
+pub mod reservations;
+pub mod routes;
+pub mod units;
+pub mod validation;
 pub mod logging;  // Add this line to register the logging module
 pub mod test_helpers;
 
+pub use routes::configure;
+
 // Re-export test_logging for tests
 #[cfg(test)]
 pub mod test_logging;
@@ -0,0 +1,102 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use crate::errors::ServiceError;
+
+/// A plain-text message addressed to a single recipient
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivery backend for outgoing mail. Implementations must be cheap to call
+/// from a blocking context; handlers invoke them off the async workers.
+pub trait Mailer: Send + Sync {
+    fn send(&self, email: &Email) -> Result<(), ServiceError>;
+}
+
+/// Writes every message as a JSON file into a directory instead of sending
+/// it. Used for local development and tests.
+#[derive(Debug, Clone)]
+pub struct OutboxMailer {
+    dir: PathBuf,
+}
+
+impl OutboxMailer {
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// All messages in the outbox addressed to `to`, oldest first
+    pub fn messages_to(&self, to: &str) -> Vec<Email> {
+        let mut entries: Vec<_> = fs::read_dir(&self.dir)
+            .map(|dir| dir.filter_map(Result::ok).map(|entry| entry.path()).collect())
+            .unwrap_or_default();
+        entries.sort();
+
+        entries
+            .iter()
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .filter_map(|content| serde_json::from_str::<Email>(&content).ok())
+            .filter(|email| email.to == to)
+            .collect()
+    }
+}
+
+impl Mailer for OutboxMailer {
+    fn send(&self, email: &Email) -> Result<(), ServiceError> {
+        // Timestamp prefix keeps directory listings in delivery order
+        let name = format!(
+            "{}_{}.json",
+            chrono::Utc::now().format("%Y%m%d%H%M%S%6f"),
+            uuid::Uuid::new_v4().simple()
+        );
+        let path = self.dir.join(name);
+
+        let content = serde_json::to_string_pretty(email)
+            .map_err(|e| ServiceError::InternalServerError(e.to_string()))?;
+        fs::write(&path, content).map_err(|e| {
+            error!("Failed to write email to {}: {}", path.display(), e);
+            ServiceError::InternalServerError("Failed to send email".into())
+        })?;
+
+        info!("Queued email '{}' to {} in {}", email.subject, email.to, path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outbox_round_trip() {
+        let dir = std::env::temp_dir().join(format!("outbox_{}", uuid::Uuid::new_v4().simple()));
+        let mailer = OutboxMailer::new(&dir).expect("Outbox should be created");
+
+        let email = Email {
+            to: "buyer@example.com".into(),
+            subject: "Hello".into(),
+            body: "First".into(),
+        };
+        mailer.send(&email).unwrap();
+        mailer.send(&Email { body: "Second".into(), ..email.clone() }).unwrap();
+        mailer.send(&Email { to: "other@example.com".into(), ..email.clone() }).unwrap();
+
+        let messages = mailer.messages_to("buyer@example.com");
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0], email);
+        assert_eq!(messages[1].body, "Second");
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
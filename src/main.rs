@@ -1,14 +1,73 @@
+use actix_web::{middleware::{from_fn, Logger}, web, App, HttpServer};
 use dotenv::dotenv;
-use rust_market::logging;
+use rust_market::{
+    auth::AccountMailer,
+    config::{AppConfig, MEDIA_PATH},
+    db, logging,
+    mailer::OutboxMailer,
+    media::{LocalMediaStore, MediaStore},
+    request_id, reservations,
+};
 use log::info;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
 
-fn main() {
+#[actix_web::main]
+async fn main() -> io::Result<()> {
     // Initialize environment variables
     dotenv().ok();
 
     // Initialize logger
     logging::init_logger().expect("Failed to initialize logger");
 
-    // Log startup message
-    info!("Rust Market Application Started");
+    let config = AppConfig::from_env()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let pool = db::establish_connection_pool(Some(&config.database_url))
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    let pool = web::Data::new(pool);
+    let tokens = web::Data::new(config.token_config());
+    let reservation_config = web::Data::new(config.reservation_config());
+
+    // Expired stock holds stop counting as soon as they expire; the sweep
+    // only keeps the table from growing
+    reservations::spawn_expiry_task(pool.get_ref().clone(), Duration::from_secs(config.reservation_sweep_secs));
+
+    // Outgoing mail is written to a local outbox until a real transport is configured
+    let outbox = OutboxMailer::new(&config.mail_outbox_dir)?;
+    info!("Writing outgoing mail to {}", outbox.dir().display());
+    let mailer = web::Data::new(AccountMailer::new(Arc::new(outbox), config.public_base_url.clone()));
+
+    // Uploads are kept on local disk and served by this process
+    let media_store = LocalMediaStore::new(&config.media_dir, config.media_base_url())?;
+    info!("Storing uploaded media in {}", media_store.dir().display());
+    let media_dir = media_store.dir().to_path_buf();
+    let media: web::Data<dyn MediaStore> = web::Data::from(Arc::new(media_store) as Arc<dyn MediaStore>);
+
+    let (host, port) = config.bind_address();
+    info!("Rust Market Application starting on {}:{}", host, port);
+
+    // Actix installs SIGINT/SIGTERM handlers and stops accepting new connections
+    // on either, giving in-flight requests `shutdown_timeout` seconds to finish
+    HttpServer::new(move || {
+        App::new()
+            .app_data(pool.clone())
+            .app_data(tokens.clone())
+            .app_data(reservation_config.clone())
+            .app_data(mailer.clone())
+            .app_data(media.clone())
+            .wrap(from_fn(request_id::middleware))
+            // Outermost, so the access log sees the request id header
+            .wrap(Logger::new(r#"%a "%r" %s %b %T %{x-request-id}o"#))
+            .configure(rust_market::configure)
+            .service(actix_files::Files::new(MEDIA_PATH, &media_dir))
+    })
+    .bind((host, port))?
+    .shutdown_timeout(config.shutdown_timeout_secs)
+    .run()
+    .await?;
+
+    info!("Rust Market Application stopped");
+    Ok(())
 }
@@ -0,0 +1,355 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::{Component, Path, PathBuf};
+use image::{codecs::jpeg::JpegEncoder, DynamicImage, ImageFormat, ImageReader, Limits};
+use log::{error, info};
+use crate::errors::{codes, ServiceError};
+
+/// Largest image upload accepted
+pub const MAX_IMAGE_BYTES: usize = 10 * 1024 * 1024;
+/// Longest edge accepted, bounding the memory a decode can take
+pub const MAX_IMAGE_DIMENSION: u32 = 12_000;
+/// Thumbnails generated for every upload, by name and longest edge
+pub const THUMBNAIL_SIZES: [(&str, u32); 3] = [("small", 160), ("medium", 480), ("large", 1024)];
+const THUMBNAIL_QUALITY: u8 = 85;
+/// Largest technical document upload accepted
+pub const MAX_DOCUMENT_BYTES: usize = 25 * 1024 * 1024;
+
+/// Accepted upload types, with the format their content must decode as and
+/// the extension they are stored under
+const IMAGE_TYPES: [(&str, ImageFormat, &str); 3] = [
+    ("image/jpeg", ImageFormat::Jpeg, "jpg"),
+    ("image/png", ImageFormat::Png, "png"),
+    ("image/webp", ImageFormat::WebP, "webp"),
+];
+
+/// Accepted document types, with the extension they are stored under.
+/// Scanned certificates often arrive as images, so those are allowed too.
+const DOCUMENT_TYPES: [(&str, &str); 3] = [
+    ("application/pdf", "pdf"),
+    ("image/jpeg", "jpg"),
+    ("image/png", "png"),
+];
+
+/// Storage backend for uploaded media. Implementations must be cheap to call
+/// from a blocking context; handlers invoke them off the async workers.
+pub trait MediaStore: Send + Sync {
+    /// Stores `data` under `key`, replacing any existing object
+    fn put(&self, key: &str, data: &[u8]) -> Result<(), ServiceError>;
+    /// Removes the object under `key`; missing objects are not an error
+    fn delete(&self, key: &str) -> Result<(), ServiceError>;
+    /// The public URL the object under `key` is served from
+    fn url(&self, key: &str) -> String;
+}
+
+/// Keeps media as files in a local directory, served under `base_url`
+#[derive(Debug, Clone)]
+pub struct LocalMediaStore {
+    dir: PathBuf,
+    base_url: String,
+}
+
+impl LocalMediaStore {
+    pub fn new(dir: impl Into<PathBuf>, base_url: impl Into<String>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, base_url: base_url.into().trim_end_matches('/').to_string() })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The file for `key`. Keys are generated by the service, but anything
+    /// that could leave the media directory is refused all the same.
+    pub fn path(&self, key: &str) -> Result<PathBuf, ServiceError> {
+        let relative = Path::new(key);
+        if key.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(ServiceError::InternalServerError(format!("Invalid media key '{}'", key)));
+        }
+        Ok(self.dir.join(relative))
+    }
+}
+
+impl MediaStore for LocalMediaStore {
+    fn put(&self, key: &str, data: &[u8]) -> Result<(), ServiceError> {
+        let path = self.path(key)?;
+        let write = |path: &Path| -> std::io::Result<()> {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, data)
+        };
+        write(&path).map_err(|e| {
+            error!("Failed to write media to {}: {}", path.display(), e);
+            ServiceError::InternalServerError("Failed to store media".into())
+        })?;
+
+        info!("Stored {} bytes of media at {}", data.len(), path.display());
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), ServiceError> {
+        let path = self.path(key)?;
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => {
+                error!("Failed to delete media at {}: {}", path.display(), e);
+                Err(ServiceError::InternalServerError("Failed to delete media".into()))
+            }
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.base_url, key)
+    }
+}
+
+/// A validated upload and the thumbnails rendered from it
+#[derive(Debug)]
+pub struct ProcessedImage {
+    pub content_type: &'static str,
+    pub extension: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub thumbnails: Vec<RenderedThumbnail>,
+}
+
+/// A JPEG thumbnail, no larger than its size in either dimension
+#[derive(Debug)]
+pub struct RenderedThumbnail {
+    pub size: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+fn invalid_image(code: &'static str, reason: impl Into<String>) -> ServiceError {
+    ServiceError::invalid_field("image", reason).with_code(code)
+}
+
+/// Rejects uploads over `MAX_IMAGE_BYTES`; also checked while an upload is
+/// still being received
+pub fn check_image_size(len: usize) -> Result<(), ServiceError> {
+    if len > MAX_IMAGE_BYTES {
+        return Err(invalid_image(
+            codes::IMAGE_TOO_LARGE,
+            format!("Images must be at most {} MB", MAX_IMAGE_BYTES / (1024 * 1024)),
+        ));
+    }
+    Ok(())
+}
+
+/// Checks an upload against its declared content type and renders its
+/// thumbnails. The content must really be an image of the declared type;
+/// neither the type nor the file name is trusted on its own.
+pub fn process_image(data: &[u8], declared_type: &str) -> Result<ProcessedImage, ServiceError> {
+    check_image_size(data.len())?;
+
+    let (content_type, format, extension) = IMAGE_TYPES
+        .into_iter()
+        .find(|(content_type, ..)| *content_type == declared_type)
+        .ok_or_else(|| {
+            let allowed: Vec<&str> = IMAGE_TYPES.iter().map(|(content_type, ..)| *content_type).collect();
+            invalid_image(
+                codes::IMAGE_UNSUPPORTED_TYPE,
+                format!("Unsupported image type '{}', expected one of: {}", declared_type, allowed.join(", ")),
+            )
+        })?;
+
+    if image::guess_format(data).ok() != Some(format) {
+        return Err(invalid_image(
+            codes::IMAGE_INVALID,
+            format!("File content is not a valid {} image", content_type),
+        ));
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    let mut reader = ImageReader::with_format(Cursor::new(data), format);
+    reader.limits(limits);
+    let decoded = reader
+        .decode()
+        .map_err(|e| invalid_image(codes::IMAGE_INVALID, format!("Image could not be decoded: {}", e)))?;
+
+    let thumbnails = THUMBNAIL_SIZES
+        .into_iter()
+        .map(|(size, edge)| render_thumbnail(&decoded, size, edge))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ProcessedImage {
+        content_type,
+        extension,
+        width: decoded.width(),
+        height: decoded.height(),
+        thumbnails,
+    })
+}
+
+/// Scales down to fit `edge`, keeping the aspect ratio; smaller images are
+/// only re-encoded, never enlarged
+fn render_thumbnail(image: &DynamicImage, size: &'static str, edge: u32) -> Result<RenderedThumbnail, ServiceError> {
+    let scaled = if image.width() > edge || image.height() > edge {
+        image.thumbnail(edge, edge)
+    } else {
+        image.clone()
+    };
+    // JPEG has no alpha channel
+    let rgb = scaled.to_rgb8();
+
+    let mut data = Vec::new();
+    JpegEncoder::new_with_quality(&mut data, THUMBNAIL_QUALITY)
+        .encode_image(&rgb)
+        .map_err(|e| ServiceError::InternalServerError(format!("Failed to encode thumbnail: {}", e)))?;
+
+    Ok(RenderedThumbnail { size, width: rgb.width(), height: rgb.height(), data })
+}
+
+fn invalid_document(code: &'static str, reason: impl Into<String>) -> ServiceError {
+    ServiceError::invalid_field("file", reason).with_code(code)
+}
+
+/// Rejects documents over `MAX_DOCUMENT_BYTES`; also checked while an upload
+/// is still being received
+pub fn check_document_size(len: usize) -> Result<(), ServiceError> {
+    if len > MAX_DOCUMENT_BYTES {
+        return Err(invalid_document(
+            codes::DOCUMENT_TOO_LARGE,
+            format!("Documents must be at most {} MB", MAX_DOCUMENT_BYTES / (1024 * 1024)),
+        ));
+    }
+    Ok(())
+}
+
+/// Checks a document upload against its declared content type and returns
+/// the type and the extension to store it under. As with images, the content
+/// must start like a file of the declared type.
+pub fn check_document(data: &[u8], declared_type: &str) -> Result<(&'static str, &'static str), ServiceError> {
+    check_document_size(data.len())?;
+
+    let (content_type, extension) = DOCUMENT_TYPES
+        .into_iter()
+        .find(|(content_type, _)| *content_type == declared_type)
+        .ok_or_else(|| {
+            let allowed: Vec<&str> = DOCUMENT_TYPES.iter().map(|(content_type, _)| *content_type).collect();
+            invalid_document(
+                codes::DOCUMENT_UNSUPPORTED_TYPE,
+                format!("Unsupported document type '{}', expected one of: {}", declared_type, allowed.join(", ")),
+            )
+        })?;
+
+    let matches = match content_type {
+        "application/pdf" => data.starts_with(b"%PDF-"),
+        "image/jpeg" => image::guess_format(data).ok() == Some(ImageFormat::Jpeg),
+        _ => image::guess_format(data).ok() == Some(ImageFormat::Png),
+    };
+    if !matches {
+        return Err(invalid_document(
+            codes::DOCUMENT_INVALID,
+            format!("File content is not a valid {} file", content_type),
+        ));
+    }
+    Ok((content_type, extension))
+}
+
+/// Key of an uploaded document version, unique per upload
+pub fn document_key(equipment_id: i32, extension: &str) -> String {
+    format!("documents/{}/{}.{}", equipment_id, uuid::Uuid::new_v4().simple(), extension)
+}
+
+/// Key of an uploaded original, unique per upload
+pub fn image_key(equipment_id: i32, extension: &str) -> String {
+    format!("equipment/{}/{}.{}", equipment_id, uuid::Uuid::new_v4().simple(), extension)
+}
+
+/// Key of one thumbnail of the original stored under `key`
+pub fn thumbnail_key(key: &str, size: &str) -> String {
+    let stem = key.rsplit_once('.').map_or(key, |(stem, _)| stem);
+    format!("{}_{}.jpg", stem, size)
+}
+
+/// Every key stored for an upload: the original and its thumbnails
+pub fn stored_keys(key: &str) -> Vec<String> {
+    std::iter::once(key.to_string())
+        .chain(THUMBNAIL_SIZES.iter().map(|(size, _)| thumbnail_key(key, size)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgba};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = ImageBuffer::from_pixel(width, height, Rgba([200u8, 120, 40, 128]));
+        let mut data = Vec::new();
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_process_image_renders_thumbnails_without_enlarging() {
+        let processed = process_image(&png(2000, 500), "image/png").expect("Image should be accepted");
+        assert_eq!((processed.width, processed.height), (2000, 500));
+        assert_eq!(processed.extension, "png");
+
+        let sizes: Vec<_> = processed.thumbnails.iter().map(|t| (t.size, t.width, t.height)).collect();
+        assert_eq!(sizes, vec![("small", 160, 40), ("medium", 480, 120), ("large", 1024, 256)]);
+        assert_eq!(image::guess_format(&processed.thumbnails[0].data).unwrap(), ImageFormat::Jpeg);
+
+        let small = process_image(&png(100, 80), "image/png").unwrap();
+        assert!(small.thumbnails.iter().all(|t| (t.width, t.height) == (100, 80)));
+    }
+
+    #[test]
+    fn test_process_image_rejects_mismatched_and_unsupported_types() {
+        let error = process_image(&png(10, 10), "image/jpeg").unwrap_err();
+        assert_eq!(error.code(), codes::IMAGE_INVALID);
+
+        let error = process_image(b"GIF89a", "image/gif").unwrap_err();
+        assert_eq!(error.code(), codes::IMAGE_UNSUPPORTED_TYPE);
+
+        let mut truncated = png(50, 50);
+        truncated.truncate(60);
+        let error = process_image(&truncated, "image/png").unwrap_err();
+        assert_eq!(error.code(), codes::IMAGE_INVALID);
+    }
+
+    #[test]
+    fn test_check_document_sniffs_content() {
+        assert_eq!(check_document(b"%PDF-1.7\n", "application/pdf").unwrap(), ("application/pdf", "pdf"));
+        assert_eq!(check_document(&png(4, 4), "image/png").unwrap().1, "png");
+
+        let error = check_document(b"<html></html>", "application/pdf").unwrap_err();
+        assert_eq!(error.code(), codes::DOCUMENT_INVALID);
+        let error = check_document(b"PK\x03\x04", "application/zip").unwrap_err();
+        assert_eq!(error.code(), codes::DOCUMENT_UNSUPPORTED_TYPE);
+        let error = check_document_size(MAX_DOCUMENT_BYTES + 1).unwrap_err();
+        assert_eq!(error.code(), codes::DOCUMENT_TOO_LARGE);
+    }
+
+    #[test]
+    fn test_local_store_round_trip_and_key_confinement() {
+        let dir = std::env::temp_dir().join(format!("media_{}", uuid::Uuid::new_v4().simple()));
+        let store = LocalMediaStore::new(&dir, "http://localhost:8080/media/").unwrap();
+
+        let key = image_key(7, "png");
+        store.put(&key, b"data").unwrap();
+        assert_eq!(fs::read(dir.join(&key)).unwrap(), b"data");
+        assert_eq!(store.url(&key), format!("http://localhost:8080/media/{}", key));
+
+        store.delete(&key).unwrap();
+        assert!(!dir.join(&key).exists());
+        store.delete(&key).expect("Deleting a missing object succeeds");
+
+        assert!(store.put("../escape.png", b"data").is_err());
+        assert!(store.put("/etc/escape.png", b"data").is_err());
+
+        assert_eq!(thumbnail_key("equipment/7/abc.png", "small"), "equipment/7/abc_small.jpg");
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -12,13 +12,88 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use diesel::{Queryable, Selectable, Identifiable, Associations, Insertable};
+use diesel::{Queryable, QueryableByName, Selectable, Identifiable, Associations, Insertable, AsChangeset, AsExpression, FromSqlRow};
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Varchar;
 use chrono::{NaiveDateTime, NaiveDate};
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, Deserializer};
 use bigdecimal::BigDecimal;
+use std::io::Write;
+
+use crate::units::{ImperialMeasurements, UnitSystem};
 
 // Import schema modules
-use crate::schema::{users, equipment, equipment_categories, orders, order_items, reviews, maintenance_records, equipment_images};
+use crate::schema::{users, carts, cart_items, equipment, equipment_categories, category_spec_attributes, orders, order_items, order_status_history, order_cancellations, refunds, reviews, stock_reservations, maintenance_records, equipment_images, technical_documents};
+
+/// Role of a user on the marketplace, stored in `users.business_type`
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "lowercase")]
+pub enum UserRole {
+    Supplier,
+    Buyer,
+    Both,
+    Admin,
+}
+
+impl UserRole {
+    pub const ALL: [UserRole; 4] = [UserRole::Supplier, UserRole::Buyer, UserRole::Both, UserRole::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserRole::Supplier => "supplier",
+            UserRole::Buyer => "buyer",
+            UserRole::Both => "both",
+            UserRole::Admin => "admin",
+        }
+    }
+
+    /// Suppliers list and manage equipment; admins may moderate listings
+    pub fn can_sell(&self) -> bool {
+        matches!(self, UserRole::Supplier | UserRole::Both | UserRole::Admin)
+    }
+
+    pub fn can_buy(&self) -> bool {
+        matches!(self, UserRole::Buyer | UserRole::Both)
+    }
+
+    pub fn is_admin(&self) -> bool {
+        matches!(self, UserRole::Admin)
+    }
+}
+
+impl std::fmt::Display for UserRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for UserRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        UserRole::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| format!("Unknown user role '{}'", s))
+    }
+}
+
+impl ToSql<Varchar, Pg> for UserRole {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Varchar, Pg> for UserRole {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Varchar, Pg>>::from_sql(bytes)?;
+        value.parse().map_err(Into::into)
+    }
+}
 
 #[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Clone, Debug)]
 #[diesel(table_name = users)]
@@ -26,12 +101,69 @@ pub struct User {
     pub id: i32,
     pub username: String,
     pub email: String,
+    // Never part of any JSON representation; clients get `UserResponse`
+    #[serde(skip)]
     pub password_hash: String,
     pub company_name: Option<String>,
-    pub business_type: Option<String>,
+    pub business_type: Option<UserRole>,
     pub contact_number: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub email_verified_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+    pub anonymized_at: Option<NaiveDateTime>,
+    /// Bumped on password reset; access tokens carrying an older version are
+    /// rejected
+    #[serde(skip)]
+    pub token_version: i32,
+}
+
+impl User {
+    pub fn is_admin(&self) -> bool {
+        self.business_type.is_some_and(|role| role.is_admin())
+    }
+
+    pub fn is_email_verified(&self) -> bool {
+        self.email_verified_at.is_some()
+    }
+
+    /// Deactivated accounts cannot log in and are hidden from lookups
+    pub fn is_active(&self) -> bool {
+        self.deleted_at.is_none()
+    }
+}
+
+/// Public representation of a user returned by the API
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserResponse {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    pub company_name: Option<String>,
+    pub business_type: Option<UserRole>,
+    pub contact_number: Option<String>,
+    pub email_verified: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl From<User> for UserResponse {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            company_name: user.company_name,
+            business_type: user.business_type,
+            contact_number: user.contact_number,
+            email_verified: user.email_verified_at.is_some(),
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+            deleted_at: user.deleted_at,
+        }
+    }
 }
 
 #[derive(Insertable, Serialize, Deserialize, Clone, Debug)]
@@ -41,7 +173,7 @@ pub struct NewUser {
     pub email: String,
     pub password_hash: String,
     pub company_name: Option<String>,
-    pub business_type: Option<String>,
+    pub business_type: Option<UserRole>,
     pub contact_number: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
@@ -63,7 +195,136 @@ impl NewUser {
     }
 }
 
-#[derive(Queryable, Selectable, Identifiable, Serialize, Deserialize, Clone, Debug)]
+/// Registration payload accepted from clients. Carries the plaintext password,
+/// which is hashed server-side before a `NewUser` is built from it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RegisterUser {
+    pub username: String,
+    pub email: String,
+    pub password: String,
+    pub company_name: Option<String>,
+    pub business_type: Option<UserRole>,
+    pub contact_number: Option<String>,
+}
+
+impl RegisterUser {
+    pub fn new(username: String, email: String, password: String) -> Self {
+        Self {
+            username,
+            email,
+            password,
+            company_name: None,
+            business_type: None,
+            contact_number: None,
+        }
+    }
+
+    /// Converts the registration into an insertable user with the given hash
+    pub fn into_new_user(self, password_hash: String) -> NewUser {
+        let mut user = NewUser::new(self.username, self.email, password_hash);
+        user.company_name = self.company_name;
+        user.business_type = self.business_type;
+        user.contact_number = self.contact_number;
+        user
+    }
+}
+
+// Hand-written so the plaintext password never ends up in logs
+impl std::fmt::Debug for RegisterUser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RegisterUser")
+            .field("username", &self.username)
+            .field("email", &self.email)
+            .field("password", &"[redacted]")
+            .field("company_name", &self.company_name)
+            .field("business_type", &self.business_type)
+            .field("contact_number", &self.contact_number)
+            .finish()
+    }
+}
+
+/// Deserializes a present-but-null field as `Some(None)` so partial updates
+/// can tell "clear this field" apart from "leave it unchanged"
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Partial update payload for `PATCH /users/{id}`. Absent fields are left
+/// untouched; `null` clears an optional profile field.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct UpdateUser {
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub password: Option<String>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub company_name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub business_type: Option<Option<UserRole>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub contact_number: Option<Option<String>>,
+}
+
+impl UpdateUser {
+    /// Converts the payload into a changeset, given the hash of `password`
+    /// when one was supplied
+    pub fn into_changeset(self, password_hash: Option<String>) -> UserChangeset {
+        UserChangeset {
+            username: self.username,
+            email: self.email,
+            password_hash,
+            company_name: self.company_name,
+            business_type: self.business_type,
+            contact_number: self.contact_number,
+        }
+    }
+}
+
+// Hand-written so the plaintext password never ends up in logs
+impl std::fmt::Debug for UpdateUser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UpdateUser")
+            .field("username", &self.username)
+            .field("email", &self.email)
+            .field("password", &self.password.as_ref().map(|_| "[redacted]"))
+            .field("company_name", &self.company_name)
+            .field("business_type", &self.business_type)
+            .field("contact_number", &self.contact_number)
+            .finish()
+    }
+}
+
+/// Columns of `users` that may be changed after registration. `None` leaves
+/// a column as is; `Some(None)` sets a nullable column to NULL.
+#[derive(AsChangeset, Clone, Debug, Default)]
+#[diesel(table_name = users)]
+pub struct UserChangeset {
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub password_hash: Option<String>,
+    pub company_name: Option<Option<String>>,
+    pub business_type: Option<Option<UserRole>>,
+    pub contact_number: Option<Option<String>>,
+}
+
+impl From<NewUser> for UserChangeset {
+    /// A changeset that replaces every mutable column, as `PUT` does
+    fn from(user: NewUser) -> Self {
+        Self {
+            username: Some(user.username),
+            email: Some(user.email),
+            password_hash: Some(user.password_hash),
+            company_name: Some(user.company_name),
+            business_type: Some(user.business_type),
+            contact_number: Some(user.contact_number),
+        }
+    }
+}
+
+#[derive(Queryable, QueryableByName, Selectable, Identifiable, Serialize, Deserialize, Clone, Debug)]
 #[diesel(table_name = equipment_categories)]
 pub struct EquipmentCategory {
     pub id: i32,
@@ -84,7 +345,284 @@ pub struct NewEquipmentCategory {
     pub updated_at: NaiveDateTime,
 }
 
-#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize, Debug)]
+/// Create/replace payload for `POST /categories` and `PUT /categories/{id}`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CategoryRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub parent_category_id: Option<i32>,
+}
+
+impl CategoryRequest {
+    pub fn into_new_category(self) -> NewEquipmentCategory {
+        let now = chrono::Utc::now().naive_utc();
+        NewEquipmentCategory {
+            name: self.name,
+            description: self.description,
+            parent_category_id: self.parent_category_id,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Partial update payload for `PATCH /categories/{id}`. `null` clears the
+/// description or moves the category to the top level.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UpdateCategory {
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub parent_category_id: Option<Option<i32>>,
+}
+
+impl UpdateCategory {
+    /// The category as it will look once the update is applied
+    pub fn apply_to(self, existing: EquipmentCategory) -> NewEquipmentCategory {
+        NewEquipmentCategory {
+            name: self.name.unwrap_or(existing.name),
+            description: self.description.unwrap_or(existing.description),
+            parent_category_id: self.parent_category_id.unwrap_or(existing.parent_category_id),
+            created_at: existing.created_at,
+            updated_at: existing.updated_at,
+        }
+    }
+}
+
+#[derive(AsChangeset, Clone, Debug, Default)]
+#[diesel(table_name = equipment_categories)]
+pub struct CategoryChangeset {
+    pub name: Option<String>,
+    pub description: Option<Option<String>>,
+    pub parent_category_id: Option<Option<i32>>,
+}
+
+impl From<NewEquipmentCategory> for CategoryChangeset {
+    fn from(category: NewEquipmentCategory) -> Self {
+        Self {
+            name: Some(category.name),
+            description: Some(category.description),
+            parent_category_id: Some(category.parent_category_id),
+        }
+    }
+}
+
+/// Value type of a specification attribute
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "lowercase")]
+pub enum SpecDataType {
+    Number,
+    Integer,
+    Text,
+    Boolean,
+}
+
+impl SpecDataType {
+    pub const ALL: [SpecDataType; 4] = [
+        SpecDataType::Number,
+        SpecDataType::Integer,
+        SpecDataType::Text,
+        SpecDataType::Boolean,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpecDataType::Number => "number",
+            SpecDataType::Integer => "integer",
+            SpecDataType::Text => "text",
+            SpecDataType::Boolean => "boolean",
+        }
+    }
+
+    /// Whether a JSON value is of this type
+    pub fn accepts(&self, value: &serde_json::Value) -> bool {
+        match self {
+            SpecDataType::Number => value.is_number(),
+            SpecDataType::Integer => value.is_i64() || value.is_u64(),
+            SpecDataType::Text => value.is_string(),
+            SpecDataType::Boolean => value.is_boolean(),
+        }
+    }
+}
+
+impl std::fmt::Display for SpecDataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for SpecDataType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SpecDataType::ALL
+            .into_iter()
+            .find(|data_type| data_type.as_str() == s)
+            .ok_or_else(|| format!("Unknown specification data type '{}'", s))
+    }
+}
+
+impl ToSql<Varchar, Pg> for SpecDataType {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Varchar, Pg> for SpecDataType {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Varchar, Pg>>::from_sql(bytes)?;
+        value.parse().map_err(Into::into)
+    }
+}
+
+/// One attribute of a category's specification schema
+#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize, Clone, Debug)]
+#[diesel(belongs_to(EquipmentCategory, foreign_key = category_id))]
+#[diesel(table_name = category_spec_attributes)]
+pub struct SpecAttribute {
+    pub id: i32,
+    /// The category defining the attribute, which may be an ancestor of the
+    /// one it applies to
+    pub category_id: i32,
+    pub name: String,
+    pub data_type: SpecDataType,
+    pub unit: Option<String>,
+    pub required: bool,
+    pub allowed_values: Option<serde_json::Value>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl SpecAttribute {
+    /// Whether `value` is one of the permitted values, if the attribute has any
+    pub fn allows(&self, value: &serde_json::Value) -> bool {
+        match self.allowed_values.as_ref().and_then(|values| values.as_array()) {
+            Some(values) => values.contains(value),
+            None => true,
+        }
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = category_spec_attributes)]
+pub struct NewSpecAttribute {
+    pub category_id: i32,
+    pub name: String,
+    pub data_type: SpecDataType,
+    pub unit: Option<String>,
+    pub required: bool,
+    pub allowed_values: Option<serde_json::Value>,
+}
+
+/// One attribute in the body of `PUT /categories/{id}/spec-schema`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SpecAttributeRequest {
+    pub name: String,
+    pub data_type: SpecDataType,
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+    pub allowed_values: Option<Vec<serde_json::Value>>,
+}
+
+/// Body of `PUT /categories/{id}/spec-schema`: the attributes the category
+/// defines itself, replacing any it had before
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SpecSchemaRequest {
+    pub attributes: Vec<SpecAttributeRequest>,
+}
+
+impl SpecAttributeRequest {
+    pub fn into_new_attribute(self, category_id: i32) -> NewSpecAttribute {
+        NewSpecAttribute {
+            category_id,
+            name: self.name,
+            data_type: self.data_type,
+            unit: self.unit,
+            required: self.required,
+            allowed_values: self.allowed_values.map(serde_json::Value::Array),
+        }
+    }
+}
+
+/// A category with its subcategories, as returned by `GET /categories/tree`
+#[derive(Serialize, Clone, Debug)]
+pub struct CategoryNode {
+    #[serde(flatten)]
+    pub category: EquipmentCategory,
+    pub children: Vec<CategoryNode>,
+}
+
+/// Condition of listed equipment, stored in `equipment.condition`
+#[derive(AsExpression, FromSqlRow, Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "lowercase")]
+pub enum EquipmentCondition {
+    New,
+    Used,
+    Refurbished,
+}
+
+impl EquipmentCondition {
+    pub const ALL: [EquipmentCondition; 3] = [EquipmentCondition::New, EquipmentCondition::Used, EquipmentCondition::Refurbished];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EquipmentCondition::New => "new",
+            EquipmentCondition::Used => "used",
+            EquipmentCondition::Refurbished => "refurbished",
+        }
+    }
+}
+
+impl std::fmt::Display for EquipmentCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for EquipmentCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EquipmentCondition::ALL
+            .into_iter()
+            .find(|value| value.as_str() == s)
+            .ok_or_else(|| format!(
+                "Unknown equipment condition '{}', expected one of: {}",
+                s,
+                EquipmentCondition::ALL.map(|value| value.as_str()).join(", ")
+            ))
+    }
+}
+
+/// Parsed through `FromStr` so unknown values are reported with the list
+/// of accepted ones
+impl<'de> Deserialize<'de> for EquipmentCondition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl ToSql<Varchar, Pg> for EquipmentCondition {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Varchar, Pg> for EquipmentCondition {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Varchar, Pg>>::from_sql(bytes)?;
+        value.parse().map_err(Into::into)
+    }
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize, Clone, Debug)]
 #[diesel(belongs_to(EquipmentCategory, foreign_key = category_id))]
 #[diesel(table_name = equipment)]
 pub struct Equipment {
@@ -95,17 +633,21 @@ pub struct Equipment {
     pub manufacturer: String,
     pub model_number: String,
     pub year_manufactured: Option<i32>,
-    pub condition: String,
+    pub condition: EquipmentCondition,
     pub price: BigDecimal,
     pub stock_level: i32,
     pub specifications: Option<serde_json::Value>,
     pub weight_kg: Option<BigDecimal>,
-    pub dimensions_cm: Option<String>,
     pub power_requirements: Option<String>,
     pub certification_info: Option<String>,
     pub warranty_info: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub supplier_id: Option<i32>,
+    /// Outer dimensions; either all three are set or none are
+    pub length_cm: Option<BigDecimal>,
+    pub width_cm: Option<BigDecimal>,
+    pub height_cm: Option<BigDecimal>,
 }
 
 #[derive(Insertable, Serialize, Deserialize, Debug)]
@@ -117,28 +659,235 @@ pub struct NewEquipment {
     pub manufacturer: String,
     pub model_number: String,
     pub year_manufactured: Option<i32>,
-    pub condition: String,
+    pub condition: EquipmentCondition,
     pub price: BigDecimal,
     pub stock_level: i32,
     pub specifications: Option<serde_json::Value>,
     pub weight_kg: Option<BigDecimal>,
-    pub dimensions_cm: Option<String>,
     pub power_requirements: Option<String>,
     pub certification_info: Option<String>,
     pub warranty_info: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub supplier_id: Option<i32>,
+    /// Outer dimensions; either all three are set or none are
+    pub length_cm: Option<BigDecimal>,
+    pub width_cm: Option<BigDecimal>,
+    pub height_cm: Option<BigDecimal>,
 }
 
-#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize, Debug)]
+/// Create/replace payload for `POST /equipment` and `PUT /equipment/{id}`.
+/// Ownership and timestamps are set server-side.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EquipmentRequest {
+    pub category_id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub manufacturer: String,
+    pub model_number: String,
+    pub year_manufactured: Option<i32>,
+    pub condition: EquipmentCondition,
+    pub price: BigDecimal,
+    pub stock_level: i32,
+    pub specifications: Option<serde_json::Value>,
+    pub weight_kg: Option<BigDecimal>,
+    pub length_cm: Option<BigDecimal>,
+    pub width_cm: Option<BigDecimal>,
+    pub height_cm: Option<BigDecimal>,
+    pub power_requirements: Option<String>,
+    pub certification_info: Option<String>,
+    pub warranty_info: Option<String>,
+}
+
+impl EquipmentRequest {
+    pub fn into_new_equipment(self, supplier_id: Option<i32>) -> NewEquipment {
+        let now = chrono::Utc::now().naive_utc();
+        NewEquipment {
+            category_id: self.category_id,
+            name: self.name,
+            description: self.description,
+            manufacturer: self.manufacturer,
+            model_number: self.model_number,
+            year_manufactured: self.year_manufactured,
+            condition: self.condition,
+            price: self.price,
+            stock_level: self.stock_level,
+            specifications: self.specifications,
+            weight_kg: self.weight_kg,
+            power_requirements: self.power_requirements,
+            certification_info: self.certification_info,
+            warranty_info: self.warranty_info,
+            created_at: now,
+            updated_at: now,
+            supplier_id,
+            length_cm: self.length_cm,
+            width_cm: self.width_cm,
+            height_cm: self.height_cm,
+        }
+    }
+}
+
+/// Partial update payload for `PATCH /equipment/{id}`. Absent fields are
+/// left untouched; `null` clears an optional field.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UpdateEquipment {
+    pub category_id: Option<i32>,
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub description: Option<Option<String>>,
+    pub manufacturer: Option<String>,
+    pub model_number: Option<String>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub year_manufactured: Option<Option<i32>>,
+    pub condition: Option<EquipmentCondition>,
+    pub price: Option<BigDecimal>,
+    pub stock_level: Option<i32>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub specifications: Option<Option<serde_json::Value>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub weight_kg: Option<Option<BigDecimal>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub length_cm: Option<Option<BigDecimal>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub width_cm: Option<Option<BigDecimal>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub height_cm: Option<Option<BigDecimal>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub power_requirements: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub certification_info: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub warranty_info: Option<Option<String>>,
+}
+
+impl UpdateEquipment {
+    /// The listing as it will look once the update is applied, so that it
+    /// can be validated as a whole
+    pub fn apply_to(self, existing: Equipment) -> NewEquipment {
+        NewEquipment {
+            category_id: self.category_id.unwrap_or(existing.category_id),
+            name: self.name.unwrap_or(existing.name),
+            description: self.description.unwrap_or(existing.description),
+            manufacturer: self.manufacturer.unwrap_or(existing.manufacturer),
+            model_number: self.model_number.unwrap_or(existing.model_number),
+            year_manufactured: self.year_manufactured.unwrap_or(existing.year_manufactured),
+            condition: self.condition.unwrap_or(existing.condition),
+            price: self.price.unwrap_or(existing.price),
+            stock_level: self.stock_level.unwrap_or(existing.stock_level),
+            specifications: self.specifications.unwrap_or(existing.specifications),
+            weight_kg: self.weight_kg.unwrap_or(existing.weight_kg),
+            power_requirements: self.power_requirements.unwrap_or(existing.power_requirements),
+            certification_info: self.certification_info.unwrap_or(existing.certification_info),
+            warranty_info: self.warranty_info.unwrap_or(existing.warranty_info),
+            created_at: existing.created_at,
+            updated_at: existing.updated_at,
+            supplier_id: existing.supplier_id,
+            length_cm: self.length_cm.unwrap_or(existing.length_cm),
+            width_cm: self.width_cm.unwrap_or(existing.width_cm),
+            height_cm: self.height_cm.unwrap_or(existing.height_cm),
+        }
+    }
+}
+
+/// Columns of `equipment` that may be changed after listing. `None` leaves a
+/// column as is; `Some(None)` sets a nullable column to NULL.
+#[derive(AsChangeset, Clone, Debug, Default)]
+#[diesel(table_name = equipment)]
+pub struct EquipmentChangeset {
+    pub category_id: Option<i32>,
+    pub name: Option<String>,
+    pub description: Option<Option<String>>,
+    pub manufacturer: Option<String>,
+    pub model_number: Option<String>,
+    pub year_manufactured: Option<Option<i32>>,
+    pub condition: Option<EquipmentCondition>,
+    pub price: Option<BigDecimal>,
+    pub stock_level: Option<i32>,
+    pub specifications: Option<Option<serde_json::Value>>,
+    pub weight_kg: Option<Option<BigDecimal>>,
+    pub length_cm: Option<Option<BigDecimal>>,
+    pub width_cm: Option<Option<BigDecimal>>,
+    pub height_cm: Option<Option<BigDecimal>>,
+    pub power_requirements: Option<Option<String>>,
+    pub certification_info: Option<Option<String>>,
+    pub warranty_info: Option<Option<String>>,
+}
+
+impl From<NewEquipment> for EquipmentChangeset {
+    /// A changeset that replaces every mutable column, as `PUT` does.
+    /// Ownership is never changed through an update.
+    fn from(item: NewEquipment) -> Self {
+        Self {
+            category_id: Some(item.category_id),
+            name: Some(item.name),
+            description: Some(item.description),
+            manufacturer: Some(item.manufacturer),
+            model_number: Some(item.model_number),
+            year_manufactured: Some(item.year_manufactured),
+            condition: Some(item.condition),
+            price: Some(item.price),
+            stock_level: Some(item.stock_level),
+            specifications: Some(item.specifications),
+            weight_kg: Some(item.weight_kg),
+            length_cm: Some(item.length_cm),
+            width_cm: Some(item.width_cm),
+            height_cm: Some(item.height_cm),
+            power_requirements: Some(item.power_requirements),
+            certification_info: Some(item.certification_info),
+            warranty_info: Some(item.warranty_info),
+        }
+    }
+}
+
+/// Equipment as returned by the API, with its category name and primary
+/// image, and imperial measurements when those were asked for
+#[derive(Serialize, Clone, Debug)]
+pub struct EquipmentResponse {
+    #[serde(flatten)]
+    pub equipment: Equipment,
+    pub category_name: String,
+    pub primary_image: Option<EquipmentImage>,
+    /// `stock_level` less the quantities held for buyers checking out
+    pub available_quantity: i32,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub imperial: Option<ImperialMeasurements>,
+}
+
+impl EquipmentResponse {
+    /// Adds the measurements of `units` that are not stored; metric values
+    /// are always present
+    pub fn in_units(mut self, units: UnitSystem) -> Self {
+        self.imperial = match units {
+            UnitSystem::Metric => None,
+            UnitSystem::Imperial => Some(ImperialMeasurements::of(&self.equipment)),
+        };
+        self
+    }
+}
+
+/// An image of a listing. Images are shown in `position` order, and an
+/// equipment with images has exactly one primary image. Uploaded images
+/// carry their metadata and generated thumbnails; images linked by URL only
+/// leave those empty.
+#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize, Clone, Debug)]
 #[diesel(belongs_to(Equipment))]
 #[diesel(table_name = equipment_images)]
 pub struct EquipmentImage {
     pub id: i32,
     pub equipment_id: i32,
     pub image_url: String,
-    pub is_primary: Option<bool>,
+    pub is_primary: bool,
     pub created_at: NaiveDateTime,
+    pub position: i32,
+    /// Where the media store keeps the original; internal
+    #[serde(skip)]
+    pub storage_key: Option<String>,
+    pub content_type: Option<String>,
+    pub byte_size: Option<i32>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// `[{"size", "width", "height", "url"}]`, smallest first
+    pub thumbnails: serde_json::Value,
 }
 
 #[derive(Insertable, Serialize, Deserialize, Debug)]
@@ -146,7 +895,254 @@ pub struct EquipmentImage {
 pub struct NewEquipmentImage {
     pub equipment_id: i32,
     pub image_url: String,
-    pub is_primary: Option<bool>,
+    pub is_primary: bool,
+    pub position: i32,
+    pub storage_key: Option<String>,
+    pub content_type: Option<String>,
+    pub byte_size: Option<i32>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub thumbnails: serde_json::Value,
+}
+
+/// A generated thumbnail of an uploaded image
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Thumbnail {
+    pub size: String,
+    pub width: u32,
+    pub height: u32,
+    pub url: String,
+}
+
+/// Body of `PUT /equipment/{id}/images/order`: every image id of the
+/// equipment, in the new display order
+#[derive(Deserialize, Debug)]
+pub struct ImageOrderRequest {
+    pub image_ids: Vec<i32>,
+}
+
+/// Kind of technical document, stored in `technical_documents.document_type`
+#[derive(AsExpression, FromSqlRow, Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentType {
+    Manual,
+    Specification,
+    Certification,
+    Warranty,
+    SafetyDataSheet,
+    ServiceBulletin,
+    Other,
+}
+
+impl DocumentType {
+    pub const ALL: [DocumentType; 7] = [
+        DocumentType::Manual,
+        DocumentType::Specification,
+        DocumentType::Certification,
+        DocumentType::Warranty,
+        DocumentType::SafetyDataSheet,
+        DocumentType::ServiceBulletin,
+        DocumentType::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocumentType::Manual => "manual",
+            DocumentType::Specification => "specification",
+            DocumentType::Certification => "certification",
+            DocumentType::Warranty => "warranty",
+            DocumentType::SafetyDataSheet => "safety_data_sheet",
+            DocumentType::ServiceBulletin => "service_bulletin",
+            DocumentType::Other => "other",
+        }
+    }
+}
+
+impl std::fmt::Display for DocumentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for DocumentType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DocumentType::ALL
+            .into_iter()
+            .find(|value| value.as_str() == s)
+            .ok_or_else(|| format!(
+                "Unknown document type '{}', expected one of: {}",
+                s,
+                DocumentType::ALL.map(|value| value.as_str()).join(", ")
+            ))
+    }
+}
+
+/// Parsed through `FromStr` so unknown values are reported with the list
+/// of accepted ones
+impl<'de> Deserialize<'de> for DocumentType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl ToSql<Varchar, Pg> for DocumentType {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Varchar, Pg> for DocumentType {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Varchar, Pg>>::from_sql(bytes)?;
+        value.parse().map_err(Into::into)
+    }
+}
+
+/// One version of a technical document. A document is identified by its
+/// equipment, type and title; its versions are numbered by `revision` from
+/// 1, while `version` is the publisher's own label, such as "Rev C".
+#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize, Clone, Debug)]
+#[diesel(belongs_to(Equipment))]
+#[diesel(table_name = technical_documents)]
+pub struct TechnicalDocument {
+    pub id: i32,
+    pub equipment_id: i32,
+    pub document_type: DocumentType,
+    pub document_url: String,
+    pub version: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub title: String,
+    pub revision: i32,
+    /// Where the media store keeps the file; internal
+    #[serde(skip)]
+    pub storage_key: Option<String>,
+    pub content_type: Option<String>,
+    pub byte_size: Option<i32>,
+    pub file_name: Option<String>,
+    pub uploaded_by: Option<i32>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = technical_documents)]
+pub struct NewTechnicalDocument {
+    pub equipment_id: i32,
+    pub document_type: DocumentType,
+    pub document_url: String,
+    pub version: Option<String>,
+    pub title: String,
+    pub revision: i32,
+    pub storage_key: Option<String>,
+    pub content_type: Option<String>,
+    pub byte_size: Option<i32>,
+    pub file_name: Option<String>,
+    pub uploaded_by: Option<i32>,
+}
+
+/// The document a new upload belongs to, and its version label
+#[derive(Debug, Clone)]
+pub struct DocumentUpload {
+    pub document_type: DocumentType,
+    pub title: String,
+    pub version: Option<String>,
+}
+
+/// The latest version of a document, with how many versions it has
+#[derive(Serialize, Clone, Debug)]
+pub struct DocumentSummary {
+    #[serde(flatten)]
+    pub latest: TechnicalDocument,
+    pub version_count: i64,
+}
+
+/// Lifecycle state of an order, stored in `orders.status`
+#[derive(AsExpression, FromSqlRow, Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderStatus {
+    Pending,
+    Confirmed,
+    Shipped,
+    Delivered,
+    Cancelled,
+}
+
+impl OrderStatus {
+    pub const ALL: [OrderStatus; 5] = [OrderStatus::Pending, OrderStatus::Confirmed, OrderStatus::Shipped, OrderStatus::Delivered, OrderStatus::Cancelled];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Confirmed => "confirmed",
+            OrderStatus::Shipped => "shipped",
+            OrderStatus::Delivered => "delivered",
+            OrderStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Statuses an order in this status may move to. Orders go pending →
+    /// confirmed → shipped → delivered and can be cancelled until they ship;
+    /// delivered and cancelled orders are final.
+    pub fn next_statuses(&self) -> &'static [OrderStatus] {
+        match self {
+            OrderStatus::Pending => &[OrderStatus::Confirmed, OrderStatus::Cancelled],
+            OrderStatus::Confirmed => &[OrderStatus::Shipped, OrderStatus::Cancelled],
+            OrderStatus::Shipped => &[OrderStatus::Delivered],
+            OrderStatus::Delivered | OrderStatus::Cancelled => &[],
+        }
+    }
+
+    pub fn can_transition_to(&self, next: OrderStatus) -> bool {
+        self.next_statuses().contains(&next)
+    }
+}
+
+impl std::fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for OrderStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OrderStatus::ALL
+            .into_iter()
+            .find(|value| value.as_str() == s)
+            .ok_or_else(|| format!(
+                "Unknown order status '{}', expected one of: {}",
+                s,
+                OrderStatus::ALL.map(|value| value.as_str()).join(", ")
+            ))
+    }
+}
+
+/// Parsed through `FromStr` so unknown values are reported with the list
+/// of accepted ones
+impl<'de> Deserialize<'de> for OrderStatus {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl ToSql<Varchar, Pg> for OrderStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Varchar, Pg> for OrderStatus {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Varchar, Pg>>::from_sql(bytes)?;
+        value.parse().map_err(Into::into)
+    }
 }
 
 #[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize, Debug)]
@@ -155,7 +1151,7 @@ pub struct NewEquipmentImage {
 pub struct Order {
     pub id: i32,
     pub user_id: i32,
-    pub status: String,
+    pub status: OrderStatus,
     pub total_amount: BigDecimal,
     pub shipping_address: String,
     pub shipping_method: String,
@@ -170,7 +1166,7 @@ pub struct Order {
 #[diesel(table_name = orders)]
 pub struct NewOrder {
     pub user_id: i32,
-    pub status: String,
+    pub status: OrderStatus,
     pub total_amount: BigDecimal,
     pub shipping_address: String,
     pub shipping_method: String,
@@ -193,6 +1189,8 @@ pub struct OrderItem {
     pub price_at_time: BigDecimal,
     pub warranty_selected: Option<bool>,
     pub special_requirements: Option<String>,
+    /// When the line was cancelled and its quantity returned to stock
+    pub cancelled_at: Option<NaiveDateTime>,
 }
 
 #[derive(Insertable, Serialize, Deserialize, Debug)]
@@ -206,6 +1204,400 @@ pub struct NewOrderItem {
     pub special_requirements: Option<String>,
 }
 
+/// One entry of an order's timeline. The entry recording the order being
+/// placed has no `from_status`.
+#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize, Clone, Debug)]
+#[diesel(belongs_to(Order))]
+#[diesel(table_name = order_status_history)]
+pub struct OrderStatusChange {
+    pub id: i32,
+    pub order_id: i32,
+    pub from_status: Option<OrderStatus>,
+    pub to_status: OrderStatus,
+    pub changed_by: Option<i32>,
+    pub note: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = order_status_history)]
+pub struct NewOrderStatusChange {
+    pub order_id: i32,
+    pub from_status: Option<OrderStatus>,
+    pub to_status: OrderStatus,
+    pub changed_by: Option<i32>,
+    pub note: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// A move of an order to another status, with what that move records
+#[derive(Clone, Debug)]
+pub enum OrderTransition {
+    Confirm,
+    Ship(ShipOrderRequest),
+    Deliver,
+    /// Cancels the whole order, returning its stock and recording a refund
+    Cancel(CancelOrderRequest),
+}
+
+impl OrderTransition {
+    pub fn target(&self) -> OrderStatus {
+        match self {
+            OrderTransition::Confirm => OrderStatus::Confirmed,
+            OrderTransition::Ship(_) => OrderStatus::Shipped,
+            OrderTransition::Deliver => OrderStatus::Delivered,
+            OrderTransition::Cancel(_) => OrderStatus::Cancelled,
+        }
+    }
+}
+
+/// Body of `POST /orders/{id}/ship`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ShipOrderRequest {
+    pub tracking_number: String,
+    pub estimated_delivery_date: NaiveDate,
+}
+
+/// Body of `POST /orders`. Prices and the total are taken from the
+/// listings at the time of ordering, never from the client.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlaceOrderRequest {
+    pub items: Vec<OrderLineRequest>,
+    pub shipping_address: String,
+    pub shipping_method: String,
+    pub special_instructions: Option<String>,
+}
+
+/// One line of a new order
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OrderLineRequest {
+    pub equipment_id: i32,
+    pub quantity: i32,
+    pub warranty_selected: Option<bool>,
+    pub special_requirements: Option<String>,
+}
+
+/// An order with its lines
+#[derive(Serialize, Debug)]
+pub struct OrderDetails {
+    #[serde(flatten)]
+    pub order: Order,
+    pub items: Vec<OrderItem>,
+}
+
+/// Why an order or line was cancelled, stored in `order_cancellations.reason`
+#[derive(AsExpression, FromSqlRow, Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "snake_case")]
+pub enum CancellationReason {
+    BuyerRequest,
+    DuplicateOrder,
+    PricingError,
+    OutOfStock,
+    UnableToFulfil,
+    Other,
+}
+
+impl CancellationReason {
+    pub const ALL: [CancellationReason; 6] = [
+        CancellationReason::BuyerRequest,
+        CancellationReason::DuplicateOrder,
+        CancellationReason::PricingError,
+        CancellationReason::OutOfStock,
+        CancellationReason::UnableToFulfil,
+        CancellationReason::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CancellationReason::BuyerRequest => "buyer_request",
+            CancellationReason::DuplicateOrder => "duplicate_order",
+            CancellationReason::PricingError => "pricing_error",
+            CancellationReason::OutOfStock => "out_of_stock",
+            CancellationReason::UnableToFulfil => "unable_to_fulfil",
+            CancellationReason::Other => "other",
+        }
+    }
+}
+
+impl std::fmt::Display for CancellationReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for CancellationReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CancellationReason::ALL
+            .into_iter()
+            .find(|value| value.as_str() == s)
+            .ok_or_else(|| format!(
+                "Unknown cancellation reason '{}', expected one of: {}",
+                s,
+                CancellationReason::ALL.map(|value| value.as_str()).join(", ")
+            ))
+    }
+}
+
+/// Parsed through `FromStr` so unknown values are reported with the list
+/// of accepted ones
+impl<'de> Deserialize<'de> for CancellationReason {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl ToSql<Varchar, Pg> for CancellationReason {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Varchar, Pg> for CancellationReason {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Varchar, Pg>>::from_sql(bytes)?;
+        value.parse().map_err(Into::into)
+    }
+}
+
+/// Body of `POST /orders/{id}/cancel` and
+/// `POST /orders/{id}/items/{item_id}/cancel`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CancelOrderRequest {
+    pub reason: CancellationReason,
+    pub note: Option<String>,
+}
+
+/// A cancellation of a whole order, or of one line when `order_item_id`
+/// is set
+#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize, Clone, Debug)]
+#[diesel(belongs_to(Order))]
+#[diesel(table_name = order_cancellations)]
+pub struct OrderCancellation {
+    pub id: i32,
+    pub order_id: i32,
+    pub order_item_id: Option<i32>,
+    pub reason: CancellationReason,
+    pub note: Option<String>,
+    pub cancelled_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = order_cancellations)]
+pub struct NewOrderCancellation {
+    pub order_id: i32,
+    pub order_item_id: Option<i32>,
+    pub reason: CancellationReason,
+    pub note: Option<String>,
+    pub cancelled_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+/// What is owed back to the buyer for a cancellation, at the prices the
+/// cancelled lines were ordered at
+#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize, Clone, Debug)]
+#[diesel(belongs_to(Order))]
+#[diesel(belongs_to(OrderCancellation, foreign_key = cancellation_id))]
+#[diesel(table_name = refunds)]
+pub struct Refund {
+    pub id: i32,
+    pub order_id: i32,
+    pub cancellation_id: i32,
+    pub amount: BigDecimal,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = refunds)]
+pub struct NewRefund {
+    pub order_id: i32,
+    pub cancellation_id: i32,
+    pub amount: BigDecimal,
+    pub created_at: NaiveDateTime,
+}
+
+/// A cancellation with its refund, which is absent when the cancelled lines
+/// cost nothing
+#[derive(Serialize, Clone, Debug)]
+pub struct CancellationRecord {
+    #[serde(flatten)]
+    pub cancellation: OrderCancellation,
+    pub refund: Option<Refund>,
+}
+
+/// Result of a cancellation: what was recorded and the order as it is now
+#[derive(Serialize, Debug)]
+pub struct CancellationOutcome {
+    pub cancellation: CancellationRecord,
+    pub order: OrderDetails,
+}
+
+/// Cancellations with one reason over a reporting period
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CancellationSummary {
+    pub reason: CancellationReason,
+    pub cancellations: i64,
+    pub refunded_amount: BigDecimal,
+}
+
+/// A user's shopping cart. Each user has at most one; it is created when
+/// the first item is added and emptied by checkout.
+#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize, Clone, Debug)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name = carts)]
+pub struct Cart {
+    pub id: i32,
+    pub user_id: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// One line of a cart. `price_when_added` is the listing's price when the
+/// line was added, kept to point out changes; checkout charges the
+/// current price.
+#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize, Clone, Debug)]
+#[diesel(belongs_to(Cart))]
+#[diesel(belongs_to(Equipment))]
+#[diesel(table_name = cart_items)]
+pub struct CartItem {
+    pub id: i32,
+    pub cart_id: i32,
+    pub equipment_id: i32,
+    pub quantity: i32,
+    pub price_when_added: BigDecimal,
+    pub warranty_selected: Option<bool>,
+    pub special_requirements: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = cart_items)]
+pub struct NewCartItem {
+    pub cart_id: i32,
+    pub equipment_id: i32,
+    pub quantity: i32,
+    pub price_when_added: BigDecimal,
+    pub warranty_selected: Option<bool>,
+    pub special_requirements: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// Body of `POST /cart/items`. Adding a listing already in the cart adds
+/// to its quantity and replaces the line's options.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AddCartItemRequest {
+    pub equipment_id: i32,
+    pub quantity: i32,
+    pub warranty_selected: Option<bool>,
+    pub special_requirements: Option<String>,
+}
+
+/// Partial update payload for `PATCH /cart/items/{id}`. `null` clears an
+/// optional field.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UpdateCartItem {
+    pub quantity: Option<i32>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub warranty_selected: Option<Option<bool>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub special_requirements: Option<Option<String>>,
+}
+
+/// Columns of `cart_items` a buyer may change. `None` leaves a column as
+/// is; `Some(None)` sets a nullable column to NULL.
+#[derive(AsChangeset, Clone, Debug)]
+#[diesel(table_name = cart_items)]
+pub struct CartItemChangeset {
+    pub quantity: Option<i32>,
+    pub warranty_selected: Option<Option<bool>>,
+    pub special_requirements: Option<Option<String>>,
+    pub updated_at: NaiveDateTime,
+}
+
+impl UpdateCartItem {
+    pub fn into_changeset(self, now: NaiveDateTime) -> CartItemChangeset {
+        CartItemChangeset {
+            quantity: self.quantity,
+            warranty_selected: self.warranty_selected,
+            special_requirements: self.special_requirements,
+            updated_at: now,
+        }
+    }
+}
+
+/// A cart line checked against its listing as it is now
+#[derive(Serialize, Clone, Debug)]
+pub struct CartLine {
+    #[serde(flatten)]
+    pub item: CartItem,
+    pub equipment_name: String,
+    /// The listing's current price, which checkout charges
+    pub unit_price: BigDecimal,
+    pub line_total: BigDecimal,
+    pub stock_level: i32,
+    /// Whether the price differs from `price_when_added`
+    pub price_changed: bool,
+    /// Whether there is enough stock for the line's quantity, leaving out
+    /// what other buyers hold
+    pub available: bool,
+    /// When the caller's hold on the listing runs out, if they have one
+    pub reserved_until: Option<NaiveDateTime>,
+}
+
+/// A cart revalidated against current prices and stock. `ready_for_checkout`
+/// is false when the cart is empty or a line cannot be filled.
+#[derive(Serialize, Clone, Debug)]
+pub struct CartView {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub items: Vec<CartLine>,
+    pub total_amount: BigDecimal,
+    pub ready_for_checkout: bool,
+}
+
+/// A quantity of a listing held for a buyer until `expires_at`, so others
+/// cannot buy it while they check out
+#[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize, Clone, Debug)]
+#[diesel(belongs_to(User))]
+#[diesel(belongs_to(Equipment))]
+#[diesel(table_name = stock_reservations)]
+pub struct StockReservation {
+    pub id: i32,
+    pub user_id: i32,
+    pub equipment_id: i32,
+    pub quantity: i32,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = stock_reservations)]
+pub struct NewStockReservation {
+    pub user_id: i32,
+    pub equipment_id: i32,
+    pub quantity: i32,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+/// Body of `POST /cart/checkout`. When `expected_total` is given, checkout
+/// fails instead of charging a different total, e.g. because prices
+/// changed since the cart was last viewed.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CheckoutRequest {
+    pub shipping_address: String,
+    pub shipping_method: String,
+    pub special_instructions: Option<String>,
+    pub expected_total: Option<BigDecimal>,
+}
+
 #[derive(Queryable, Selectable, Identifiable, Associations, Serialize, Deserialize, Debug)]
 #[diesel(belongs_to(Equipment))]
 #[diesel(belongs_to(User))]
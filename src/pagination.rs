@@ -0,0 +1,82 @@
+use serde::Serialize;
+
+pub const DEFAULT_PER_PAGE: i64 = 20;
+pub const MAX_PER_PAGE: i64 = 100;
+
+/// A validated page request. Out-of-range values are clamped rather than
+/// rejected so that clients can't trigger errors with large page sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pagination {
+    pub page: i64,
+    pub per_page: i64,
+}
+
+impl Pagination {
+    pub fn new(page: Option<i64>, per_page: Option<i64>) -> Self {
+        Self {
+            page: page.unwrap_or(1).max(1),
+            per_page: per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE),
+        }
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page - 1) * self.per_page
+    }
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+/// One page of results together with the total number of matching rows
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+    pub total_pages: i64,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, pagination: Pagination, total: i64) -> Self {
+        Self {
+            items,
+            page: pagination.page,
+            per_page: pagination.per_page,
+            total,
+            total_pages: (total + pagination.per_page - 1) / pagination.per_page,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            page: self.page,
+            per_page: self.per_page,
+            total: self.total,
+            total_pages: self.total_pages,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pagination_clamps_values() {
+        assert_eq!(Pagination::new(None, None), Pagination { page: 1, per_page: DEFAULT_PER_PAGE });
+        assert_eq!(Pagination::new(Some(0), Some(1000)), Pagination { page: 1, per_page: MAX_PER_PAGE });
+        assert_eq!(Pagination::new(Some(3), Some(10)).offset(), 20);
+    }
+
+    #[test]
+    fn test_page_total_pages() {
+        let page = Page::new(vec![1, 2], Pagination::new(Some(1), Some(2)), 5);
+        assert_eq!(page.total_pages, 3);
+        assert_eq!(Page::<i32>::new(vec![], Pagination::default(), 0).total_pages, 0);
+    }
+}
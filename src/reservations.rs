@@ -0,0 +1,37 @@
+//! Time-limited stock holds for buyers checking out. Holds count against
+//! availability only until they expire, so removing expired ones is
+//! housekeeping that runs in the background.
+
+use std::time::Duration;
+use log::{error, info};
+use crate::db::{self, DbPool};
+
+/// How long stock stays held. Registered as app data for the handlers that
+/// create holds.
+#[derive(Clone, Copy, Debug)]
+pub struct ReservationConfig {
+    pub ttl: chrono::Duration,
+}
+
+impl ReservationConfig {
+    pub fn new(ttl_secs: i64) -> Self {
+        Self { ttl: chrono::Duration::seconds(ttl_secs) }
+    }
+}
+
+/// Removes expired holds every `every` until the runtime shuts down
+pub fn spawn_expiry_task(pool: DbPool, every: Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        loop {
+            interval.tick().await;
+            let pool = pool.clone();
+            match tokio::task::spawn_blocking(move || db::reservations::release_expired(&pool)).await {
+                Ok(Ok(0)) => {}
+                Ok(Ok(released)) => info!("Released {} expired stock reservations", released),
+                Ok(Err(e)) => error!("Failed to release expired stock reservations: {}", e),
+                Err(e) => error!("Stock reservation sweep panicked: {}", e),
+            }
+        }
+    })
+}
@@ -0,0 +1,191 @@
+use actix_web::web;
+use crate::errors::{codes, ServiceError};
+use crate::handlers;
+
+/// Central route table: every HTTP endpoint the service exposes is registered here
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    // Malformed bodies, query strings and paths get the same error format as
+    // everything else
+    cfg.app_data(web::JsonConfig::default().error_handler(|err, _| {
+        ServiceError::BadRequest(err.to_string()).with_code(codes::INVALID_JSON).into()
+    }))
+    .app_data(web::QueryConfig::default().error_handler(|err, _| {
+        ServiceError::BadRequest(err.to_string()).with_code(codes::INVALID_QUERY).into()
+    }))
+    .app_data(web::PathConfig::default().error_handler(|err, _| {
+        ServiceError::BadRequest(err.to_string()).with_code(codes::INVALID_PATH).into()
+    }));
+
+    cfg.service(web::resource("/health").route(web::get().to(handlers::health_check)))
+        .service(
+            web::scope("/users")
+                .service(
+                    web::resource("")
+                        .route(web::post().to(handlers::users::create_user))
+                        .route(web::get().to(handlers::users::list_users))
+                )
+                // Registered before `/{id}` so "me" is not parsed as an id
+                .service(web::resource("/me").route(web::get().to(handlers::users::get_current_user)))
+                .service(
+                    web::resource("/{id}")
+                        .route(web::get().to(handlers::users::get_user))
+                        .route(web::put().to(handlers::users::update_user))
+                        .route(web::patch().to(handlers::users::patch_user))
+                        .route(web::delete().to(handlers::users::delete_user))
+                )
+                .service(web::resource("/{id}/anonymize").route(web::post().to(handlers::users::anonymize_user)))
+                .service(web::resource("/{id}/restore").route(web::post().to(handlers::users::restore_user)))
+        )
+        .service(
+            web::scope("/categories")
+                .service(
+                    web::resource("")
+                        .route(web::post().to(handlers::categories::create_category))
+                        .route(web::get().to(handlers::categories::list_categories))
+                )
+                // Registered before `/{id}` so "tree" is not parsed as an id
+                .service(web::resource("/tree").route(web::get().to(handlers::categories::category_tree)))
+                .service(
+                    web::resource("/{id}")
+                        .route(web::get().to(handlers::categories::get_category))
+                        .route(web::put().to(handlers::categories::update_category))
+                        .route(web::patch().to(handlers::categories::patch_category))
+                        .route(web::delete().to(handlers::categories::delete_category))
+                )
+                .service(web::resource("/{id}/breadcrumbs").route(web::get().to(handlers::categories::get_breadcrumbs)))
+                .service(web::resource("/{id}/equipment").route(web::get().to(handlers::categories::list_category_equipment)))
+                .service(
+                    web::resource("/{id}/spec-schema")
+                        .route(web::get().to(handlers::categories::get_spec_schema))
+                        .route(web::put().to(handlers::categories::replace_spec_schema))
+                )
+        )
+        .service(
+            web::scope("/equipment")
+                .service(
+                    web::resource("")
+                        .route(web::post().to(handlers::equipment::create_equipment))
+                        .route(web::get().to(handlers::equipment::list_equipment))
+                )
+                // Registered before `/{id}` so "search", "import" and "export"
+                // are not parsed as ids
+                .service(web::resource("/search").route(web::get().to(handlers::equipment::search_equipment)))
+                .service(
+                    web::resource("/import")
+                        .app_data(web::PayloadConfig::new(crate::catalog::MAX_IMPORT_BYTES))
+                        .route(web::post().to(handlers::equipment::import_equipment))
+                )
+                .service(web::resource("/export").route(web::get().to(handlers::equipment::export_equipment)))
+                .service(
+                    web::resource("/{id}/images")
+                        .route(web::get().to(handlers::images::list_images))
+                        .route(web::post().to(handlers::images::upload_image))
+                )
+                // Registered before `/{id}/images/{image_id}` so "order" is
+                // not parsed as an image id
+                .service(web::resource("/{id}/images/order").route(web::put().to(handlers::images::reorder_images)))
+                .service(web::resource("/{id}/images/{image_id}").route(web::delete().to(handlers::images::delete_image)))
+                .service(
+                    web::resource("/{id}/images/{image_id}/primary")
+                        .route(web::put().to(handlers::images::set_primary_image))
+                )
+                .service(
+                    web::resource("/{id}/documents")
+                        .route(web::get().to(handlers::documents::list_documents))
+                        .route(web::post().to(handlers::documents::upload_document))
+                )
+                .service(
+                    web::resource("/{id}/documents/{document_id}")
+                        .route(web::get().to(handlers::documents::get_document))
+                        .route(web::delete().to(handlers::documents::delete_document))
+                )
+                .service(
+                    web::resource("/{id}/documents/{document_id}/versions")
+                        .route(web::get().to(handlers::documents::list_document_versions))
+                )
+                .service(
+                    web::resource("/{id}")
+                        .route(web::get().to(handlers::equipment::get_equipment))
+                        .route(web::put().to(handlers::equipment::update_equipment))
+                        .route(web::patch().to(handlers::equipment::patch_equipment))
+                        .route(web::delete().to(handlers::equipment::delete_equipment))
+                )
+        )
+        .service(
+            web::scope("/cart")
+                .service(
+                    web::resource("")
+                        .route(web::get().to(handlers::carts::get_cart))
+                        .route(web::delete().to(handlers::carts::clear_cart))
+                )
+                .service(web::resource("/items").route(web::post().to(handlers::carts::add_cart_item)))
+                .service(
+                    web::resource("/items/{item_id}")
+                        .route(web::patch().to(handlers::carts::update_cart_item))
+                        .route(web::delete().to(handlers::carts::remove_cart_item))
+                )
+                .service(
+                    web::resource("/reserve")
+                        .route(web::post().to(handlers::carts::reserve_cart))
+                        .route(web::delete().to(handlers::carts::release_reservations))
+                )
+                .service(web::resource("/checkout").route(web::post().to(handlers::carts::checkout)))
+        )
+        .service(
+            web::scope("/orders")
+                .service(web::resource("").route(web::post().to(handlers::orders::place_order)))
+                .service(
+                    web::resource("/cancellations/summary")
+                        .route(web::get().to(handlers::orders::cancellation_summary))
+                )
+                .service(web::resource("/{id}").route(web::get().to(handlers::orders::get_order)))
+                .service(
+                    web::resource("/{id}/history")
+                        .route(web::get().to(handlers::orders::get_order_history))
+                )
+                .service(
+                    web::resource("/{id}/confirm")
+                        .route(web::post().to(handlers::orders::confirm_order))
+                )
+                .service(
+                    web::resource("/{id}/ship")
+                        .route(web::post().to(handlers::orders::ship_order))
+                )
+                .service(
+                    web::resource("/{id}/deliver")
+                        .route(web::post().to(handlers::orders::deliver_order))
+                )
+                .service(
+                    web::resource("/{id}/cancel")
+                        .route(web::post().to(handlers::orders::cancel_order))
+                )
+                .service(
+                    web::resource("/{id}/cancellations")
+                        .route(web::get().to(handlers::orders::list_order_cancellations))
+                )
+                .service(
+                    web::resource("/{id}/items/{item_id}/cancel")
+                        .route(web::post().to(handlers::orders::cancel_order_item))
+                )
+        )
+        .service(
+            web::scope("/auth")
+                .service(web::resource("/login").route(web::post().to(handlers::auth::login)))
+                .service(
+                    web::resource("/verify-email/request")
+                        .route(web::post().to(handlers::auth::request_email_verification))
+                )
+                .service(
+                    web::resource("/verify-email/confirm")
+                        .route(web::post().to(handlers::auth::confirm_email_verification))
+                )
+                .service(
+                    web::resource("/password-reset/request")
+                        .route(web::post().to(handlers::auth::request_password_reset))
+                )
+                .service(
+                    web::resource("/password-reset/confirm")
+                        .route(web::post().to(handlers::auth::confirm_password_reset))
+                )
+        );
+}
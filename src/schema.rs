@@ -1,5 +1,55 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    cart_items (id) {
+        id -> Int4,
+        cart_id -> Int4,
+        equipment_id -> Int4,
+        quantity -> Int4,
+        price_when_added -> Numeric,
+        warranty_selected -> Nullable<Bool>,
+        special_requirements -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    carts (id) {
+        id -> Int4,
+        user_id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    category_spec_attributes (id) {
+        id -> Int4,
+        category_id -> Int4,
+        #[max_length = 64]
+        name -> Varchar,
+        data_type -> Varchar,
+        #[max_length = 32]
+        unit -> Nullable<Varchar>,
+        required -> Bool,
+        allowed_values -> Nullable<Jsonb>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    email_verification_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token_hash -> Varchar,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     equipment (id) {
         id -> Int4,
@@ -14,12 +64,17 @@ diesel::table! {
         stock_level -> Int4,
         specifications -> Nullable<Jsonb>,
         weight_kg -> Nullable<Numeric>,
-        dimensions_cm -> Nullable<Varchar>,
         power_requirements -> Nullable<Varchar>,
         certification_info -> Nullable<Text>,
         warranty_info -> Nullable<Text>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        supplier_id -> Nullable<Int4>,
+        length_cm -> Nullable<Numeric>,
+        width_cm -> Nullable<Numeric>,
+        height_cm -> Nullable<Numeric>,
+        // `search_vector` (a generated tsvector) is deliberately not declared;
+        // see db::search
     }
 }
 
@@ -39,8 +94,17 @@ diesel::table! {
         id -> Int4,
         equipment_id -> Int4,
         image_url -> Varchar,
-        is_primary -> Nullable<Bool>,
+        is_primary -> Bool,
         created_at -> Timestamp,
+        position -> Int4,
+        #[max_length = 255]
+        storage_key -> Nullable<Varchar>,
+        #[max_length = 64]
+        content_type -> Nullable<Varchar>,
+        byte_size -> Nullable<Int4>,
+        width -> Nullable<Int4>,
+        height -> Nullable<Int4>,
+        thumbnails -> Jsonb,
     }
 }
 
@@ -66,6 +130,31 @@ diesel::table! {
         price_at_time -> Numeric,
         warranty_selected -> Nullable<Bool>,
         special_requirements -> Nullable<Text>,
+        cancelled_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    order_cancellations (id) {
+        id -> Int4,
+        order_id -> Int4,
+        order_item_id -> Nullable<Int4>,
+        reason -> Varchar,
+        note -> Nullable<Text>,
+        cancelled_by -> Nullable<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    order_status_history (id) {
+        id -> Int4,
+        order_id -> Int4,
+        from_status -> Nullable<Varchar>,
+        to_status -> Varchar,
+        changed_by -> Nullable<Int4>,
+        note -> Nullable<Text>,
+        created_at -> Timestamp,
     }
 }
 
@@ -85,6 +174,27 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    password_reset_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token_hash -> Varchar,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    refunds (id) {
+        id -> Int4,
+        order_id -> Int4,
+        cancellation_id -> Int4,
+        amount -> Numeric,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     reviews (id) {
         id -> Int4,
@@ -100,6 +210,17 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    stock_reservations (id) {
+        id -> Int4,
+        user_id -> Int4,
+        equipment_id -> Int4,
+        quantity -> Int4,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     technical_documents (id) {
         id -> Int4,
@@ -108,6 +229,17 @@ diesel::table! {
         document_url -> Varchar,
         version -> Nullable<Varchar>,
         created_at -> Timestamp,
+        #[max_length = 200]
+        title -> Varchar,
+        revision -> Int4,
+        #[max_length = 255]
+        storage_key -> Nullable<Varchar>,
+        #[max_length = 64]
+        content_type -> Nullable<Varchar>,
+        byte_size -> Nullable<Int4>,
+        #[max_length = 255]
+        file_name -> Nullable<Varchar>,
+        uploaded_by -> Nullable<Int4>,
     }
 }
 
@@ -122,27 +254,57 @@ diesel::table! {
         contact_number -> Nullable<Varchar>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        email_verified_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+        anonymized_at -> Nullable<Timestamp>,
+        token_version -> Int4,
     }
 }
 
+diesel::joinable!(cart_items -> carts (cart_id));
+diesel::joinable!(cart_items -> equipment (equipment_id));
+diesel::joinable!(carts -> users (user_id));
+diesel::joinable!(category_spec_attributes -> equipment_categories (category_id));
+diesel::joinable!(email_verification_tokens -> users (user_id));
 diesel::joinable!(equipment -> equipment_categories (category_id));
+diesel::joinable!(equipment -> users (supplier_id));
 diesel::joinable!(equipment_images -> equipment (equipment_id));
 diesel::joinable!(maintenance_records -> equipment (equipment_id));
+diesel::joinable!(order_cancellations -> order_items (order_item_id));
+diesel::joinable!(order_cancellations -> orders (order_id));
+diesel::joinable!(order_cancellations -> users (cancelled_by));
 diesel::joinable!(order_items -> equipment (equipment_id));
 diesel::joinable!(order_items -> orders (order_id));
+diesel::joinable!(order_status_history -> orders (order_id));
+diesel::joinable!(order_status_history -> users (changed_by));
 diesel::joinable!(orders -> users (user_id));
+diesel::joinable!(password_reset_tokens -> users (user_id));
+diesel::joinable!(refunds -> order_cancellations (cancellation_id));
+diesel::joinable!(refunds -> orders (order_id));
 diesel::joinable!(reviews -> equipment (equipment_id));
 diesel::joinable!(reviews -> users (user_id));
+diesel::joinable!(stock_reservations -> equipment (equipment_id));
+diesel::joinable!(stock_reservations -> users (user_id));
 diesel::joinable!(technical_documents -> equipment (equipment_id));
+diesel::joinable!(technical_documents -> users (uploaded_by));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    cart_items,
+    carts,
+    category_spec_attributes,
+    email_verification_tokens,
     equipment,
     equipment_categories,
     equipment_images,
     maintenance_records,
+    order_cancellations,
     order_items,
+    order_status_history,
     orders,
+    password_reset_tokens,
+    refunds,
     reviews,
+    stock_reservations,
     technical_documents,
     users,
 );
@@ -7,8 +7,15 @@ use chrono::Utc;
 use std::sync::Once;
 use diesel::RunQueryDsl;
 use diesel::result::Error as DieselError;
-use crate::schema::{users::dsl::*, orders::dsl::*, order_items::dsl::*, equipment::dsl::*, equipment_categories::dsl::*, equipment_images::dsl::*, reviews::dsl::*, maintenance_records::dsl::*, technical_documents::dsl::*};
+use crate::schema::{users::dsl::*, stock_reservations::dsl::*, carts::dsl::*, cart_items::dsl::*, orders::dsl::*, order_items::dsl::*, order_status_history::dsl::*, order_cancellations::dsl::*, refunds::dsl::*, equipment::dsl::*, equipment_categories::dsl::*, equipment_images::dsl::*, reviews::dsl::*, maintenance_records::dsl::*, technical_documents::dsl::*, email_verification_tokens::dsl::*, password_reset_tokens::dsl::*};
 use crate::db;
+use crate::auth::{password, TokenConfig};
+use crate::media::LocalMediaStore;
+use crate::models::{Equipment, EquipmentCategory, EquipmentCondition, NewEquipment, NewEquipmentCategory, NewUser, OrderDetails, PlaceOrderRequest, User, UserRole};
+use crate::reservations::ReservationConfig;
+use actix_web::{body::BoxBody, dev::{ServiceFactory, ServiceRequest, ServiceResponse}, web, App};
+use diesel::prelude::*;
+use serde_json::json;
 
 // Used to ensure logger is initialized only once
 static INIT: Once = Once::new();
@@ -38,6 +45,155 @@ pub fn setup() {
     info!("Running test setup at {}", Utc::now());
 }
 
+/// Token settings for tests, keyed from `JWT_SECRET` like the server's own
+pub fn token_config() -> TokenConfig {
+    let secret = env::var("JWT_SECRET")
+        .unwrap_or_else(|_| "test-only-secret-not-for-production-0123456789".to_string());
+    TokenConfig::new(secret.as_bytes(), 3600)
+}
+
+/// The application as the server configures it, with test tokens. Tests
+/// that need more app data, such as a media store, add it to the result.
+pub fn app(
+    pool: &db::DbPool,
+) -> App<impl ServiceFactory<ServiceRequest, Config = (), Response = ServiceResponse<BoxBody>, Error = actix_web::Error, InitError = ()>> {
+    App::new()
+        .app_data(web::Data::new(pool.clone()))
+        .app_data(web::Data::new(token_config()))
+        .app_data(web::Data::new(ReservationConfig::new(900)))
+        .configure(crate::configure)
+}
+
+/// A media store in a fresh temporary directory
+pub fn media_store() -> LocalMediaStore {
+    let dir = env::temp_dir().join(format!("media_{}", uuid::Uuid::new_v4().simple()));
+    LocalMediaStore::new(dir, "http://localhost:8080/media").expect("Failed to create media store")
+}
+
+/// Password of every user created by `create_test_user`
+pub const TEST_PASSWORD: &str = "haul truck 797";
+
+/// Inserts a user with a unique username/email and the given role
+pub fn create_test_user(pool: &db::DbPool, role: Option<UserRole>) -> User {
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    let hash = password::hash_password(TEST_PASSWORD)
+        .expect("Failed to hash test password");
+    let mut new_user = NewUser::new(
+        format!("user_{}", &suffix[..12]),
+        format!("user_{}@example.com", &suffix[..12]),
+        hash,
+    );
+    new_user.business_type = role;
+    db::users::create_user(pool, new_user).expect("Failed to create test user")
+}
+
+/// A buyer whose email address is verified, as placing orders requires
+pub fn create_verified_buyer(pool: &db::DbPool) -> User {
+    use diesel::{ExpressionMethods, QueryDsl};
+
+    let user = create_test_user(pool, Some(UserRole::Buyer));
+    let conn = &mut pool.get().expect("Failed to get db connection");
+    diesel::update(crate::schema::users::table.find(user.id))
+        .set(crate::schema::users::email_verified_at.eq(Utc::now().naive_utc()))
+        .get_result(conn)
+        .expect("Failed to verify test user")
+}
+
+/// `Authorization` header value for the given user
+pub fn bearer_header(user: &User) -> (&'static str, String) {
+    let token = token_config().issue(user.id, user.token_version).expect("Failed to issue test token");
+    ("Authorization", format!("Bearer {}", token.access_token))
+}
+
+/// Inserts a top-level category with a unique name
+pub fn create_test_category(pool: &db::DbPool) -> EquipmentCategory {
+    let conn = &mut pool.get().expect("Failed to get db connection");
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    let now = Utc::now().naive_utc();
+    diesel::insert_into(crate::schema::equipment_categories::table)
+        .values(NewEquipmentCategory {
+            name: format!("Category {}", &suffix[..12]),
+            description: None,
+            parent_category_id: None,
+            created_at: now,
+            updated_at: now,
+        })
+        .get_result(conn)
+        .expect("Failed to create test category")
+}
+
+/// Shipping address and method for test orders and checkouts
+pub fn shipping_details() -> serde_json::Value {
+    json!({
+        "shipping_address": "12 Pit Road, Kalgoorlie WA 6430",
+        "shipping_method": "freight",
+    })
+}
+
+/// Body of `POST /orders` for the given `(equipment_id, quantity)` lines
+pub fn order_request(lines: &[(i32, i32)]) -> serde_json::Value {
+    let items: Vec<_> = lines
+        .iter()
+        .map(|(listing, count)| json!({ "equipment_id": listing, "quantity": count }))
+        .collect();
+    let mut body = shipping_details();
+    body["items"] = json!(items);
+    body
+}
+
+/// Places an order directly, for tests about what happens after
+pub fn place_order(pool: &db::DbPool, buyer_id: i32, lines: &[(i32, i32)]) -> OrderDetails {
+    let request: PlaceOrderRequest = serde_json::from_value(order_request(lines)).expect("valid order request");
+    db::orders::place_order(pool, buyer_id, &request).expect("Failed to place test order")
+}
+
+pub fn stock_level(pool: &db::DbPool, listing: i32) -> i32 {
+    use crate::schema::equipment;
+    let conn = &mut pool.get().expect("Failed to get db connection");
+    equipment::table
+        .find(listing)
+        .select(equipment::stock_level)
+        .first(conn)
+        .expect("Failed to load stock level")
+}
+
+pub fn set_stock_level(pool: &db::DbPool, listing: i32, level: i32) {
+    use crate::schema::equipment;
+    let conn = &mut pool.get().expect("Failed to get db connection");
+    diesel::update(equipment::table.find(listing))
+        .set(equipment::stock_level.eq(level))
+        .execute(conn)
+        .expect("Failed to set stock level");
+}
+
+/// Inserts a used excavator listing in the given category
+pub fn create_test_equipment(pool: &db::DbPool, category: i32, supplier: Option<i32>) -> Equipment {
+    let now = Utc::now().naive_utc();
+    db::equipment::create_equipment(pool, NewEquipment {
+        category_id: category,
+        name: "Test Excavator".to_string(),
+        description: Some("Low hours, full service history".to_string()),
+        manufacturer: "Caterpillar".to_string(),
+        model_number: "320D".to_string(),
+        year_manufactured: Some(2018),
+        condition: EquipmentCondition::Used,
+        price: "125000.00".parse().expect("valid decimal"),
+        stock_level: 3,
+        specifications: None,
+        weight_kg: None,
+        power_requirements: None,
+        certification_info: None,
+        warranty_info: None,
+        created_at: now,
+        updated_at: now,
+        supplier_id: supplier,
+        length_cm: None,
+        width_cm: None,
+        height_cm: None,
+    })
+    .expect("Failed to create test equipment")
+}
+
 pub fn cleanup_database(pool: &db::DbPool) {
     let conn = &mut pool.get().expect("Failed to get db connection");
     info!("Starting database cleanup at {}", Utc::now());
@@ -48,6 +204,21 @@ pub fn cleanup_database(pool: &db::DbPool) {
         .run::<_, DieselError, _>(|conn| {
             // Delete in order of dependencies to avoid foreign key violations
             // First delete tables that have foreign keys to other tables
+            match diesel::delete(stock_reservations).execute(conn) {
+                Ok(count) => info!("Deleted {} records from stock_reservations", count),
+                Err(e) => error!("Error deleting stock_reservations: {}", e),
+            }
+
+            match diesel::delete(cart_items).execute(conn) {
+                Ok(count) => info!("Deleted {} records from cart_items", count),
+                Err(e) => error!("Error deleting cart_items: {}", e),
+            }
+
+            match diesel::delete(carts).execute(conn) {
+                Ok(count) => info!("Deleted {} records from carts", count),
+                Err(e) => error!("Error deleting carts: {}", e),
+            }
+
             match diesel::delete(technical_documents).execute(conn) {
                 Ok(count) => info!("Deleted {} records from technical_documents", count),
                 Err(e) => error!("Error deleting technical_documents: {}", e),
@@ -68,11 +239,26 @@ pub fn cleanup_database(pool: &db::DbPool) {
                 Err(e) => error!("Error deleting equipment_images: {}", e),
             }
 
+            match diesel::delete(refunds).execute(conn) {
+                Ok(count) => info!("Deleted {} records from refunds", count),
+                Err(e) => error!("Error deleting refunds: {}", e),
+            }
+
+            match diesel::delete(order_cancellations).execute(conn) {
+                Ok(count) => info!("Deleted {} records from order_cancellations", count),
+                Err(e) => error!("Error deleting order_cancellations: {}", e),
+            }
+
             match diesel::delete(order_items).execute(conn) {
                 Ok(count) => info!("Deleted {} records from order_items", count),
                 Err(e) => error!("Error deleting order_items: {}", e),
             }
 
+            match diesel::delete(order_status_history).execute(conn) {
+                Ok(count) => info!("Deleted {} records from order_status_history", count),
+                Err(e) => error!("Error deleting order_status_history: {}", e),
+            }
+
             match diesel::delete(orders).execute(conn) {
                 Ok(count) => info!("Deleted {} records from orders", count),
                 Err(e) => error!("Error deleting orders: {}", e),
@@ -88,6 +274,16 @@ pub fn cleanup_database(pool: &db::DbPool) {
                 Err(e) => error!("Error deleting equipment_categories: {}", e),
             }
 
+            match diesel::delete(email_verification_tokens).execute(conn) {
+                Ok(count) => info!("Deleted {} records from email_verification_tokens", count),
+                Err(e) => error!("Error deleting email_verification_tokens: {}", e),
+            }
+
+            match diesel::delete(password_reset_tokens).execute(conn) {
+                Ok(count) => info!("Deleted {} records from password_reset_tokens", count),
+                Err(e) => error!("Error deleting password_reset_tokens: {}", e),
+            }
+
             // Finally delete users, which other tables depend on
             match diesel::delete(users).execute(conn) {
                 Ok(count) => info!("Deleted {} records from users", count),
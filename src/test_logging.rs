@@ -25,7 +25,7 @@ pub struct TestMetrics {
     error_message: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct CrudStats {
     creates: usize,
     reads: usize,
@@ -42,17 +42,6 @@ pub struct PerformanceStats {
     operations_per_second: f64,
 }
 
-impl Default for CrudStats {
-    fn default() -> Self {
-        Self {
-            creates: 0,
-            reads: 0,
-            updates: 0,
-            deletes: 0,
-        }
-    }
-}
-
 impl Default for PerformanceStats {
     fn default() -> Self {
         Self {
@@ -192,7 +181,7 @@ where
 
     loop {
         let op_start = Instant::now();
-        if let Err(_) = operation() {
+        if operation().is_err() {
             break;
         }
         let duration = op_start.elapsed();
@@ -0,0 +1,75 @@
+//! Unit conversion for presenting measurements. Weights and dimensions are
+//! always stored and accepted in metric units; imperial values are derived
+//! on read.
+
+use std::str::FromStr;
+use bigdecimal::{BigDecimal, RoundingMode};
+use serde::{Deserialize, Serialize};
+use crate::models::Equipment;
+
+/// Kilograms in a pound, exact by definition
+const KG_PER_LB: &str = "0.45359237";
+/// Centimetres in an inch, exact by definition
+const CM_PER_IN: &str = "2.54";
+/// Decimal places of converted values, matching the stored metric ones
+const CONVERTED_SCALE: i64 = 2;
+
+/// Unit system measurements are presented in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitSystem {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+fn divide(value: &BigDecimal, divisor: &str) -> BigDecimal {
+    let divisor = BigDecimal::from_str(divisor).expect("valid conversion factor");
+    (value / divisor).with_scale_round(CONVERTED_SCALE, RoundingMode::HalfUp)
+}
+
+pub fn kg_to_lb(kg: &BigDecimal) -> BigDecimal {
+    divide(kg, KG_PER_LB)
+}
+
+pub fn cm_to_in(cm: &BigDecimal) -> BigDecimal {
+    divide(cm, CM_PER_IN)
+}
+
+/// Weight and dimensions of a listing in pounds and inches, presented next
+/// to the metric values
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImperialMeasurements {
+    pub weight_lb: Option<BigDecimal>,
+    pub length_in: Option<BigDecimal>,
+    pub width_in: Option<BigDecimal>,
+    pub height_in: Option<BigDecimal>,
+}
+
+impl ImperialMeasurements {
+    pub fn of(item: &Equipment) -> Self {
+        Self {
+            weight_lb: item.weight_kg.as_ref().map(kg_to_lb),
+            length_in: item.length_cm.as_ref().map(cm_to_in),
+            width_in: item.width_cm.as_ref().map(cm_to_in),
+            height_in: item.height_cm.as_ref().map(cm_to_in),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(value: &str) -> BigDecimal {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_conversions_round_to_two_places() {
+        assert_eq!(kg_to_lb(&decimal("45000.00")), decimal("99208.02"));
+        assert_eq!(kg_to_lb(&decimal("0.45359237")), decimal("1.00"));
+        assert_eq!(cm_to_in(&decimal("254.00")), decimal("100.00"));
+        assert_eq!(cm_to_in(&decimal("800")), decimal("314.96"));
+    }
+}
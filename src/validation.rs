@@ -0,0 +1,639 @@
+//! Declarative input validation.
+//!
+//! Models implement `Validate` by running their fields through a `Validator`,
+//! which collects every problem instead of stopping at the first one. The
+//! `Validated<T>` extractor deserializes a JSON body and validates it, so
+//! handlers only ever see well-formed input; failures become a single 400
+//! response listing each offending field.
+
+use std::fmt::Display;
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use bigdecimal::BigDecimal;
+use chrono::Datelike;
+use serde::de::DeserializeOwned;
+use crate::auth::password;
+use crate::db::search::EquipmentSearch;
+use crate::errors::{FieldError, ServiceError};
+use crate::models::{
+    AddCartItemRequest, CancelOrderRequest, CheckoutRequest, DocumentUpload, NewEquipment, NewEquipmentCategory,
+    NewEquipmentImage, NewMaintenanceRecord, NewOrder, NewOrderItem, NewReview, NewUser, PlaceOrderRequest,
+    RegisterUser, ShipOrderRequest, SpecAttribute, SpecDataType, SpecSchemaRequest, UpdateCartItem, UpdateUser,
+};
+
+pub const MAX_USERNAME_LENGTH: usize = 50;
+pub const MAX_EMAIL_LENGTH: usize = 100;
+/// Company names, equipment names, manufacturers and similar short labels
+pub const MAX_NAME_LENGTH: usize = 200;
+/// Free-text fields such as descriptions and review bodies
+pub const MAX_TEXT_LENGTH: usize = 10_000;
+pub const MAX_URL_LENGTH: usize = 2048;
+pub const MIN_RATING: i32 = 1;
+pub const MAX_RATING: i32 = 5;
+pub const EARLIEST_MANUFACTURE_YEAR: i32 = 1900;
+pub const MAX_SPEC_NAME_LENGTH: usize = 64;
+pub const MAX_SPEC_UNIT_LENGTH: usize = 32;
+/// Publisher version labels of technical documents, such as "Rev C"
+pub const MAX_VERSION_LENGTH: usize = 50;
+/// Most lines a single order may have
+pub const MAX_ORDER_LINES: usize = 100;
+
+/// Implemented by every input model that is checked before it reaches the database
+pub trait Validate {
+    fn validate_fields(&self, v: &mut Validator);
+
+    /// Runs all checks and reports every failing field at once
+    fn validate(&self) -> Result<(), ServiceError> {
+        let mut v = Validator::new();
+        self.validate_fields(&mut v);
+        v.finish()
+    }
+}
+
+/// Collects field errors for one input
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+/// "model_number" -> "Model number", for messages
+fn label(field: &str) -> String {
+    let text = field.replace('_', " ");
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => text,
+    }
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field: &str, reason: impl Into<String>) {
+        self.errors.push(FieldError::new(field, reason));
+    }
+
+    pub fn check(&mut self, ok: bool, field: &str, reason: impl Into<String>) {
+        if !ok {
+            self.add(field, reason);
+        }
+    }
+
+    /// Length in characters, inclusive on both ends
+    pub fn length(&mut self, field: &str, value: &str, min: usize, max: usize) {
+        let length = value.chars().count();
+        if length < min || length > max {
+            let reason = if min == 0 {
+                format!("{} must be at most {} characters", label(field), max)
+            } else {
+                format!("{} must be between {} and {} characters", label(field), min, max)
+            };
+            self.add(field, reason);
+        }
+    }
+
+    /// A required, non-blank label of at most `MAX_NAME_LENGTH` characters
+    pub fn name(&mut self, field: &str, value: &str) {
+        if value.trim().is_empty() {
+            self.add(field, format!("{} must not be blank", label(field)));
+        } else {
+            self.length(field, value, 1, MAX_NAME_LENGTH);
+        }
+    }
+
+    pub fn optional_length(&mut self, field: &str, value: Option<&str>, max: usize) {
+        if let Some(value) = value {
+            self.length(field, value, 0, max);
+        }
+    }
+
+    pub fn email(&mut self, field: &str, value: &str) {
+        if !is_valid_email(value) {
+            self.add(field, "Invalid email format");
+        } else {
+            self.length(field, value, 1, MAX_EMAIL_LENGTH);
+        }
+    }
+
+    pub fn phone(&mut self, field: &str, value: &str) {
+        self.check(
+            is_e164(value),
+            field,
+            format!("{} must be in E.164 format, e.g. +61400111222", label(field)),
+        );
+    }
+
+    pub fn range<T: PartialOrd + Display>(&mut self, field: &str, value: &T, min: &T, max: &T) {
+        if value < min || value > max {
+            self.add(field, format!("{} must be between {} and {}", label(field), min, max));
+        }
+    }
+
+    /// A non-negative amount fitting `NUMERIC(precision, 2)`
+    pub fn amount(&mut self, field: &str, value: &BigDecimal, precision: u32) {
+        let limit = BigDecimal::from(10i64.pow(precision - 2));
+        if value < &BigDecimal::from(0) {
+            self.add(field, format!("{} must not be negative", label(field)));
+        } else if value >= &limit {
+            self.add(field, format!("{} must be less than {}", label(field), limit));
+        } else if value.with_scale(2) != *value {
+            self.add(field, format!("{} must have at most 2 decimal places", label(field)));
+        }
+    }
+
+    pub fn one_of(&mut self, field: &str, value: &str, allowed: &[&str]) {
+        self.check(
+            allowed.contains(&value),
+            field,
+            format!("{} must be one of: {}", label(field), allowed.join(", ")),
+        );
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn finish(self) -> Result<(), ServiceError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ServiceError::InvalidFields(self.errors))
+        }
+    }
+}
+
+/// Address syntax per RFC 5322, restricted to addresses with a domain part
+pub fn is_valid_email(value: &str) -> bool {
+    email_address::EmailAddress::is_valid(value)
+}
+
+/// Specification attribute names are snake_case identifiers such as `payload_t`
+pub fn is_spec_name(value: &str) -> bool {
+    let mut chars = value.chars();
+    value.len() <= MAX_SPEC_NAME_LENGTH
+        && chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// `+` followed by 2 to 15 digits, the first of which is not zero
+pub fn is_e164(value: &str) -> bool {
+    let Some(digits) = value.strip_prefix('+') else {
+        return false;
+    };
+    (2..=15).contains(&digits.len())
+        && digits.chars().all(|c| c.is_ascii_digit())
+        && !digits.starts_with('0')
+}
+
+/// JSON body extractor that rejects payloads failing `Validate`
+pub struct Validated<T>(pub T);
+
+impl<T> Validated<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Validated<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for Validated<T> {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let json = web::Json::<T>::from_request(req, payload);
+        Box::pin(async move {
+            let value = json.await?.into_inner();
+            value.validate()?;
+            Ok(Validated(value))
+        })
+    }
+}
+
+fn user_profile(v: &mut Validator, company_name: Option<&str>, contact_number: Option<&str>) {
+    v.optional_length("company_name", company_name, MAX_NAME_LENGTH);
+    if let Some(number) = contact_number {
+        v.phone("contact_number", number);
+    }
+}
+
+impl Validate for NewUser {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.length("username", &self.username, 1, MAX_USERNAME_LENGTH);
+        v.email("email", &self.email);
+        user_profile(v, self.company_name.as_deref(), self.contact_number.as_deref());
+    }
+}
+
+impl Validate for RegisterUser {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.length("username", &self.username, 1, MAX_USERNAME_LENGTH);
+        v.email("email", &self.email);
+        if let Some(reason) = password::strength_problem(&self.password, &self.username) {
+            v.add("password", reason);
+        }
+        user_profile(v, self.company_name.as_deref(), self.contact_number.as_deref());
+    }
+}
+
+impl Validate for UpdateUser {
+    /// Only present fields are checked. A new password is compared against the
+    /// new username when one is given; the handler checks it against the
+    /// stored username otherwise.
+    fn validate_fields(&self, v: &mut Validator) {
+        if let Some(username) = &self.username {
+            v.length("username", username, 1, MAX_USERNAME_LENGTH);
+        }
+        if let Some(email) = &self.email {
+            v.email("email", email);
+        }
+        if let Some(plaintext) = &self.password {
+            let username = self.username.as_deref().unwrap_or_default();
+            if let Some(reason) = password::strength_problem(plaintext, username) {
+                v.add("password", reason);
+            }
+        }
+        user_profile(
+            v,
+            self.company_name.as_ref().and_then(Option::as_deref),
+            self.contact_number.as_ref().and_then(Option::as_deref),
+        );
+    }
+}
+
+impl Validate for NewEquipmentCategory {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.name("name", &self.name);
+        v.optional_length("description", self.description.as_deref(), MAX_TEXT_LENGTH);
+    }
+}
+
+impl Validate for NewEquipment {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.name("name", &self.name);
+        v.name("manufacturer", &self.manufacturer);
+        v.name("model_number", &self.model_number);
+        v.optional_length("description", self.description.as_deref(), MAX_TEXT_LENGTH);
+        v.amount("price", &self.price, 15);
+        v.range("stock_level", &self.stock_level, &0, &i32::MAX);
+        if let Some(year) = self.year_manufactured {
+            let next_year = chrono::Utc::now().year() + 1;
+            v.range("year_manufactured", &year, &EARLIEST_MANUFACTURE_YEAR, &next_year);
+        }
+        if let Some(weight) = &self.weight_kg {
+            v.amount("weight_kg", weight, 10);
+            v.check(weight > &BigDecimal::from(0), "weight_kg", "Weight kg must be greater than 0");
+        }
+        let dimensions = [("length_cm", &self.length_cm), ("width_cm", &self.width_cm), ("height_cm", &self.height_cm)];
+        for (field, value) in dimensions {
+            match value {
+                Some(value) => {
+                    v.amount(field, value, 10);
+                    v.check(value > &BigDecimal::from(0), field, format!("{} must be greater than 0", label(field)));
+                }
+                None if dimensions.iter().any(|(_, value)| value.is_some()) => {
+                    v.add(field, "Length, width and height must be given together");
+                }
+                None => {}
+            }
+        }
+        v.optional_length("power_requirements", self.power_requirements.as_deref(), MAX_NAME_LENGTH);
+        v.optional_length("certification_info", self.certification_info.as_deref(), MAX_TEXT_LENGTH);
+        v.optional_length("warranty_info", self.warranty_info.as_deref(), MAX_TEXT_LENGTH);
+        if let Some(specs) = &self.specifications {
+            v.check(specs.is_object(), "specifications", "Specifications must be a JSON object");
+        }
+    }
+}
+
+impl Validate for SpecSchemaRequest {
+    fn validate_fields(&self, v: &mut Validator) {
+        let mut names = std::collections::HashSet::new();
+        for (i, attribute) in self.attributes.iter().enumerate() {
+            let field = |name: &str| format!("attributes[{}].{}", i, name);
+
+            v.check(
+                is_spec_name(&attribute.name),
+                &field("name"),
+                "Name must be a snake_case identifier of at most 64 characters",
+            );
+            v.check(names.insert(attribute.name.as_str()), &field("name"), "Name is used more than once");
+            v.optional_length(&field("unit"), attribute.unit.as_deref(), MAX_SPEC_UNIT_LENGTH);
+
+            if let Some(values) = &attribute.allowed_values {
+                v.check(
+                    attribute.data_type != SpecDataType::Boolean,
+                    &field("allowed_values"),
+                    "Allowed values cannot be restricted for boolean attributes",
+                );
+                v.check(!values.is_empty(), &field("allowed_values"), "Allowed values must not be empty");
+                v.check(
+                    values.iter().all(|value| attribute.data_type.accepts(value)),
+                    &field("allowed_values"),
+                    format!("Allowed values must all be of type {}", attribute.data_type),
+                );
+            }
+        }
+    }
+}
+
+/// Checks `specifications` against a category's schema. Categories without
+/// a schema accept any object; otherwise every key must be a known attribute
+/// of the right type, and required attributes must be present. A `null`
+/// value counts as absent.
+pub fn check_specifications(v: &mut Validator, schema: &[SpecAttribute], specifications: Option<&serde_json::Value>) {
+    if schema.is_empty() {
+        return;
+    }
+    let none = serde_json::Map::new();
+    let Some(values) = specifications.map_or(Some(&none), |s| s.as_object()) else {
+        // Reported by the listing's own validation
+        return;
+    };
+
+    for attribute in schema {
+        let field = format!("specifications.{}", attribute.name);
+        match values.get(&attribute.name).filter(|value| !value.is_null()) {
+            None => v.check(!attribute.required, &field, format!("Specification '{}' is required", attribute.name)),
+            Some(value) if !attribute.data_type.accepts(value) => v.add(
+                &field,
+                format!("Specification '{}' must be of type {}", attribute.name, attribute.data_type),
+            ),
+            Some(value) => v.check(
+                attribute.allows(value),
+                &field,
+                format!("Specification '{}' is not one of the allowed values", attribute.name),
+            ),
+        }
+    }
+
+    for key in values.keys() {
+        v.check(
+            schema.iter().any(|attribute| &attribute.name == key),
+            &format!("specifications.{}", key),
+            format!("Unknown specification '{}'", key),
+        );
+    }
+}
+
+/// Validates a listing together with its specifications
+pub fn validate_listing(item: &NewEquipment, schema: &[SpecAttribute]) -> Result<(), ServiceError> {
+    let mut v = Validator::new();
+    item.validate_fields(&mut v);
+    check_specifications(&mut v, schema, item.specifications.as_ref());
+    v.finish()
+}
+
+impl Validate for EquipmentSearch {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.optional_length("q", self.text.as_deref(), MAX_NAME_LENGTH);
+        if let (Some(min), Some(max)) = (&self.min_price, &self.max_price) {
+            v.check(min <= max, "min_price", "Min price must not exceed max price");
+        }
+        if let (Some(min), Some(max)) = (self.min_year, self.max_year) {
+            v.check(min <= max, "min_year", "Min year must not exceed max year");
+        }
+    }
+}
+
+impl Validate for NewEquipmentImage {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.length("image_url", &self.image_url, 1, MAX_URL_LENGTH);
+    }
+}
+
+impl Validate for DocumentUpload {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.name("title", &self.title);
+        if let Some(version) = &self.version {
+            v.length("version", version, 1, MAX_VERSION_LENGTH);
+        }
+    }
+}
+
+impl Validate for NewOrder {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.amount("total_amount", &self.total_amount, 15);
+        v.check(!self.shipping_address.trim().is_empty(), "shipping_address", "Shipping address must not be blank");
+        v.optional_length("shipping_address", Some(&self.shipping_address), MAX_TEXT_LENGTH);
+        v.name("shipping_method", &self.shipping_method);
+        v.optional_length("tracking_number", self.tracking_number.as_deref(), MAX_NAME_LENGTH);
+        v.optional_length("special_instructions", self.special_instructions.as_deref(), MAX_TEXT_LENGTH);
+    }
+}
+
+impl Validate for PlaceOrderRequest {
+    fn validate_fields(&self, v: &mut Validator) {
+        if self.items.is_empty() {
+            v.add("items", "An order needs at least one item");
+        } else if self.items.len() > MAX_ORDER_LINES {
+            v.add("items", format!("An order can have at most {} items", MAX_ORDER_LINES));
+        }
+        let mut listings = std::collections::HashSet::new();
+        for (i, line) in self.items.iter().enumerate() {
+            let field = |name: &str| format!("items[{}].{}", i, name);
+
+            v.check(
+                listings.insert(line.equipment_id),
+                &field("equipment_id"),
+                "Equipment appears more than once; combine the quantities",
+            );
+            v.check(line.quantity >= 1, &field("quantity"), "Quantity must be at least 1");
+            v.optional_length(&field("special_requirements"), line.special_requirements.as_deref(), MAX_TEXT_LENGTH);
+        }
+
+        shipping_fields(v, &self.shipping_address, &self.shipping_method, self.special_instructions.as_deref());
+    }
+}
+
+/// Where and how an order is shipped, shared by orders and cart checkout
+fn shipping_fields(v: &mut Validator, address: &str, method: &str, instructions: Option<&str>) {
+    v.check(!address.trim().is_empty(), "shipping_address", "Shipping address must not be blank");
+    v.optional_length("shipping_address", Some(address), MAX_TEXT_LENGTH);
+    v.name("shipping_method", method);
+    v.optional_length("special_instructions", instructions, MAX_TEXT_LENGTH);
+}
+
+impl Validate for AddCartItemRequest {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.check(self.quantity >= 1, "quantity", "Quantity must be at least 1");
+        v.optional_length("special_requirements", self.special_requirements.as_deref(), MAX_TEXT_LENGTH);
+    }
+}
+
+impl Validate for UpdateCartItem {
+    fn validate_fields(&self, v: &mut Validator) {
+        if let Some(quantity) = self.quantity {
+            v.check(quantity >= 1, "quantity", "Quantity must be at least 1");
+        }
+        if let Some(requirements) = &self.special_requirements {
+            v.optional_length("special_requirements", requirements.as_deref(), MAX_TEXT_LENGTH);
+        }
+    }
+}
+
+impl Validate for CheckoutRequest {
+    fn validate_fields(&self, v: &mut Validator) {
+        shipping_fields(v, &self.shipping_address, &self.shipping_method, self.special_instructions.as_deref());
+        if let Some(total) = &self.expected_total {
+            v.amount("expected_total", total, 15);
+        }
+    }
+}
+
+impl Validate for CancelOrderRequest {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.optional_length("note", self.note.as_deref(), MAX_TEXT_LENGTH);
+    }
+}
+
+impl Validate for ShipOrderRequest {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.name("tracking_number", &self.tracking_number);
+        v.check(
+            self.estimated_delivery_date >= chrono::Utc::now().date_naive(),
+            "estimated_delivery_date",
+            "Estimated delivery date must not be in the past",
+        );
+    }
+}
+
+impl Validate for NewOrderItem {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.range("quantity", &self.quantity, &1, &i32::MAX);
+        v.amount("price_at_time", &self.price_at_time, 15);
+        v.optional_length("special_requirements", self.special_requirements.as_deref(), MAX_TEXT_LENGTH);
+    }
+}
+
+impl Validate for NewReview {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.range("rating", &self.rating, &MIN_RATING, &MAX_RATING);
+        v.optional_length("review_text", self.review_text.as_deref(), MAX_TEXT_LENGTH);
+        v.optional_length("usage_duration", self.usage_duration.as_deref(), MAX_NAME_LENGTH);
+        v.optional_length("pros", self.pros.as_deref(), MAX_TEXT_LENGTH);
+        v.optional_length("cons", self.cons.as_deref(), MAX_TEXT_LENGTH);
+    }
+}
+
+impl Validate for NewMaintenanceRecord {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.name("service_type", &self.service_type);
+        v.optional_length("description", self.description.as_deref(), MAX_TEXT_LENGTH);
+        v.optional_length("performed_by", self.performed_by.as_deref(), MAX_NAME_LENGTH);
+        if let Some(next) = self.next_service_date {
+            v.check(
+                next >= self.service_date,
+                "next_service_date",
+                "Next service date must not be before the service date",
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn fields(result: Result<(), ServiceError>) -> Vec<String> {
+        result
+            .unwrap_err()
+            .details()
+            .iter()
+            .map(|f| f.field.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_email_and_phone_formats() {
+        assert!(is_valid_email("ops@pit-7.example.com"));
+        assert!(is_valid_email("first.last+orders@example.co"));
+        assert!(!is_valid_email("not-an-email"));
+        assert!(!is_valid_email("two@@example.com"));
+        assert!(!is_valid_email("@example.com"));
+
+        assert!(is_e164("+61400111222"));
+        assert!(!is_e164("0400111222"));
+        assert!(!is_e164("+0400111222"));
+        assert!(!is_e164("+61 400 111 222"));
+        assert!(!is_e164("+1234567890123456"));
+    }
+
+    #[test]
+    fn test_register_user_collects_all_errors() {
+        let mut user = RegisterUser::new(String::new(), "nope".into(), "short".into());
+        user.contact_number = Some("12345".into());
+        assert_eq!(fields(user.validate()), ["username", "email", "password", "contact_number"]);
+
+        let user = RegisterUser::new("miner".into(), "miner@example.com".into(), "haul truck 797".into());
+        assert!(user.validate().is_ok());
+    }
+
+    #[test]
+    fn test_amount_and_range_checks() {
+        let mut v = Validator::new();
+        v.amount("price", &BigDecimal::from_str("125000.50").unwrap(), 15);
+        v.range("rating", &5, &MIN_RATING, &MAX_RATING);
+        assert!(v.is_valid());
+
+        v.amount("price", &BigDecimal::from_str("-1").unwrap(), 15);
+        v.amount("price", &BigDecimal::from_str("10.001").unwrap(), 15);
+        v.amount("weight_kg", &BigDecimal::from_str("100000000").unwrap(), 10);
+        v.range("rating", &6, &MIN_RATING, &MAX_RATING);
+        v.range("stock_level", &-1, &0, &i32::MAX);
+        let error = v.finish().unwrap_err();
+        assert_eq!(error.details().len(), 5);
+        assert_eq!(error.details()[3].reason, "Rating must be between 1 and 5");
+    }
+
+    fn attribute(name: &str, data_type: SpecDataType, required: bool, allowed: Option<serde_json::Value>) -> SpecAttribute {
+        SpecAttribute {
+            id: 0,
+            category_id: 0,
+            name: name.to_string(),
+            data_type,
+            unit: None,
+            required,
+            allowed_values: allowed,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    #[test]
+    fn test_specifications_checked_against_schema() {
+        let schema = vec![
+            attribute("payload_t", SpecDataType::Number, true, None),
+            attribute("axles", SpecDataType::Integer, false, None),
+            attribute("drive", SpecDataType::Text, false, Some(serde_json::json!(["diesel", "electric"]))),
+        ];
+        let check = |specs: serde_json::Value| {
+            let mut v = Validator::new();
+            check_specifications(&mut v, &schema, Some(&specs));
+            v.finish().map_or_else(|e| e.details().iter().map(|f| f.field.clone()).collect(), |_| Vec::new())
+        };
+
+        assert!(check(serde_json::json!({"payload_t": 363, "axles": 2, "drive": "electric"})).is_empty());
+        assert!(check(serde_json::json!({"payload_t": 363.5, "axles": null})).is_empty());
+        assert_eq!(
+            check(serde_json::json!({"Payload (tons)": "400", "axles": 2.5, "drive": "steam"})),
+            vec!["specifications.payload_t", "specifications.axles", "specifications.drive", "specifications.Payload (tons)"]
+        );
+
+        // Categories without a schema take anything
+        let mut v = Validator::new();
+        check_specifications(&mut v, &[], Some(&serde_json::json!({"anything": [1, 2]})));
+        assert!(v.is_valid());
+    }
+}
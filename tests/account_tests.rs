@@ -0,0 +1,267 @@
+use std::sync::Arc;
+use actix_web::{http::StatusCode, web, App};
+use chrono::{Duration, Utc};
+use rust_market::{
+    auth::{account, AccountMailer},
+    configure,
+    db::{self, establish_connection_pool},
+    errors::{codes, ServiceError},
+    mailer::OutboxMailer,
+    models::{UserChangeset, UserRole},
+    test_helpers,
+};
+use uuid::Uuid;
+
+fn outbox() -> OutboxMailer {
+    let dir = std::env::temp_dir().join(format!("rust_market_outbox_{}", Uuid::new_v4().simple()));
+    OutboxMailer::new(dir).expect("Failed to create outbox")
+}
+
+/// Pulls the token out of the link in the most recent email to `to`
+fn token_from_latest(outbox: &OutboxMailer, to: &str) -> String {
+    let messages = outbox.messages_to(to);
+    let email = messages.last().expect("Expected an email");
+    let start = email.body.find("token=").expect("Email should contain a token link") + "token=".len();
+    email.body[start..].split_whitespace().next().unwrap().to_string()
+}
+
+#[actix_web::test]
+async fn test_registration_sends_verification_email() {
+    use actix_web::test;
+
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let outbox = outbox();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool))
+            .app_data(web::Data::new(AccountMailer::new(Arc::new(outbox.clone()), "https://market.test")))
+            .configure(configure)
+    ).await;
+
+    let suffix = &Uuid::new_v4().simple().to_string()[..12];
+    let email = format!("verify{}@example.com", suffix);
+    let req = test::TestRequest::post()
+        .uri("/users")
+        .set_json(serde_json::json!({
+            "username": format!("verify{}", suffix),
+            "email": email,
+            "password": "haul truck 797",
+            "business_type": "buyer",
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["email_verified"], false);
+
+    let messages = outbox.messages_to(&email);
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].body.contains("https://market.test/verify-email?token="));
+    let token = token_from_latest(&outbox, &email);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/verify-email/confirm")
+        .set_json(serde_json::json!({ "token": token }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["email_verified"], true);
+
+    // Tokens are single-use
+    let req = test::TestRequest::post()
+        .uri("/auth/verify-email/confirm")
+        .set_json(serde_json::json!({ "token": token }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    std::fs::remove_dir_all(outbox.dir()).ok();
+}
+
+#[actix_web::test]
+async fn test_request_verification_replaces_previous_token() {
+    use actix_web::test;
+
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let outbox = outbox();
+    let user = test_helpers::create_test_user(&pool, Some(UserRole::Buyer));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::new(test_helpers::token_config()))
+            .app_data(web::Data::new(AccountMailer::new(Arc::new(outbox.clone()), "https://market.test")))
+            .configure(configure)
+    ).await;
+
+    for _ in 0..2 {
+        let req = test::TestRequest::post()
+            .uri("/auth/verify-email/request")
+            .insert_header(test_helpers::bearer_header(&user))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    }
+
+    let messages = outbox.messages_to(&user.email);
+    assert_eq!(messages.len(), 2);
+    let first = {
+        let start = messages[0].body.find("token=").unwrap() + "token=".len();
+        messages[0].body[start..].split_whitespace().next().unwrap().to_string()
+    };
+
+    // Only the most recent link is valid
+    let stale = account::confirm_email_verification(&pool, &first);
+    assert_eq!(stale.unwrap_err().code(), codes::ACCOUNT_TOKEN_INVALID);
+    let verified = account::confirm_email_verification(&pool, &token_from_latest(&outbox, &user.email))
+        .expect("Latest token should verify");
+    assert!(verified.is_email_verified());
+
+    // Nothing left to verify
+    let req = test::TestRequest::post()
+        .uri("/auth/verify-email/request")
+        .insert_header(test_helpers::bearer_header(&user))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    std::fs::remove_dir_all(outbox.dir()).ok();
+}
+
+#[actix_web::test]
+async fn test_password_reset_flow() {
+    use actix_web::test;
+
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let outbox = outbox();
+    let user = test_helpers::create_test_user(&pool, Some(UserRole::Supplier));
+    let old_session = test_helpers::bearer_header(&user);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool))
+            .app_data(web::Data::new(test_helpers::token_config()))
+            .app_data(web::Data::new(AccountMailer::new(Arc::new(outbox.clone()), "https://market.test")))
+            .configure(configure)
+    ).await;
+
+    // Unknown addresses get the same answer and no email
+    let req = test::TestRequest::post()
+        .uri("/auth/password-reset/request")
+        .set_json(serde_json::json!({ "email": "nobody-here@example.com" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    assert!(outbox.messages_to("nobody-here@example.com").is_empty());
+
+    let req = test::TestRequest::post()
+        .uri("/auth/password-reset/request")
+        .set_json(serde_json::json!({ "email": user.email }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let token = token_from_latest(&outbox, &user.email);
+
+    // The new password must still meet the strength rules
+    let req = test::TestRequest::post()
+        .uri("/auth/password-reset/confirm")
+        .set_json(serde_json::json!({ "token": token, "new_password": "short" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/password-reset/confirm")
+        .set_json(serde_json::json!({ "token": token, "new_password": "dump body 240" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    // Sessions from before the reset no longer work
+    let req = test::TestRequest::get().uri("/users/me").insert_header(old_session).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::AUTH_TOKEN_INVALID);
+
+    let mut new_session = None;
+    for (secret, expected) in [("dump body 240", StatusCode::OK), (test_helpers::TEST_PASSWORD, StatusCode::UNAUTHORIZED)] {
+        let req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(serde_json::json!({ "email": user.email, "password": secret }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), expected);
+        if expected == StatusCode::OK {
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            new_session = body["access_token"].as_str().map(str::to_string);
+        }
+    }
+    let req = test::TestRequest::get()
+        .uri("/users/me")
+        .insert_header(("Authorization", format!("Bearer {}", new_session.unwrap())))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/password-reset/confirm")
+        .set_json(serde_json::json!({ "token": token, "new_password": "dump body 241" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    std::fs::remove_dir_all(outbox.dir()).ok();
+}
+
+#[test]
+fn test_expired_tokens_are_rejected() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let user = test_helpers::create_test_user(&pool, Some(UserRole::Buyer));
+
+    let expired = Utc::now().naive_utc() - Duration::minutes(1);
+    let (token, digest) = account::generate_token();
+    db::account_tokens::create_email_verification_token(&pool, user.id, &digest, expired).unwrap();
+    let error = account::confirm_email_verification(&pool, &token).unwrap_err();
+    assert!(matches!(error.root(), ServiceError::BadRequest(_)));
+    assert_eq!(error.code(), codes::ACCOUNT_TOKEN_INVALID);
+
+    let (token, digest) = account::generate_token();
+    db::account_tokens::create_password_reset_token(&pool, user.id, &digest, expired).unwrap();
+    let error = account::confirm_password_reset(&pool, &token, "dump body 240").unwrap_err();
+    assert!(matches!(error.root(), ServiceError::BadRequest(_)));
+    assert_eq!(error.code(), codes::ACCOUNT_TOKEN_INVALID);
+}
+
+#[test]
+fn test_changing_email_clears_verification() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let user = test_helpers::create_test_user(&pool, Some(UserRole::Buyer));
+
+    let (token, digest) = account::generate_token();
+    let expires = Utc::now().naive_utc() + Duration::hours(1);
+    db::account_tokens::create_email_verification_token(&pool, user.id, &digest, expires).unwrap();
+    let verified = account::confirm_email_verification(&pool, &token).unwrap();
+    assert!(verified.is_email_verified());
+
+    // Re-submitting the same address keeps the verification
+    let same = UserChangeset { email: Some(user.email.clone()), ..Default::default() };
+    assert!(db::users::update_user(&pool, user.id, &same).unwrap().is_email_verified());
+
+    let changed = UserChangeset {
+        email: Some(format!("moved{}@example.com", &Uuid::new_v4().simple().to_string()[..12])),
+        ..Default::default()
+    };
+    assert!(!db::users::update_user(&pool, user.id, &changed).unwrap().is_email_verified());
+}
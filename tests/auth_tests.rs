@@ -0,0 +1,261 @@
+use actix_web::{http::StatusCode, web, App, HttpResponse};
+use argon2::password_hash::{PasswordHasher, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rust_market::{
+    auth::{self, password, AuthenticatedUser, Authorized, CanSell},
+    configure,
+    db::{establish_connection_pool, users::create_user},
+    errors::{codes, ServiceError},
+    models::{NewUser, UserRole},
+    test_helpers,
+};
+use uuid::Uuid;
+
+fn unique_suffix() -> String {
+    Uuid::new_v4().to_string().split('-').next().unwrap().to_string()
+}
+
+#[test]
+fn test_authenticate_valid_and_invalid_credentials() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+
+    let suffix = unique_suffix();
+    let email = format!("auth{}@example.com", suffix);
+    let hash = password::hash_password("drill rig 3000").expect("Hashing should succeed");
+    let user = create_user(&pool, NewUser::new(format!("auth{}", suffix), email.clone(), hash))
+        .expect("Failed to create user");
+
+    let authenticated = auth::authenticate(&pool, &email, "drill rig 3000")
+        .expect("Valid credentials should authenticate");
+    assert_eq!(authenticated.id, user.id);
+
+    let wrong_password = auth::authenticate(&pool, &email, "drill rig 3001").unwrap_err();
+    assert!(matches!(wrong_password.root(), ServiceError::Unauthorized(_)));
+    assert_eq!(wrong_password.code(), codes::AUTH_INVALID_CREDENTIALS);
+
+    let unknown_email = auth::authenticate(&pool, "nobody@example.com", "drill rig 3000").unwrap_err();
+    assert!(matches!(unknown_email.root(), ServiceError::Unauthorized(_)));
+    assert_eq!(unknown_email.code(), codes::AUTH_INVALID_CREDENTIALS);
+}
+
+#[test]
+fn test_authenticate_rehashes_outdated_hash() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+
+    // Simulate a hash created before the cost parameters were raised
+    let legacy = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(8 * 1024, 1, 1, None).unwrap(),
+    );
+    let salt = SaltString::generate(&mut rand::rngs::OsRng);
+    let legacy_hash = legacy.hash_password(b"drill rig 3000", &salt).unwrap().to_string();
+    assert!(password::needs_rehash(&legacy_hash));
+
+    let suffix = unique_suffix();
+    let email = format!("legacy{}@example.com", suffix);
+    create_user(&pool, NewUser::new(format!("legacy{}", suffix), email.clone(), legacy_hash.clone()))
+        .expect("Failed to create user");
+
+    let user = auth::authenticate(&pool, &email, "drill rig 3000")
+        .expect("Legacy hash should still authenticate");
+    assert_ne!(user.password_hash, legacy_hash);
+    assert!(!password::needs_rehash(&user.password_hash));
+    assert!(password::verify_password("drill rig 3000", &user.password_hash));
+}
+
+async fn whoami(user: AuthenticatedUser) -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "id": user.id, "username": user.username }))
+}
+
+#[actix_web::test]
+async fn test_login_issues_token_accepted_by_extractor() {
+    use actix_web::test;
+
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+
+    let suffix = unique_suffix();
+    let email = format!("login{}@example.com", suffix);
+    let hash = password::hash_password("drill rig 3000").expect("Hashing should succeed");
+    let user = create_user(&pool, NewUser::new(format!("login{}", suffix), email.clone(), hash))
+        .expect("Failed to create user");
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool))
+            .app_data(web::Data::new(test_helpers::token_config()))
+            .configure(configure)
+            .route("/whoami", web::get().to(whoami))
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(serde_json::json!({ "email": email, "password": "drill rig 3000" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["token_type"], "Bearer");
+    let token = body["access_token"].as_str().expect("Token should be a string");
+
+    let req = test::TestRequest::get()
+        .uri("/whoami")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["id"], user.id);
+
+    // Wrong password is rejected without issuing a token
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(serde_json::json!({ "email": email, "password": "wrong password 1" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn test_extractor_rejects_missing_and_invalid_tokens() {
+    use actix_web::test;
+
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool))
+            .app_data(web::Data::new(test_helpers::token_config()))
+            .route("/whoami", web::get().to(whoami))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/whoami").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::get()
+        .uri("/whoami")
+        .insert_header(("Authorization", "Bearer not.a.token"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // A well-formed token for a user that does not exist
+    let token = test_helpers::token_config().issue(i32::MAX, 0).unwrap();
+    let req = test::TestRequest::get()
+        .uri("/whoami")
+        .insert_header(("Authorization", format!("Bearer {}", token.access_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+async fn list_equipment_stub(user: Authorized<CanSell>) -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "supplier": user.id }))
+}
+
+#[actix_web::test]
+async fn test_role_guard_enforces_policy() {
+    use actix_web::test;
+
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let tokens = test_helpers::token_config();
+
+    let mut tokens_by_role = Vec::new();
+    for role in [UserRole::Supplier, UserRole::Buyer] {
+        let suffix = unique_suffix();
+        let mut new_user = NewUser::new(
+            format!("{}{}", role, suffix),
+            format!("{}{}@example.com", role, suffix),
+            "unused-hash".to_string(),
+        );
+        new_user.business_type = Some(role);
+        let user = create_user(&pool, new_user).expect("Failed to create user");
+        tokens_by_role.push((role, tokens.issue(user.id, user.token_version).unwrap().access_token));
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool))
+            .app_data(web::Data::new(tokens))
+            .route("/listings", web::post().to(list_equipment_stub))
+    ).await;
+
+    for (role, token) in tokens_by_role {
+        let req = test::TestRequest::post()
+            .uri("/listings")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let expected = if role == UserRole::Supplier { StatusCode::OK } else { StatusCode::FORBIDDEN };
+        assert_eq!(resp.status(), expected, "Unexpected status for {}", role);
+    }
+
+    let req = test::TestRequest::post().uri("/listings").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn test_registration_rejects_admin_and_unknown_roles() {
+    use actix_web::test;
+
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool))
+            .configure(configure)
+    ).await;
+
+    let suffix = unique_suffix();
+    for (role, expected) in [("admin", StatusCode::FORBIDDEN), ("overlord", StatusCode::BAD_REQUEST)] {
+        let req = test::TestRequest::post()
+            .uri("/users")
+            .set_json(serde_json::json!({
+                "username": format!("role{}", suffix),
+                "email": format!("role{}@example.com", suffix),
+                "password": "haul truck 797",
+                "business_type": role,
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), expected, "Unexpected status for role {}", role);
+    }
+}
+
+#[test]
+fn test_database_rejects_unknown_business_type() {
+    use diesel::prelude::*;
+
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let conn = &mut pool.get().expect("Failed to get db connection");
+
+    let suffix = unique_suffix();
+    let result = diesel::sql_query(
+        "INSERT INTO users (username, email, password_hash, business_type) VALUES ($1, $2, 'x', 'overlord')"
+    )
+        .bind::<diesel::sql_types::Text, _>(format!("check{}", suffix))
+        .bind::<diesel::sql_types::Text, _>(format!("check{}@example.com", suffix))
+        .execute(conn);
+
+    assert!(matches!(
+        result,
+        Err(diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::CheckViolation, _))
+    ));
+}
@@ -0,0 +1,150 @@
+use actix_web::{test, http::StatusCode};
+use serde_json::json;
+use rust_market::{
+    db::{self, establish_connection_pool},
+    errors::codes,
+    models::{OrderTransition, ShipOrderRequest, UserRole},
+    test_helpers::{
+        self, bearer_header, create_test_category, create_test_equipment, create_test_user, create_verified_buyer,
+        place_order, stock_level,
+    },
+};
+
+#[actix_web::test]
+async fn test_cancellations_restock_and_record_refunds() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let loader_supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let drill_supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let buyer = create_verified_buyer(&pool);
+    let admin = create_test_user(&pool, Some(UserRole::Admin));
+    let category = create_test_category(&pool);
+    let loader = create_test_equipment(&pool, category.id, Some(loader_supplier.id));
+    let drill = create_test_equipment(&pool, category.id, Some(drill_supplier.id));
+    let placed = place_order(&pool, buyer.id, &[(loader.id, 2), (drill.id, 1)]);
+    let order_id = placed.order.id;
+    let drill_item = placed.items.iter().find(|item| item.equipment_id == drill.id).unwrap().id;
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let cancel = |user, uri: String, body: serde_json::Value| test::TestRequest::post()
+        .uri(&uri)
+        .insert_header(bearer_header(user))
+        .set_json(body)
+        .to_request();
+    let item_uri = format!("/orders/{}/items/{}/cancel", order_id, drill_item);
+    let order_uri = format!("/orders/{}/cancel", order_id);
+
+    // A supplier cancels the line of their own listing
+    let req = cancel(&drill_supplier, item_uri.clone(), json!({ "reason": "out_of_stock" }));
+    let outcome: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(outcome["cancellation"]["order_item_id"], drill_item);
+    assert_eq!(outcome["cancellation"]["refund"]["amount"], "125000.00");
+    assert_eq!(outcome["order"]["status"], "pending");
+    let cancelled: Vec<_> = outcome["order"]["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| (item["equipment_id"].as_i64().unwrap(), !item["cancelled_at"].is_null()))
+        .collect();
+    assert_eq!(cancelled, vec![(loader.id as i64, false), (drill.id as i64, true)]);
+    assert_eq!((stock_level(&pool, loader.id), stock_level(&pool, drill.id)), (1, 3));
+
+    // but not the other supplier's lines
+    let resp = test::call_service(&app, cancel(&drill_supplier, order_uri.clone(), json!({ "reason": "other" }))).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = test::call_service(&app, cancel(&buyer, item_uri, json!({ "reason": "buyer_request" }))).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::ORDER_ITEM_CANCELLED);
+    assert_eq!(stock_level(&pool, drill.id), 3);
+
+    let req = cancel(&buyer, order_uri, json!({ "reason": "buyer_request", "note": "Project postponed" }));
+    let outcome: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(outcome["cancellation"]["order_item_id"].is_null());
+    assert_eq!(outcome["cancellation"]["refund"]["amount"], "250000.00");
+    assert_eq!(outcome["order"]["status"], "cancelled");
+    assert_eq!((stock_level(&pool, loader.id), stock_level(&pool, drill.id)), (3, 3));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/orders/{}/cancellations", order_id))
+        .insert_header(bearer_header(&buyer))
+        .to_request();
+    let records: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let records: Vec<_> = records
+        .iter()
+        .map(|record| (record["reason"].clone(), record["cancelled_by"].as_i64().unwrap(), record["refund"]["amount"].clone()))
+        .collect();
+    assert_eq!(records, vec![
+        (json!("out_of_stock"), drill_supplier.id as i64, json!("125000.00")),
+        (json!("buyer_request"), buyer.id as i64, json!("250000.00")),
+    ]);
+
+    let history = db::orders::order_history(&pool, order_id).unwrap();
+    assert_eq!(history.last().unwrap().note.as_deref(), Some("buyer_request: Project postponed"));
+
+    let req = test::TestRequest::get()
+        .uri("/orders/cancellations/summary")
+        .insert_header(bearer_header(&buyer))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    // Other tests may cancel orders today too, so only look for ours
+    let today = chrono::Utc::now().date_naive();
+    let req = test::TestRequest::get()
+        .uri(&format!("/orders/cancellations/summary?from={}&to={}", today, today))
+        .insert_header(bearer_header(&admin))
+        .to_request();
+    let summary: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let reasons: Vec<_> = summary.iter().map(|row| row["reason"].as_str().unwrap()).collect();
+    assert!(reasons.contains(&"buyer_request") && reasons.contains(&"out_of_stock"), "{:?}", reasons);
+    assert!(summary.iter().all(|row| row["cancellations"].as_i64().unwrap() >= 1));
+
+    let yesterday = today.pred_opt().unwrap();
+    let req = test::TestRequest::get()
+        .uri(&format!("/orders/cancellations/summary?to={}", yesterday))
+        .insert_header(bearer_header(&admin))
+        .to_request();
+    let summary: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(summary, Vec::<serde_json::Value>::new());
+}
+
+#[actix_web::test]
+async fn test_shipped_orders_cannot_be_cancelled() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let buyer = create_verified_buyer(&pool);
+    let other_buyer = create_verified_buyer(&pool);
+    let category = create_test_category(&pool);
+    let loader = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let order_id = place_order(&pool, buyer.id, &[(loader.id, 1)]).order.id;
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let cancel = |user, body: serde_json::Value| test::TestRequest::post()
+        .uri(&format!("/orders/{}/cancel", order_id))
+        .insert_header(bearer_header(user))
+        .set_json(body)
+        .to_request();
+
+    let resp = test::call_service(&app, cancel(&other_buyer, json!({ "reason": "buyer_request" }))).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = test::call_service(&app, cancel(&buyer, json!({ "reason": "changed_mind" }))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    db::orders::transition_order(&pool, order_id, supplier.id, &OrderTransition::Confirm).unwrap();
+    let shipment = ShipOrderRequest {
+        tracking_number: "TRK-9".into(),
+        estimated_delivery_date: chrono::Utc::now().date_naive(),
+    };
+    db::orders::transition_order(&pool, order_id, supplier.id, &OrderTransition::Ship(shipment)).unwrap();
+
+    let resp = test::call_service(&app, cancel(&buyer, json!({ "reason": "buyer_request" }))).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::ORDER_INVALID_TRANSITION);
+    assert_eq!(stock_level(&pool, loader.id), 2);
+    assert!(db::cancellations::order_cancellations(&pool, order_id).unwrap().is_empty());
+}
@@ -0,0 +1,196 @@
+use actix_web::{test, http::StatusCode};
+use bigdecimal::BigDecimal;
+use diesel::prelude::*;
+use serde_json::json;
+use rust_market::{
+    db::establish_connection_pool,
+    errors::codes,
+    models::UserRole,
+    schema::equipment,
+    test_helpers::{
+        self, bearer_header, create_test_category, create_test_equipment, create_test_user, create_verified_buyer,
+        set_stock_level, shipping_details, stock_level,
+    },
+};
+
+fn checkout(expected_total: Option<&str>) -> serde_json::Value {
+    let mut body = shipping_details();
+    body["expected_total"] = json!(expected_total);
+    body
+}
+
+#[actix_web::test]
+async fn test_cart_is_revalidated_and_checked_out() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let buyer = create_verified_buyer(&pool);
+    let category = create_test_category(&pool);
+    let loader = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let drill = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let add = |body: serde_json::Value| test::TestRequest::post()
+        .uri("/cart/items")
+        .insert_header(bearer_header(&buyer))
+        .set_json(body)
+        .to_request();
+    let checkout_request = |expected_total| test::TestRequest::post()
+        .uri("/cart/checkout")
+        .insert_header(bearer_header(&buyer))
+        .set_json(checkout(expected_total))
+        .to_request();
+
+    // Adding a listing twice adds to its line
+    for _ in 0..2 {
+        let resp = test::call_service(&app, add(json!({ "equipment_id": loader.id, "quantity": 1 }))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    let req = add(json!({ "equipment_id": drill.id, "quantity": 1, "warranty_selected": true }));
+    let cart: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let lines: Vec<_> = cart["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|line| (line["equipment_id"].as_i64().unwrap(), line["quantity"].as_i64().unwrap()))
+        .collect();
+    assert_eq!(lines, vec![(loader.id as i64, 2), (drill.id as i64, 1)]);
+    assert_eq!(cart["total_amount"], "375000.00");
+    assert_eq!(cart["ready_for_checkout"], true);
+
+    let resp = test::call_service(&app, add(json!({ "equipment_id": drill.id, "quantity": 5 }))).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::INSUFFICIENT_STOCK);
+
+    // The listings change while the cart waits
+    {
+        let conn = &mut pool.get().expect("Failed to get db connection");
+        diesel::update(equipment::table.find(loader.id))
+            .set(equipment::price.eq("130000.00".parse::<BigDecimal>().unwrap()))
+            .execute(conn)
+            .unwrap();
+    }
+    set_stock_level(&pool, drill.id, 0);
+
+    let req = test::TestRequest::get().uri("/cart").insert_header(bearer_header(&buyer)).to_request();
+    let cart: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let (loader_line, drill_line) = (&cart["items"][0], &cart["items"][1]);
+    assert_eq!(loader_line["price_when_added"], "125000.00");
+    assert_eq!(loader_line["unit_price"], "130000.00");
+    assert_eq!(loader_line["line_total"], "260000.00");
+    assert_eq!(loader_line["price_changed"], true);
+    assert_eq!(drill_line["available"], false);
+    assert_eq!(drill_line["warranty_selected"], true);
+    assert_eq!(cart["ready_for_checkout"], false);
+
+    let resp = test::call_service(&app, checkout_request(None)).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::INSUFFICIENT_STOCK);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/cart/items/{}", drill_line["id"]))
+        .insert_header(bearer_header(&buyer))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+    let req = test::TestRequest::patch()
+        .uri(&format!("/cart/items/{}", loader_line["id"]))
+        .insert_header(bearer_header(&buyer))
+        .set_json(json!({ "quantity": 1, "special_requirements": "Tier 4 engine" }))
+        .to_request();
+    let cart: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(cart["total_amount"], "130000.00");
+
+    // A total the buyer has not seen is not charged
+    let resp = test::call_service(&app, checkout_request(Some("125000.00"))).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::CART_CHANGED);
+    assert_eq!(stock_level(&pool, loader.id), 3);
+
+    let resp = test::call_service(&app, checkout_request(Some("130000.00"))).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let order: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(order["user_id"], buyer.id);
+    assert_eq!(order["status"], "pending");
+    assert_eq!(order["items"][0]["price_at_time"], "130000.00");
+    assert_eq!(order["items"][0]["special_requirements"], "Tier 4 engine");
+    assert_eq!(stock_level(&pool, loader.id), 2);
+
+    let req = test::TestRequest::get().uri("/cart").insert_header(bearer_header(&buyer)).to_request();
+    let cart: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(cart["items"], json!([]));
+    assert_eq!(cart["ready_for_checkout"], false);
+
+    let resp = test::call_service(&app, checkout_request(None)).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::CART_EMPTY);
+}
+
+#[actix_web::test]
+async fn test_carts_are_private_to_their_buyer() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let buyer = create_verified_buyer(&pool);
+    let unverified = create_test_user(&pool, Some(UserRole::Buyer));
+    let category = create_test_category(&pool);
+    let loader = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/cart/items")
+        .insert_header(bearer_header(&buyer))
+        .set_json(json!({ "equipment_id": loader.id, "quantity": 1 }))
+        .to_request();
+    let cart: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let item_id = cart["items"][0]["id"].clone();
+
+    let req = test::TestRequest::get().uri("/cart").insert_header(bearer_header(&supplier)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    // Another buyer's cart lines do not exist for this one
+    let req = test::TestRequest::delete()
+        .uri(&format!("/cart/items/{}", item_id))
+        .insert_header(bearer_header(&unverified))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::CART_ITEM_NOT_FOUND);
+
+    let cases = [
+        (json!({ "equipment_id": loader.id, "quantity": 0 }), "quantity"),
+        (json!({ "equipment_id": -1, "quantity": 1 }), "equipment_id"),
+    ];
+    for (body, field) in cases {
+        let req = test::TestRequest::post()
+            .uri("/cart/items")
+            .insert_header(bearer_header(&unverified))
+            .set_json(body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["details"][0]["field"], field);
+    }
+
+    // Unverified buyers can fill a cart but not check it out
+    let req = test::TestRequest::post()
+        .uri("/cart/items")
+        .insert_header(bearer_header(&unverified))
+        .set_json(json!({ "equipment_id": loader.id, "quantity": 1 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::post()
+        .uri("/cart/checkout")
+        .insert_header(bearer_header(&unverified))
+        .set_json(checkout(None))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+    assert_eq!(stock_level(&pool, loader.id), 3);
+}
@@ -0,0 +1,284 @@
+use actix_web::{test, http::StatusCode};
+use diesel::prelude::*;
+use rust_market::{
+    db::{establish_connection_pool, DbPool},
+    errors::codes,
+    models::{Equipment, EquipmentCondition, UserRole},
+    schema::equipment,
+    test_helpers::{self, bearer_header, create_test_category, create_test_equipment, create_test_user},
+};
+
+const HEADER: &str = "category_id,name,description,manufacturer,model_number,condition,price,stock_level,specifications";
+
+fn listings_in(pool: &DbPool, category_id: i32) -> Vec<Equipment> {
+    let conn = &mut pool.get().expect("Failed to get db connection");
+    equipment::table
+        .filter(equipment::category_id.eq(category_id))
+        .order(equipment::id.asc())
+        .load(conn)
+        .expect("Failed to load equipment")
+}
+
+#[actix_web::test]
+async fn test_import_upserts_valid_rows_and_reports_bad_ones() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let other_supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let category = create_test_category(&pool);
+    let own = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let theirs = create_test_equipment(&pool, category.id, Some(other_supplier.id));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let c = category.id;
+    let csv = format!(
+        "{HEADER}\n\
+         {c},Excavator,Repainted,caterpillar,320d,refurbished,99000.00,1,\n\
+         {c},Wheel Loader,,Volvo,L120H,new,210000,2,\"{{\"\"bucket_m3\"\": 4}}\"\n\
+         {c},Dozer,,Komatsu,D65,broken,50000,1,\n\
+         {c},,,Liebherr,R 920,used,80000,1,\n\
+         {c},Grader,,CAT,140M,used,not-a-price,1,\n\
+         {c},Crane,,Tadano,GR-1000,used,300000,1,{{oops\n\
+         999999999,Compactor,,Bomag,BW 213,used,60000,1,\n\
+         {c},Loader again,,VOLVO,l120h,used,1,1,\n"
+    );
+    let req = test::TestRequest::post()
+        .uri("/equipment/import")
+        .insert_header(bearer_header(&supplier))
+        .insert_header(("content-type", "text/csv"))
+        .set_payload(csv)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let report: serde_json::Value = test::read_body_json(resp).await;
+
+    assert_eq!(report["dry_run"], false);
+    assert_eq!(report["rows"], 8);
+    assert_eq!(report["created"], 1);
+    assert_eq!(report["updated"], 1);
+    assert_eq!(report["failed"], 6);
+
+    let errors: Vec<(u64, Option<&str>)> = report["errors"].as_array().unwrap()
+        .iter()
+        .map(|e| (e["line"].as_u64().unwrap(), e["field"].as_str()))
+        .collect();
+    assert_eq!(errors, vec![
+        (4, Some("condition")),
+        (5, Some("name")),
+        (6, Some("price")),
+        (7, Some("specifications")),
+        (8, Some("category_id")),
+        (9, None),
+    ]);
+    let condition_reason = report["errors"][0]["reason"].as_str().unwrap();
+    assert!(condition_reason.contains("Unknown equipment condition 'broken'"), "{}", condition_reason);
+    let duplicate_reason = report["errors"][5]["reason"].as_str().unwrap();
+    assert!(duplicate_reason.contains("line 3"), "{}", duplicate_reason);
+
+    // The supplier's own listing was updated in place, matching
+    // case-insensitively; the other supplier's identical one was not touched
+    let items = listings_in(&pool, category.id);
+    assert_eq!(items.len(), 3);
+    let updated = items.iter().find(|item| item.id == own.id).unwrap();
+    assert_eq!(updated.name, "Excavator");
+    assert_eq!(updated.description.as_deref(), Some("Repainted"));
+    assert_eq!(updated.condition, EquipmentCondition::Refurbished);
+    assert_eq!(updated.stock_level, 1);
+    let untouched = items.iter().find(|item| item.id == theirs.id).unwrap();
+    assert_eq!(untouched.name, "Test Excavator");
+
+    let created = items.iter().find(|item| item.model_number == "L120H").unwrap();
+    assert_eq!(created.supplier_id, Some(supplier.id));
+    assert_eq!(created.description, None);
+    assert_eq!(created.specifications, Some(serde_json::json!({"bucket_m3": 4})));
+}
+
+#[actix_web::test]
+async fn test_import_keeps_columns_the_file_leaves_out() {
+    use bigdecimal::BigDecimal;
+
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let category = create_test_category(&pool);
+    let listing = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let dimension = |value: &str| Some(value.parse::<BigDecimal>().unwrap());
+    {
+        let conn = &mut pool.get().expect("Failed to get db connection");
+        diesel::update(equipment::table.find(listing.id))
+            .set((
+                equipment::specifications.eq(Some(serde_json::json!({"bucket_m3": 1.2}))),
+                equipment::weight_kg.eq(dimension("21500.00")),
+                equipment::length_cm.eq(dimension("950.00")),
+                equipment::width_cm.eq(dimension("280.00")),
+                equipment::height_cm.eq(dimension("300.00")),
+                equipment::power_requirements.eq(Some("diesel")),
+                equipment::certification_info.eq(Some("CE")),
+                equipment::warranty_info.eq(Some("12 months")),
+            ))
+            .execute(conn)
+            .unwrap();
+    }
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let csv = format!(
+        "{}\n{},Excavator,Caterpillar,320D,used,99000.00,2\n",
+        rust_market::catalog::REQUIRED_COLUMNS.join(","),
+        category.id,
+    );
+    let req = test::TestRequest::post()
+        .uri("/equipment/import")
+        .insert_header(bearer_header(&supplier))
+        .insert_header(("content-type", "text/csv"))
+        .set_payload(csv)
+        .to_request();
+    let report: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(report["updated"], 1);
+
+    let items = listings_in(&pool, category.id);
+    let updated = items.iter().find(|item| item.id == listing.id).unwrap();
+    assert_eq!(updated.name, "Excavator");
+    assert_eq!(updated.price, "99000.00".parse::<BigDecimal>().unwrap());
+    assert_eq!(updated.stock_level, 2);
+    assert_eq!(updated.description.as_deref(), Some("Low hours, full service history"));
+    assert_eq!(updated.year_manufactured, Some(2018));
+    assert_eq!(updated.specifications, Some(serde_json::json!({"bucket_m3": 1.2})));
+    assert_eq!(updated.weight_kg, dimension("21500.00"));
+    assert_eq!(
+        (updated.length_cm.clone(), updated.width_cm.clone(), updated.height_cm.clone()),
+        (dimension("950.00"), dimension("280.00"), dimension("300.00")),
+    );
+    assert_eq!(updated.power_requirements.as_deref(), Some("diesel"));
+    assert_eq!(updated.certification_info.as_deref(), Some("CE"));
+    assert_eq!(updated.warranty_info.as_deref(), Some("12 months"));
+}
+
+#[actix_web::test]
+async fn test_import_dry_run_reports_without_saving() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let category = create_test_category(&pool);
+    let own = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let c = category.id;
+    let csv = format!(
+        "{HEADER}\n\
+         {c},Excavator,,Caterpillar,320D,used,1.00,9,\n\
+         {c},Wheel Loader,,Volvo,L120H,new,210000,2,\n"
+    );
+    let req = test::TestRequest::post()
+        .uri("/equipment/import?dry_run=true")
+        .insert_header(bearer_header(&supplier))
+        .set_payload(csv)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let report: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(report["created"], 1);
+    assert_eq!(report["updated"], 1);
+    assert_eq!(report["failed"], 0);
+
+    let items = listings_in(&pool, category.id);
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].id, own.id);
+    assert_eq!(items[0].stock_level, 3);
+}
+
+#[actix_web::test]
+async fn test_import_rejects_malformed_files_and_non_sellers() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let buyer = create_test_user(&pool, Some(UserRole::Buyer));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    for (csv, message) in [
+        (format!("{HEADER},colour\n"), "Unknown column 'colour'"),
+        ("name,manufacturer\n".to_string(), "Missing required columns: category_id, model_number"),
+        (String::new(), "Missing required columns"),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/equipment/import")
+            .insert_header(bearer_header(&supplier))
+            .set_payload(csv)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], codes::CSV_INVALID);
+        assert!(body["message"].as_str().unwrap().contains(message), "{}", body["message"]);
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/equipment/import")
+        .insert_header(bearer_header(&buyer))
+        .set_payload(format!("{HEADER}\n"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_export_streams_filtered_catalog_that_imports_back() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let category = create_test_category(&pool);
+    let empty_category = create_test_category(&pool);
+    let first = create_test_equipment(&pool, category.id, Some(supplier.id));
+    {
+        let conn = &mut pool.get().expect("Failed to get db connection");
+        diesel::update(equipment::table.find(first.id))
+            .set(equipment::specifications.eq(serde_json::json!({"bucket_m3": 1.2, "note": "a, \"quoted\" value"})))
+            .execute(conn)
+            .expect("Failed to set specifications");
+    }
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/export?category_id={}", category.id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "text/csv; charset=utf-8");
+    let body = test::read_body(resp).await;
+
+    let mut reader = csv::Reader::from_reader(body.as_ref());
+    assert_eq!(
+        reader.headers().unwrap().iter().collect::<Vec<_>>(),
+        rust_market::catalog::COLUMNS.to_vec()
+    );
+    let records: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(&records[0][0], first.id.to_string());
+    assert_eq!(&records[0][5], "320D");
+    assert_eq!(&records[0][7], "used");
+    let specs: serde_json::Value = serde_json::from_str(&records[0][10]).unwrap();
+    assert_eq!(specs["note"], "a, \"quoted\" value");
+
+    // The exported file is a valid import, updating the same listing
+    let req = test::TestRequest::post()
+        .uri("/equipment/import")
+        .insert_header(bearer_header(&supplier))
+        .set_payload(body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let report: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(report["updated"], 1);
+    assert_eq!(report["failed"], 0, "{}", report);
+
+    // An empty selection still has a header row
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/export?category_id={}", empty_category.id))
+        .to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    assert_eq!(body.as_ref(), format!("{}\n", rust_market::catalog::COLUMNS.join(",")).as_bytes());
+}
@@ -0,0 +1,226 @@
+use actix_web::{test, http::StatusCode};
+use serde_json::json;
+use rust_market::{
+    db::{categories, establish_connection_pool},
+    errors::codes,
+    models::{CategoryRequest, EquipmentCategory, UserRole},
+    test_helpers::{self, bearer_header, create_test_category, create_test_equipment, create_test_user},
+};
+
+fn subcategory(pool: &rust_market::db::DbPool, parent: &EquipmentCategory) -> EquipmentCategory {
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    let request = CategoryRequest {
+        name: format!("{} / {}", parent.name, &suffix[..8]),
+        description: None,
+        parent_category_id: Some(parent.id),
+    };
+    categories::create_category(pool, request.into_new_category()).expect("Failed to create subcategory")
+}
+
+#[actix_web::test]
+async fn test_only_admins_manage_categories() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let admin = create_test_user(&pool, Some(UserRole::Admin));
+    let parent = create_test_category(&pool);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+    let name = format!("Drills {}", uuid::Uuid::new_v4().simple());
+
+    let payload = json!({"name": name, "parent_category_id": parent.id});
+    let req = test::TestRequest::post()
+        .uri("/categories")
+        .insert_header(bearer_header(&supplier))
+        .set_json(&payload)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri("/categories")
+        .insert_header(bearer_header(&admin))
+        .set_json(&payload)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["parent_category_id"], parent.id);
+
+    // Names are unique
+    let req = test::TestRequest::post()
+        .uri("/categories")
+        .insert_header(bearer_header(&admin))
+        .set_json(&payload)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::CATEGORY_NAME_TAKEN);
+
+    let req = test::TestRequest::post()
+        .uri("/categories")
+        .insert_header(bearer_header(&admin))
+        .set_json(json!({"name": "Orphans", "parent_category_id": i32::MAX}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["details"][0]["field"], "parent_category_id");
+}
+
+#[actix_web::test]
+async fn test_tree_and_breadcrumbs() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let root = create_test_category(&pool);
+    let child = subcategory(&pool, &root);
+    let grandchild = subcategory(&pool, &child);
+    let sibling = subcategory(&pool, &root);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::get().uri("/categories/tree").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let tree: serde_json::Value = test::read_body_json(resp).await;
+    let node = tree.as_array().unwrap()
+        .iter()
+        .find(|n| n["id"] == root.id)
+        .expect("root category should be top level");
+    let children = node["children"].as_array().unwrap();
+    assert_eq!(children.len(), 2);
+    let child_node = children.iter().find(|n| n["id"] == child.id).unwrap();
+    assert_eq!(child_node["children"][0]["id"], grandchild.id);
+    assert!(children.iter().any(|n| n["id"] == sibling.id));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/categories/{}/breadcrumbs", grandchild.id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let path: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    let ids: Vec<i64> = path.iter().map(|c| c["id"].as_i64().unwrap()).collect();
+    assert_eq!(ids, vec![root.id as i64, child.id as i64, grandchild.id as i64]);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/categories/{}/breadcrumbs", i32::MAX))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::CATEGORY_NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_moves_that_would_create_a_cycle_are_rejected() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let admin = create_test_user(&pool, Some(UserRole::Admin));
+    let root = create_test_category(&pool);
+    let child = subcategory(&pool, &root);
+    let grandchild = subcategory(&pool, &child);
+    let other = create_test_category(&pool);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    for parent in [root.id, grandchild.id] {
+        let req = test::TestRequest::patch()
+            .uri(&format!("/categories/{}", root.id))
+            .insert_header(bearer_header(&admin))
+            .set_json(json!({"parent_category_id": parent}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], codes::CATEGORY_CYCLE);
+        assert_eq!(body["details"][0]["field"], "parent_category_id");
+    }
+
+    // Moving a subtree elsewhere, and back to the top level, is fine
+    let req = test::TestRequest::patch()
+        .uri(&format!("/categories/{}", child.id))
+        .insert_header(bearer_header(&admin))
+        .set_json(json!({"parent_category_id": other.id}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/categories/{}", child.id))
+        .insert_header(bearer_header(&admin))
+        .set_json(json!({"parent_category_id": null}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["parent_category_id"].is_null());
+}
+
+#[actix_web::test]
+async fn test_delete_requires_empty_leaf() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let admin = create_test_user(&pool, Some(UserRole::Admin));
+    let root = create_test_category(&pool);
+    let leaf = subcategory(&pool, &root);
+    let stocked = create_test_category(&pool);
+    create_test_equipment(&pool, stocked.id, None);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    for category in [root.id, stocked.id] {
+        let req = test::TestRequest::delete()
+            .uri(&format!("/categories/{}", category))
+            .insert_header(bearer_header(&admin))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], codes::CATEGORY_IN_USE);
+    }
+
+    for category in [leaf.id, root.id] {
+        let req = test::TestRequest::delete()
+            .uri(&format!("/categories/{}", category))
+            .insert_header(bearer_header(&admin))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+    }
+
+    let err = categories::get_category_by_id(&pool, root.id).unwrap_err();
+    assert_eq!(err.code(), codes::CATEGORY_NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_equipment_in_category_includes_descendants() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let root = create_test_category(&pool);
+    let child = subcategory(&pool, &root);
+    let grandchild = subcategory(&pool, &child);
+    let direct = create_test_equipment(&pool, root.id, None);
+    let nested = create_test_equipment(&pool, grandchild.id, None);
+    create_test_equipment(&pool, create_test_category(&pool).id, None);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    for uri in [
+        format!("/categories/{}/equipment", root.id),
+        format!("/equipment?category_id={}&include_subcategories=true", root.id),
+    ] {
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK, "GET {}", uri);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["total"], 2, "GET {}", uri);
+        let ids: Vec<&serde_json::Value> = body["items"].as_array().unwrap().iter().map(|i| &i["id"]).collect();
+        assert!(ids.contains(&&json!(direct.id)));
+        assert!(ids.contains(&&json!(nested.id)));
+    }
+
+    // Without the flag only direct members match
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment?category_id={}", root.id))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["total"], 1);
+}
@@ -0,0 +1,172 @@
+use std::sync::Arc;
+use actix_web::{test, http::StatusCode, web};
+use rust_market::{
+    db::establish_connection_pool,
+    errors::codes,
+    media::{LocalMediaStore, MediaStore},
+    models::UserRole,
+    test_helpers::{self, bearer_header, create_test_category, create_test_equipment, create_test_user},
+};
+
+const BOUNDARY: &str = "----technical-document-boundary";
+
+/// Text fields, file content, file content type and the expected error code
+type Case<'a> = (&'a [(&'a str, &'a str)], &'a [u8], &'a str, &'a str);
+
+/// A document upload with a PDF `file` part and the given text fields
+fn document_form(fields: &[(&str, &str)], file: &[u8], content_type: &str) -> (String, Vec<u8>) {
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+            BOUNDARY, name, value
+        ).as_bytes());
+    }
+    body.extend_from_slice(format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"docs/manual.pdf\"\r\nContent-Type: {}\r\n\r\n",
+        BOUNDARY, content_type
+    ).as_bytes());
+    body.extend_from_slice(file);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+    (format!("multipart/form-data; boundary={}", BOUNDARY), body)
+}
+
+fn stored(store: &LocalMediaStore, url: &serde_json::Value) -> Vec<u8> {
+    let key = url.as_str().unwrap().split("/media/").nth(1).unwrap();
+    std::fs::read(store.path(key).unwrap()).unwrap_or_default()
+}
+
+#[actix_web::test]
+async fn test_uploads_keep_versions_and_list_latest() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let store = test_helpers::media_store();
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let category = create_test_category(&pool);
+    let item = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let app = test::init_service(
+        test_helpers::app(&pool).app_data(web::Data::from(Arc::new(store.clone()) as Arc<dyn MediaStore>))
+    ).await;
+
+    let upload = |document_type: &str, title: &str, version: &str, file: &[u8]| {
+        let (content_type, body) = document_form(
+            &[("document_type", document_type), ("title", title), ("version", version)],
+            file,
+            "application/pdf",
+        );
+        test::TestRequest::post()
+            .uri(&format!("/equipment/{}/documents", item.id))
+            .insert_header(bearer_header(&supplier))
+            .insert_header(("content-type", content_type))
+            .set_payload(body)
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, upload("manual", "Operator manual", "Rev A", b"%PDF-1.4 first")).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let first: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(first["revision"], 1);
+    assert_eq!(first["version"], "Rev A");
+    assert_eq!(first["document_type"], "manual");
+    assert_eq!(first["file_name"], "manual.pdf");
+    assert_eq!(first["uploaded_by"], supplier.id);
+    assert!(first.get("storage_key").is_none());
+    assert_eq!(stored(&store, &first["document_url"]), b"%PDF-1.4 first");
+
+    let resp = test::call_service(&app, upload("manual", "Operator manual", "Rev B", b"%PDF-1.4 second")).await;
+    let second: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(second["revision"], 2);
+
+    let resp = test::call_service(&app, upload("certification", "CE certificate", "", b"%PDF-1.7 ce")).await;
+    let certificate: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(certificate["revision"], 1);
+    assert!(certificate["version"].is_null());
+
+    let req = test::TestRequest::get().uri(&format!("/equipment/{}/documents", item.id)).to_request();
+    let latest: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let summary: Vec<_> = latest.iter().map(|d| (d["id"].clone(), d["version_count"].as_i64().unwrap())).collect();
+    assert_eq!(summary, vec![(certificate["id"].clone(), 1), (second["id"].clone(), 2)]);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/{}/documents?document_type=certification", item.id))
+        .to_request();
+    let latest: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(latest.len(), 1);
+    assert_eq!(latest[0]["id"], certificate["id"]);
+
+    // The history is reachable from any version
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/{}/documents/{}/versions", item.id, first["id"]))
+        .to_request();
+    let versions: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let revisions: Vec<_> = versions.iter().map(|v| v["revision"].as_i64().unwrap()).collect();
+    assert_eq!(revisions, vec![2, 1]);
+
+    // Deleting the latest version makes the previous one current again
+    let req = test::TestRequest::delete()
+        .uri(&format!("/equipment/{}/documents/{}", item.id, second["id"]))
+        .insert_header(bearer_header(&supplier))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+    assert!(stored(&store, &second["document_url"]).is_empty());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/{}/documents?document_type=manual", item.id))
+        .to_request();
+    let latest: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(latest[0]["id"], first["id"]);
+    assert_eq!(latest[0]["version_count"], 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/{}/documents/{}", item.id, second["id"]))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::DOCUMENT_NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_upload_rejects_invalid_documents_and_other_suppliers() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let store = test_helpers::media_store();
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let other = create_test_user(&pool, Some(UserRole::Supplier));
+    let category = create_test_category(&pool);
+    let item = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let app = test::init_service(
+        test_helpers::app(&pool).app_data(web::Data::from(Arc::new(store.clone()) as Arc<dyn MediaStore>))
+    ).await;
+
+    let upload = |user, fields: &[(&str, &str)], file: &[u8], content_type: &str| {
+        let (form_type, body) = document_form(fields, file, content_type);
+        test::TestRequest::post()
+            .uri(&format!("/equipment/{}/documents", item.id))
+            .insert_header(bearer_header(user))
+            .insert_header(("content-type", form_type))
+            .set_payload(body)
+            .to_request()
+    };
+    let valid = [("document_type", "manual"), ("title", "Manual")];
+
+    let resp = test::call_service(&app, upload(&other, &valid, b"%PDF-1.4", "application/pdf")).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let cases: [Case; 4] = [
+        (&[("document_type", "brochure"), ("title", "Manual")], b"%PDF-1.4", "application/pdf", codes::VALIDATION_FAILED),
+        (&[("document_type", "manual"), ("title", "  ")], b"%PDF-1.4", "application/pdf", codes::VALIDATION_FAILED),
+        (&valid, b"<html>not a pdf</html>", "application/pdf", codes::DOCUMENT_INVALID),
+        (&valid, b"PK\x03\x04", "application/zip", codes::DOCUMENT_UNSUPPORTED_TYPE),
+    ];
+    for (fields, file, content_type, code) in cases {
+        let resp = test::call_service(&app, upload(&supplier, fields, file, content_type)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{:?}", fields);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], code, "{:?}", fields);
+    }
+
+    assert_eq!(std::fs::read_dir(store.dir()).unwrap().count(), 0, "Rejected uploads leave no files");
+}
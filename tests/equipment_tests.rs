@@ -0,0 +1,372 @@
+use actix_web::{test, http::StatusCode};
+use diesel::RunQueryDsl;
+use serde_json::json;
+use rust_market::{
+    db::{equipment, establish_connection_pool},
+    errors::codes,
+    models::{NewEquipmentImage, NewOrder, NewOrderItem, OrderStatus, UserRole},
+    schema::{equipment_images, order_items, orders},
+    test_helpers::{self, bearer_header, create_test_category, create_test_equipment, create_test_user},
+};
+
+fn listing(category_id: i32) -> serde_json::Value {
+    json!({
+        "category_id": category_id,
+        "name": "Wheel Loader",
+        "description": "Bucket and forks included",
+        "manufacturer": "Komatsu",
+        "model_number": "WA380",
+        "year_manufactured": 2020,
+        "condition": "used",
+        "price": "98000.00",
+        "stock_level": 2
+    })
+}
+
+#[actix_web::test]
+async fn test_supplier_creates_and_reads_listing() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let category = create_test_category(&pool);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/equipment")
+        .insert_header(bearer_header(&supplier))
+        .set_json(listing(category.id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let created: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(created["supplier_id"], supplier.id);
+    assert_eq!(created["category_name"], category.name.as_str());
+    assert!(created["primary_image"].is_null());
+
+    // Reading a listing needs no account
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/{}", created["id"]))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let fetched: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(fetched["model_number"], "WA380");
+}
+
+#[actix_web::test]
+async fn test_buyers_cannot_create_listings() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let buyer = create_test_user(&pool, Some(UserRole::Buyer));
+    let category = create_test_category(&pool);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/equipment")
+        .insert_header(bearer_header(&buyer))
+        .set_json(listing(category.id))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_create_rejects_unknown_category_and_invalid_fields() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/equipment")
+        .insert_header(bearer_header(&supplier))
+        .set_json(listing(i32::MAX))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["details"][0]["field"], "category_id");
+
+    let mut invalid = listing(i32::MAX);
+    invalid["name"] = json!("");
+    invalid["stock_level"] = json!(-1);
+    let req = test::TestRequest::post()
+        .uri("/equipment")
+        .insert_header(bearer_header(&supplier))
+        .set_json(invalid)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::VALIDATION_FAILED);
+    let fields: Vec<&str> = body["details"].as_array().unwrap()
+        .iter()
+        .map(|d| d["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, vec!["name", "stock_level"]);
+
+    // Unknown conditions are rejected while reading the body
+    let mut invalid = listing(i32::MAX);
+    invalid["condition"] = json!("scrap");
+    let req = test::TestRequest::post()
+        .uri("/equipment")
+        .insert_header(bearer_header(&supplier))
+        .set_json(invalid)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::INVALID_JSON);
+    let message = body["message"].as_str().unwrap();
+    assert!(
+        message.contains("Unknown equipment condition 'scrap', expected one of: new, used, refurbished"),
+        "{}", message
+    );
+}
+
+#[actix_web::test]
+async fn test_only_owner_or_admin_can_modify_listing() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let owner = create_test_user(&pool, Some(UserRole::Supplier));
+    let other = create_test_user(&pool, Some(UserRole::Supplier));
+    let admin = create_test_user(&pool, Some(UserRole::Admin));
+    let category = create_test_category(&pool);
+    let item = create_test_equipment(&pool, category.id, Some(owner.id));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/equipment/{}", item.id))
+        .insert_header(bearer_header(&other))
+        .set_json(json!({"price": "1.00"}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/equipment/{}", item.id))
+        .insert_header(bearer_header(&owner))
+        .set_json(json!({"price": "119500.00", "description": null}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["price"], "119500.00");
+    assert!(body["description"].is_null());
+    assert_eq!(body["name"], item.name.as_str());
+
+    // A full replacement keeps the original supplier even when done by an admin
+    let req = test::TestRequest::put()
+        .uri(&format!("/equipment/{}", item.id))
+        .insert_header(bearer_header(&admin))
+        .set_json(listing(category.id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["name"], "Wheel Loader");
+    assert_eq!(body["supplier_id"], owner.id);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/equipment/{}", item.id))
+        .insert_header(bearer_header(&other))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/equipment/{}", item.id))
+        .insert_header(bearer_header(&owner))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/{}", item.id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::EQUIPMENT_NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_list_filters_and_embeds_primary_image() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let category = create_test_category(&pool);
+    let other_category = create_test_category(&pool);
+    let pictured = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let plain = create_test_equipment(&pool, category.id, Some(supplier.id));
+    create_test_equipment(&pool, other_category.id, Some(supplier.id));
+
+    {
+        let conn = &mut pool.get().expect("Failed to get db connection");
+        diesel::insert_into(equipment_images::table)
+            .values(&vec![
+                NewEquipmentImage {
+                    equipment_id: pictured.id,
+                    image_url: "https://img.example.com/side.jpg".to_string(),
+                    is_primary: false,
+                    position: 0,
+                    storage_key: None,
+                    content_type: None,
+                    byte_size: None,
+                    width: None,
+                    height: None,
+                    thumbnails: serde_json::json!([]),
+                },
+                NewEquipmentImage {
+                    equipment_id: pictured.id,
+                    image_url: "https://img.example.com/front.jpg".to_string(),
+                    is_primary: true,
+                    position: 1,
+                    storage_key: None,
+                    content_type: None,
+                    byte_size: None,
+                    width: None,
+                    height: None,
+                    thumbnails: serde_json::json!([]),
+                },
+            ])
+            .execute(conn)
+            .expect("Failed to insert images");
+    }
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment?category_id={}&per_page=10", category.id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["total"], 2);
+
+    let items = body["items"].as_array().unwrap();
+    // Newest first
+    assert_eq!(items[0]["id"], plain.id);
+    assert!(items[0]["primary_image"].is_null());
+    assert_eq!(items[1]["id"], pictured.id);
+    assert_eq!(items[1]["primary_image"]["image_url"], "https://img.example.com/front.jpg");
+    assert_eq!(items[1]["category_name"], category.name.as_str());
+}
+
+#[actix_web::test]
+async fn test_dimensions_are_validated_filtered_and_converted() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let category = create_test_category(&pool);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let create = |changes: serde_json::Value| {
+        let mut body = listing(category.id);
+        body.as_object_mut().unwrap().extend(changes.as_object().unwrap().clone());
+        test::TestRequest::post()
+            .uri("/equipment")
+            .insert_header(bearer_header(&supplier))
+            .set_json(body)
+            .to_request()
+    };
+
+    // All three dimensions or none, each positive
+    let resp = test::call_service(&app, create(json!({ "length_cm": "800", "width_cm": "0" }))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let fields: Vec<_> = body["details"].as_array().unwrap().iter().map(|d| d["field"].as_str().unwrap()).collect();
+    assert_eq!(fields, ["width_cm", "height_cm"]);
+
+    let large = json!({ "weight_kg": "45000.00", "length_cm": "800", "width_cm": "300", "height_cm": "254" });
+    let resp = test::call_service(&app, create(large)).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let large: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(large["length_cm"], "800.00");
+    assert!(large.get("length_in").is_none(), "Imperial values are only added on request");
+
+    let small = json!({ "weight_kg": "900", "length_cm": "120", "width_cm": "80", "height_cm": "100" });
+    let small: serde_json::Value = test::call_and_read_body_json(&app, create(small)).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/{}?units=imperial", large["id"]))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["weight_kg"], "45000.00");
+    assert_eq!(body["weight_lb"], "99208.02");
+    assert_eq!(body["length_in"], "314.96");
+    assert_eq!(body["height_in"], "100.00");
+
+    // Size limits are metric; listings without dimensions never match
+    create_test_equipment(&pool, category.id, Some(supplier.id));
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/equipment/search?category_id={}&max_length_cm=500&max_height_cm=100&units=imperial",
+            category.id
+        ))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let items = body["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["id"], small["id"]);
+    assert_eq!(items[0]["weight_lb"], "1984.16");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/search?category_id={}&max_weight_kg=1000", category.id))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["total"], 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/{}?units=furlongs", large["id"]))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn test_delete_ordered_equipment_conflicts() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let buyer = create_test_user(&pool, Some(UserRole::Buyer));
+    let category = create_test_category(&pool);
+    let item = create_test_equipment(&pool, category.id, Some(supplier.id));
+
+    {
+        let conn = &mut pool.get().expect("Failed to get db connection");
+        let now = chrono::Utc::now().naive_utc();
+        let order: rust_market::models::Order = diesel::insert_into(orders::table)
+            .values(NewOrder {
+                user_id: buyer.id,
+                status: OrderStatus::Pending,
+                total_amount: item.price.clone(),
+                shipping_address: "1 Quarry Road".to_string(),
+                shipping_method: "freight".to_string(),
+                tracking_number: None,
+                estimated_delivery_date: None,
+                special_instructions: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .get_result(conn)
+            .expect("Failed to insert order");
+        diesel::insert_into(order_items::table)
+            .values(NewOrderItem {
+                order_id: order.id,
+                equipment_id: item.id,
+                quantity: 1,
+                price_at_time: item.price.clone(),
+                warranty_selected: None,
+                special_requirements: None,
+            })
+            .execute(conn)
+            .expect("Failed to insert order item");
+    }
+
+    let err = equipment::delete_equipment(&pool, item.id).unwrap_err();
+    assert_eq!(err.code(), codes::EQUIPMENT_IN_USE);
+    assert!(equipment::get_equipment_by_id(&pool, item.id).is_ok());
+}
@@ -1,5 +1,5 @@
 use actix_web::ResponseError;
-use rust_market::errors::ServiceError;
+use rust_market::errors::{codes, ServiceError};
 
 #[test]
 fn test_service_error_response_database_error() {
@@ -41,6 +41,14 @@ fn test_service_error_response_unauthorized() {
     assert_eq!(response.status().as_u16(), 401);
 }
 
+#[test]
+fn test_service_error_response_forbidden() {
+    let forbidden_error = ServiceError::Forbidden("Only suppliers can list equipment".into());
+    let response = forbidden_error.error_response();
+    
+    assert_eq!(response.status().as_u16(), 403);
+}
+
 #[test]
 fn test_service_error_response_bad_request() {
     let bad_request_error = ServiceError::BadRequest("Invalid input format".into());
@@ -49,11 +57,95 @@ fn test_service_error_response_bad_request() {
     assert_eq!(response.status().as_u16(), 400);
 }
 
+#[actix_web::test]
+async fn test_error_body_has_code_and_sanitized_message() {
+    use actix_web::body::to_bytes;
+
+    let response = ServiceError::DatabaseError("relation \"users\" does not exist".into()).error_response();
+    let body: serde_json::Value = serde_json::from_slice(&to_bytes(response.into_body()).await.unwrap()).unwrap();
+    assert_eq!(body["code"], "DATABASE_ERROR");
+    assert_eq!(body["message"], "An unexpected error occurred");
+    assert!(body.get("details").is_none());
+
+    let coded = ServiceError::Conflict("Email is already taken".into()).with_code(codes::USER_EMAIL_TAKEN);
+    assert_eq!(coded.status_code().as_u16(), 409);
+    let body: serde_json::Value = serde_json::from_slice(&to_bytes(coded.error_response().into_body()).await.unwrap()).unwrap();
+    assert_eq!(body["error"], "Conflict");
+    assert_eq!(body["code"], "USER_EMAIL_TAKEN");
+    assert_eq!(body["message"], "Email is already taken");
+}
+
+#[actix_web::test]
+async fn test_unique_violation_does_not_leak_constraint_text() {
+    use actix_web::body::to_bytes;
+    use diesel::result::{DatabaseErrorKind, Error};
+
+    let violation = Error::DatabaseError(
+        DatabaseErrorKind::UniqueViolation,
+        Box::new("duplicate key value violates unique constraint \"users_email_key\"".to_string()),
+    );
+    let response = ServiceError::from(violation).error_response();
+    assert_eq!(response.status().as_u16(), 409);
+    let body: serde_json::Value = serde_json::from_slice(&to_bytes(response.into_body()).await.unwrap()).unwrap();
+    assert_eq!(body["code"], "CONFLICT");
+    assert_eq!(body["message"], "Resource already exists");
+}
+
+#[actix_web::test]
+async fn test_field_errors_and_request_id_in_response() {
+    use actix_web::{middleware::from_fn, test, App, web};
+    use rust_market::db::establish_connection_pool;
+    use rust_market::{configure, request_id, test_helpers};
+
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool))
+            .wrap(from_fn(request_id::middleware))
+            .configure(configure)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/users")
+        .insert_header(("X-Request-Id", "req-123"))
+        .set_json(serde_json::json!({
+            "username": "",
+            "email": "nobody@example.com",
+            "password": "haul truck 797",
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    assert_eq!(resp.headers().get("x-request-id").unwrap(), "req-123");
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "VALIDATION_FAILED");
+    assert_eq!(body["request_id"], "req-123");
+    assert_eq!(body["details"][0]["field"], "username");
+    assert!(body["details"][0]["reason"].as_str().unwrap().contains("between 1 and 50"));
+
+    // Malformed JSON uses the same format, with a generated request id
+    let req = test::TestRequest::post()
+        .uri("/users")
+        .insert_header(("Content-Type", "application/json"))
+        .set_payload("{not json")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    let generated = resp.headers().get("x-request-id").unwrap().to_str().unwrap().to_string();
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "INVALID_JSON");
+    assert_eq!(body["request_id"], generated.as_str());
+}
+
 #[actix_web::test]
 async fn test_create_user_validation_error() {
     use actix_web::{test, App, web};
     use rust_market::handlers::create_user;
-    use rust_market::models::NewUser;
+    use rust_market::models::RegisterUser;
     use rust_market::db::establish_connection_pool;
     use rust_market::test_helpers;
     
@@ -69,7 +161,7 @@ async fn test_create_user_validation_error() {
 
     let req = test::TestRequest::post()
         .uri("/users")
-        .set_json(&NewUser::new(
+        .set_json(RegisterUser::new(
             "".to_string(),
             "test@example.com".to_string(),
             "hashedpassword123".to_string(),
@@ -89,7 +181,7 @@ async fn test_create_user_validation_error() {
 async fn test_create_user_invalid_email() {
     use actix_web::{test, App, web};
     use rust_market::handlers::create_user;
-    use rust_market::models::NewUser;
+    use rust_market::models::RegisterUser;
     use rust_market::db::establish_connection_pool;
     use rust_market::test_helpers;
     
@@ -105,7 +197,7 @@ async fn test_create_user_invalid_email() {
 
     let req = test::TestRequest::post()
         .uri("/users")
-        .set_json(&NewUser::new(
+        .set_json(RegisterUser::new(
             "testuser".to_string(),
             "invalid-email".to_string(),
             "hashedpassword123".to_string(),
@@ -125,7 +217,7 @@ async fn test_create_user_invalid_email() {
 async fn test_create_user_invalid_password() {
     use actix_web::{test, App, web};
     use rust_market::handlers::create_user;
-    use rust_market::models::NewUser;
+    use rust_market::models::RegisterUser;
     use rust_market::db::establish_connection_pool;
     use rust_market::test_helpers;
     
@@ -141,7 +233,7 @@ async fn test_create_user_invalid_password() {
 
     let req = test::TestRequest::post()
         .uri("/users")
-        .set_json(&NewUser::new(
+        .set_json(RegisterUser::new(
             "testuser".to_string(),
             "test@example.com".to_string(),
             "short".to_string(),
@@ -154,5 +246,5 @@ async fn test_create_user_invalid_password() {
     
     let body: serde_json::Value = test::read_body_json(resp).await;
     assert_eq!(body["error"], "Validation Error");
-    assert!(body["message"].as_str().unwrap().contains("Password hash must be at least 8 characters"));
+    assert!(body["message"].as_str().unwrap().contains("Password must be at least 8 characters"));
 }
@@ -1,7 +1,8 @@
 use actix_web::{test, http::StatusCode, App, web};
 use rust_market::{
+    configure,
     handlers::{health_check, create_user},
-    models::NewUser,
+    models::RegisterUser,
     db::establish_connection_pool,
     test_helpers,
     schema::users,
@@ -42,6 +43,29 @@ async fn test_health_check_wrong_method() {
     );
 }
 
+#[actix_web::test]
+async fn test_configure_registers_routes() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool))
+            .app_data(web::Data::new(test_helpers::token_config()))
+            .configure(configure)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/health").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Registered but protected routes reject anonymous requests rather than 404
+    let req = test::TestRequest::get().uri("/users/me").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
 #[actix_web::test]
 async fn test_create_user() {
     test_helpers::setup();
@@ -50,7 +74,7 @@ async fn test_create_user() {
     
     // Get a connection and start a transaction
     let conn = &mut pool.get().expect("Failed to get db connection");
-    let _transaction = conn.begin_test_transaction().expect("Failed to start test transaction");
+    conn.begin_test_transaction().expect("Failed to start test transaction");
     
     // Clean up the database before the test
     info!("Starting test_create_user - cleaning database");
@@ -67,7 +91,7 @@ async fn test_create_user() {
     ).await;
 
     // First attempt should succeed
-    let user_data = NewUser::new(
+    let user_data = RegisterUser::new(
         "testuser".to_string(),
         "test@example.com".to_string(),
         "hashedpassword123".to_string(),
@@ -105,7 +129,7 @@ async fn test_create_user_invalid_username() {
     
     // Get a connection and start a transaction
     let conn = &mut pool.get().expect("Failed to get db connection");
-    let _transaction = conn.begin_test_transaction().expect("Failed to start test transaction");
+    conn.begin_test_transaction().expect("Failed to start test transaction");
     
     // Clean up the database before the test
     info!("Starting test_create_user_invalid_username - cleaning database");
@@ -121,7 +145,7 @@ async fn test_create_user_invalid_username() {
             .service(web::resource("/users").route(web::post().to(create_user)))
     ).await;
 
-    let user_data = NewUser::new(
+    let user_data = RegisterUser::new(
         "".to_string(), // Empty username
         "test@example.com".to_string(),
         "hashedpassword123".to_string(),
@@ -149,7 +173,7 @@ async fn test_create_user_invalid_email() {
     
     // Get a connection and start a transaction
     let conn = &mut pool.get().expect("Failed to get db connection");
-    let _transaction = conn.begin_test_transaction().expect("Failed to start test transaction");
+    conn.begin_test_transaction().expect("Failed to start test transaction");
     
     // Clean up the database before the test
     info!("Starting test_create_user_invalid_email - cleaning database");
@@ -165,7 +189,7 @@ async fn test_create_user_invalid_email() {
             .service(web::resource("/users").route(web::post().to(create_user)))
     ).await;
 
-    let user_data = NewUser::new(
+    let user_data = RegisterUser::new(
         "testuser".to_string(),
         "invalid-email".to_string(),
         "hashedpassword123".to_string(),
@@ -193,7 +217,7 @@ async fn test_create_user_invalid_password() {
     
     // Get a connection and start a transaction
     let conn = &mut pool.get().expect("Failed to get db connection");
-    let _transaction = conn.begin_test_transaction().expect("Failed to start test transaction");
+    conn.begin_test_transaction().expect("Failed to start test transaction");
     
     // Clean up the database before the test
     info!("Starting test_create_user_invalid_password - cleaning database");
@@ -209,7 +233,7 @@ async fn test_create_user_invalid_password() {
             .service(web::resource("/users").route(web::post().to(create_user)))
     ).await;
 
-    let user_data = NewUser::new(
+    let user_data = RegisterUser::new(
         "testuser".to_string(),
         "test@example.com".to_string(),
         "short".to_string(),
@@ -0,0 +1,261 @@
+use std::io::Cursor;
+use std::sync::Arc;
+use actix_web::{test, http::StatusCode, web};
+use image::{DynamicImage, ImageBuffer, ImageFormat, Rgb};
+use rust_market::{
+    db::establish_connection_pool,
+    errors::codes,
+    media::{LocalMediaStore, MediaStore, MAX_IMAGE_BYTES},
+    models::UserRole,
+    test_helpers::{self, bearer_header, create_test_category, create_test_equipment, create_test_user},
+};
+
+const BOUNDARY: &str = "----equipment-image-boundary";
+
+/// Name, content type for file parts, and content of a form part
+type Part<'a> = (&'a str, Option<&'a str>, &'a [u8]);
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let image = ImageBuffer::from_pixel(width, height, Rgb([30u8, 90, 160]));
+    let mut data = Vec::new();
+    DynamicImage::ImageRgb8(image)
+        .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+        .unwrap();
+    data
+}
+
+/// A `multipart/form-data` body; parts with a content type are sent as files
+fn multipart(parts: &[Part]) -> (String, Vec<u8>) {
+    let mut body = Vec::new();
+    for (name, content_type, data) in parts {
+        body.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+        match content_type {
+            Some(content_type) => body.extend_from_slice(format!(
+                "Content-Disposition: form-data; name=\"{}\"; filename=\"upload\"\r\nContent-Type: {}\r\n\r\n",
+                name, content_type
+            ).as_bytes()),
+            None => body.extend_from_slice(format!(
+                "Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name
+            ).as_bytes()),
+        }
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+    (format!("multipart/form-data; boundary={}", BOUNDARY), body)
+}
+
+fn stored_files(store: &LocalMediaStore) -> usize {
+    fn count(dir: &std::path::Path) -> usize {
+        std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| if entry.path().is_dir() { count(&entry.path()) } else { 1 })
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+    count(store.dir())
+}
+
+#[actix_web::test]
+async fn test_upload_keeps_exactly_one_primary_image() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let store = test_helpers::media_store();
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let category = create_test_category(&pool);
+    let item = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let app = test::init_service(
+        test_helpers::app(&pool).app_data(web::Data::from(Arc::new(store.clone()) as Arc<dyn MediaStore>))
+    ).await;
+
+    let upload = |primary: Option<&'static [u8]>| {
+        let image = png(1600, 1200);
+        let mut parts: Vec<Part> = vec![("image", Some("image/png"), &image)];
+        if let Some(primary) = primary {
+            parts.push(("primary", None, primary));
+        }
+        let (content_type, body) = multipart(&parts);
+        test::TestRequest::post()
+            .uri(&format!("/equipment/{}/images", item.id))
+            .insert_header(bearer_header(&supplier))
+            .insert_header(("content-type", content_type))
+            .set_payload(body)
+            .to_request()
+    };
+
+    // The first image is primary even when not asked for
+    let resp = test::call_service(&app, upload(None)).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let first: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(first["is_primary"], true);
+    assert_eq!(first["position"], 0);
+    assert_eq!(first["content_type"], "image/png");
+    assert_eq!((first["width"].as_i64(), first["height"].as_i64()), (Some(1600), Some(1200)));
+    assert!(first.get("storage_key").is_none());
+    let thumbnails = first["thumbnails"].as_array().unwrap();
+    let sizes: Vec<_> = thumbnails.iter().map(|t| (t["size"].as_str().unwrap(), t["width"].as_u64().unwrap())).collect();
+    assert_eq!(sizes, vec![("small", 160), ("medium", 480), ("large", 1024)]);
+    assert!(first["image_url"].as_str().unwrap().starts_with("http://localhost:8080/media/equipment/"));
+    assert_eq!(stored_files(&store), 4);
+
+    let resp = test::call_service(&app, upload(Some(b"false"))).await;
+    let second: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(second["is_primary"], false);
+    assert_eq!(second["position"], 1);
+
+    let resp = test::call_service(&app, upload(Some(b"true"))).await;
+    let third: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(third["is_primary"], true);
+
+    let req = test::TestRequest::get().uri(&format!("/equipment/{}/images", item.id)).to_request();
+    let images: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let flags: Vec<_> = images.iter().map(|i| (i["id"].clone(), i["is_primary"].as_bool().unwrap())).collect();
+    assert_eq!(flags, vec![(first["id"].clone(), false), (second["id"].clone(), false), (third["id"].clone(), true)]);
+
+    let req = test::TestRequest::get().uri(&format!("/equipment/{}", item.id)).to_request();
+    let details: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(details["primary_image"]["id"], third["id"]);
+
+    // Setting another primary clears the previous one
+    let req = test::TestRequest::put()
+        .uri(&format!("/equipment/{}/images/{}/primary", item.id, second["id"]))
+        .insert_header(bearer_header(&supplier))
+        .to_request();
+    let images: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let primaries: Vec<_> = images.iter().filter(|i| i["is_primary"] == true).map(|i| i["id"].clone()).collect();
+    assert_eq!(primaries, vec![second["id"].clone()]);
+}
+
+#[actix_web::test]
+async fn test_upload_validates_type_size_and_ownership() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let store = test_helpers::media_store();
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let other_supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let category = create_test_category(&pool);
+    let item = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let app = test::init_service(
+        test_helpers::app(&pool).app_data(web::Data::from(Arc::new(store.clone()) as Arc<dyn MediaStore>))
+    ).await;
+
+    let image = png(64, 64);
+    let oversized = vec![0u8; MAX_IMAGE_BYTES + 1];
+    let cases: Vec<(Vec<Part>, &str)> = vec![
+        (vec![("image", Some("text/plain"), b"hello")], codes::IMAGE_UNSUPPORTED_TYPE),
+        (vec![("image", Some("image/jpeg"), &image)], codes::IMAGE_INVALID),
+        (vec![("image", Some("image/png"), &image[..40])], codes::IMAGE_INVALID),
+        (vec![("image", Some("image/png"), &oversized)], codes::IMAGE_TOO_LARGE),
+        (vec![("primary", None, b"true")], codes::VALIDATION_FAILED),
+        (vec![("image", Some("image/png"), &image), ("primary", None, b"maybe")], codes::VALIDATION_FAILED),
+    ];
+    for (parts, code) in cases {
+        let (content_type, body) = multipart(&parts);
+        let req = test::TestRequest::post()
+            .uri(&format!("/equipment/{}/images", item.id))
+            .insert_header(bearer_header(&supplier))
+            .insert_header(("content-type", content_type))
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "expected {}", code);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], code);
+    }
+    assert_eq!(stored_files(&store), 0);
+
+    let (content_type, body) = multipart(&[("image", Some("image/png"), &image)]);
+    let req = test::TestRequest::post()
+        .uri(&format!("/equipment/{}/images", item.id))
+        .insert_header(bearer_header(&other_supplier))
+        .insert_header(("content-type", content_type))
+        .set_payload(body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_reorder_and_delete_images() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let store = test_helpers::media_store();
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let category = create_test_category(&pool);
+    let item = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let app = test::init_service(
+        test_helpers::app(&pool).app_data(web::Data::from(Arc::new(store.clone()) as Arc<dyn MediaStore>))
+    ).await;
+
+    let mut ids = Vec::new();
+    for _ in 0..3 {
+        let image = png(32, 32);
+        let (content_type, body) = multipart(&[("image", Some("image/png"), &image)]);
+        let req = test::TestRequest::post()
+            .uri(&format!("/equipment/{}/images", item.id))
+            .insert_header(bearer_header(&supplier))
+            .insert_header(("content-type", content_type))
+            .set_payload(body)
+            .to_request();
+        let image: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        ids.push(image["id"].as_i64().unwrap());
+    }
+
+    // Every image exactly once
+    for image_ids in [vec![ids[0], ids[1]], vec![ids[0], ids[1], ids[1]], vec![ids[0], ids[1], ids[2], 0]] {
+        let req = test::TestRequest::put()
+            .uri(&format!("/equipment/{}/images/order", item.id))
+            .insert_header(bearer_header(&supplier))
+            .set_json(serde_json::json!({ "image_ids": image_ids }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/equipment/{}/images/order", item.id))
+        .insert_header(bearer_header(&supplier))
+        .set_json(serde_json::json!({ "image_ids": [ids[2], ids[0], ids[1]] }))
+        .to_request();
+    let images: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let order: Vec<_> = images.iter().map(|i| i["id"].as_i64().unwrap()).collect();
+    assert_eq!(order, vec![ids[2], ids[0], ids[1]]);
+
+    // Deleting the primary promotes the first remaining image
+    let req = test::TestRequest::delete()
+        .uri(&format!("/equipment/{}/images/{}", item.id, ids[0]))
+        .insert_header(bearer_header(&supplier))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(stored_files(&store), 8);
+
+    let req = test::TestRequest::get().uri(&format!("/equipment/{}/images", item.id)).to_request();
+    let images: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let flags: Vec<_> = images.iter().map(|i| (i["id"].as_i64().unwrap(), i["is_primary"].as_bool().unwrap())).collect();
+    assert_eq!(flags, vec![(ids[2], true), (ids[1], false)]);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/equipment/{}/images/{}", item.id, ids[0]))
+        .insert_header(bearer_header(&supplier))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::IMAGE_NOT_FOUND);
+
+    // Deleting the listing removes the remaining files too
+    let req = test::TestRequest::delete()
+        .uri(&format!("/equipment/{}", item.id))
+        .insert_header(bearer_header(&supplier))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(stored_files(&store), 0);
+}
@@ -6,6 +6,20 @@ pub mod models_tests;
 pub mod handlers_tests;
 pub mod errors_tests;
 pub mod performance_tests;
+pub mod auth_tests;
+pub mod users_tests;
+pub mod account_tests;
+pub mod equipment_tests;
+pub mod categories_tests;
+pub mod search_tests;
+pub mod spec_schema_tests;
+pub mod catalog_tests;
+pub mod images_tests;
+pub mod documents_tests;
+pub mod orders_tests;
+pub mod carts_tests;
+pub mod cancellations_tests;
+pub mod reservations_tests;
 
 // Test configuration and utilities
 pub mod test_config;
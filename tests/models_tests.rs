@@ -6,7 +6,7 @@ use rust_market::{
     models::*,
     schema::*,
     db::establish_connection_pool,
-    test_helpers::{setup, cleanup_database},
+    test_helpers::{setup, cleanup_database, create_test_category, create_test_equipment, create_test_user},
 };
 
 // Helper Functions
@@ -30,7 +30,7 @@ fn test_create_new_user() {
         email: format!("testuser{}@example.com", unique_id),
         password_hash: "hashed_password".to_string(),
         company_name: Some("Mining Corp".to_string()),
-        business_type: Some("buyer".to_string()),
+        business_type: Some(UserRole::Buyer),
         contact_number: Some("+1234567890".to_string()),
         created_at: chrono::Utc::now().naive_utc(),
         updated_at: chrono::Utc::now().naive_utc(),
@@ -116,7 +116,7 @@ fn test_create_equipment() {
         manufacturer: "CAT".to_string(),
         model_number: format!("MT-{}", unique_id),
         year_manufactured: Some(2023),
-        condition: "new".to_string(),
+        condition: EquipmentCondition::New,
         price: BigDecimal::from_str("250000.00").unwrap(),
         stock_level: 5,
         specifications: Some(serde_json::json!({
@@ -125,12 +125,15 @@ fn test_create_equipment() {
             "power": "2000 hp"
         })),
         weight_kg: Some(BigDecimal::from_str("45000.00").unwrap()),
-        dimensions_cm: Some("800x300x400".to_string()),
         power_requirements: Some("diesel".to_string()),
         certification_info: Some("ISO 9001:2015".to_string()),
         warranty_info: Some("3 years full warranty".to_string()),
         created_at: chrono::Utc::now().naive_utc(),
         updated_at: chrono::Utc::now().naive_utc(),
+        supplier_id: None,
+        length_cm: Some(BigDecimal::from_str("800").unwrap()),
+        width_cm: Some(BigDecimal::from_str("300").unwrap()),
+        height_cm: Some(BigDecimal::from_str("400").unwrap()),
     };
 
     let result = diesel::insert_into(equipment::table)
@@ -167,7 +170,7 @@ fn test_create_order() {
             email: "testuser@example.com".to_string(),
             password_hash: "hashed_password".to_string(),
             company_name: Some("Mining Corp".to_string()),
-            business_type: Some("buyer".to_string()),
+            business_type: Some(UserRole::Buyer),
             contact_number: Some("+1234567890".to_string()),
             created_at: chrono::Utc::now().naive_utc(),
             updated_at: chrono::Utc::now().naive_utc(),
@@ -201,7 +204,7 @@ fn test_create_order() {
             manufacturer: "CAT".to_string(),
             model_number: format!("MT-{}", unique_id),
             year_manufactured: Some(2023),
-            condition: "new".to_string(),
+            condition: EquipmentCondition::New,
             price: BigDecimal::from_str("250000.00").unwrap(),
             stock_level: 5,
             specifications: Some(serde_json::json!({
@@ -209,12 +212,15 @@ fn test_create_order() {
                 "engine": "diesel"
             })),
             weight_kg: Some(BigDecimal::from_str("45000.00").unwrap()),
-            dimensions_cm: Some("800x300x400".to_string()),
             power_requirements: Some("diesel".to_string()),
             certification_info: Some("ISO 9001:2015".to_string()),
             warranty_info: Some("3 years full warranty".to_string()),
             created_at: chrono::Utc::now().naive_utc(),
             updated_at: chrono::Utc::now().naive_utc(),
+            supplier_id: None,
+            length_cm: Some(BigDecimal::from_str("800").unwrap()),
+            width_cm: Some(BigDecimal::from_str("300").unwrap()),
+            height_cm: Some(BigDecimal::from_str("400").unwrap()),
         };
 
         let equipment = diesel::insert_into(equipment::table)
@@ -225,7 +231,7 @@ fn test_create_order() {
         // Create order
         let new_order = NewOrder {
             user_id: user.id,
-            status: "pending".to_string(),
+            status: OrderStatus::Pending,
             total_amount: BigDecimal::from_str("250000.00").unwrap(),
             shipping_address: "123 Mining Ave, Rock City".to_string(),
             shipping_method: "freight".to_string(),
@@ -266,3 +272,49 @@ fn test_create_order() {
 
     cleanup_database(&pool);
 }
+
+#[test]
+fn test_condition_and_status_parse_and_serialize() {
+    for condition in EquipmentCondition::ALL {
+        assert_eq!(condition.as_str().parse::<EquipmentCondition>(), Ok(condition));
+        assert_eq!(serde_json::to_value(condition).unwrap(), condition.as_str());
+    }
+    for status in OrderStatus::ALL {
+        assert_eq!(status.as_str().parse::<OrderStatus>(), Ok(status));
+    }
+
+    let err = serde_json::from_str::<OrderStatus>("\"lost\"").unwrap_err();
+    assert!(err.to_string().starts_with(
+        "Unknown order status 'lost', expected one of: pending, confirmed, shipped, delivered, cancelled"
+    ));
+    assert!("Used".parse::<EquipmentCondition>().is_err());
+}
+
+#[test]
+fn test_database_rejects_unknown_condition_and_status() {
+    setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create connection pool");
+    let user = create_test_user(&pool, Some(UserRole::Buyer));
+    let category = create_test_category(&pool);
+    let item = create_test_equipment(&pool, category.id, None);
+    let conn = &mut pool.get()
+        .expect("Failed to get db connection");
+
+    let condition = diesel::sql_query("UPDATE equipment SET condition = 'scrap' WHERE id = $1")
+        .bind::<diesel::sql_types::Integer, _>(item.id)
+        .execute(conn);
+    let status = diesel::sql_query(
+        "INSERT INTO orders (user_id, status, total_amount, shipping_address, shipping_method) \
+         VALUES ($1, 'lost', 0, 'x', 'x')"
+    )
+        .bind::<diesel::sql_types::Integer, _>(user.id)
+        .execute(conn);
+
+    for result in [condition, status] {
+        assert!(matches!(
+            result,
+            Err(diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::CheckViolation, _))
+        ), "{:?}", result);
+    }
+}
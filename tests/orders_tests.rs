@@ -0,0 +1,364 @@
+use actix_web::{test, http::StatusCode};
+use bigdecimal::BigDecimal;
+use diesel::prelude::*;
+use serde_json::json;
+use rust_market::{
+    db::{self, establish_connection_pool},
+    errors::{codes, ServiceError},
+    models::{
+        CancelOrderRequest, CancellationReason, OrderLineRequest, OrderStatus, OrderTransition, PlaceOrderRequest, UserRole,
+    },
+    schema::{equipment, order_items, orders},
+    test_helpers::{
+        self, bearer_header, create_test_category, create_test_equipment, create_test_user, create_verified_buyer,
+        order_request, place_order, stock_level,
+    },
+};
+
+fn order_count(pool: &db::DbPool, user_id: i32) -> i64 {
+    let conn = &mut pool.get().expect("Failed to get db connection");
+    orders::table.filter(orders::user_id.eq(user_id)).count().get_result(conn).unwrap()
+}
+
+#[actix_web::test]
+async fn test_place_order_decrements_stock_and_snapshots_prices() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let buyer = create_verified_buyer(&pool);
+    let category = create_test_category(&pool);
+    let loader = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let drill = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    // The client cannot set prices or the total
+    let mut body = order_request(&[(loader.id, 2), (drill.id, 1)]);
+    body["total_amount"] = json!("1.00");
+    body["items"][0]["price_at_time"] = json!("1.00");
+    let req = test::TestRequest::post()
+        .uri("/orders")
+        .insert_header(bearer_header(&buyer))
+        .set_json(body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let placed: serde_json::Value = test::read_body_json(resp).await;
+
+    assert_eq!(placed["user_id"], buyer.id);
+    assert_eq!(placed["status"], "pending");
+    assert_eq!(placed["total_amount"], "375000.00");
+    let lines: Vec<_> = placed["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| (item["equipment_id"].as_i64().unwrap(), item["quantity"].as_i64().unwrap(), item["price_at_time"].clone()))
+        .collect();
+    assert_eq!(lines, vec![
+        (loader.id as i64, 2, json!("125000.00")),
+        (drill.id as i64, 1, json!("125000.00")),
+    ]);
+    assert_eq!((stock_level(&pool, loader.id), stock_level(&pool, drill.id)), (1, 2));
+
+    // Later price changes leave the order as placed
+    {
+        let conn = &mut pool.get().expect("Failed to get db connection");
+        diesel::update(equipment::table.find(loader.id))
+            .set(equipment::price.eq("99.00".parse::<BigDecimal>().unwrap()))
+            .execute(conn)
+            .unwrap();
+    }
+    let conn = &mut pool.get().expect("Failed to get db connection");
+    let price: BigDecimal = order_items::table
+        .filter(order_items::order_id.eq(placed["id"].as_i64().unwrap() as i32))
+        .filter(order_items::equipment_id.eq(loader.id))
+        .select(order_items::price_at_time)
+        .first(conn)
+        .unwrap();
+    assert_eq!(price, "125000.00".parse().unwrap());
+}
+
+#[actix_web::test]
+async fn test_insufficient_stock_changes_nothing() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let buyer = create_verified_buyer(&pool);
+    let category = create_test_category(&pool);
+    let loader = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let drill = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/orders")
+        .insert_header(bearer_header(&buyer))
+        .set_json(order_request(&[(loader.id, 1), (drill.id, 4)]))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::INSUFFICIENT_STOCK);
+    assert!(body["message"].as_str().unwrap().contains(&format!("equipment {} has 3 in stock, 4 requested", drill.id)));
+
+    assert_eq!((stock_level(&pool, loader.id), stock_level(&pool, drill.id)), (3, 3));
+    assert_eq!(order_count(&pool, buyer.id), 0);
+
+    // Callers of the db layer get the same checks as the endpoint, so two
+    // lines of one listing cannot each be filled from the whole stock
+    let request: PlaceOrderRequest = serde_json::from_value(order_request(&[(drill.id, 2), (drill.id, 2)])).unwrap();
+    let err = db::orders::place_order(&pool, buyer.id, &request).unwrap_err();
+    assert!(matches!(err, ServiceError::InvalidFields(_)));
+    assert_eq!(stock_level(&pool, drill.id), 3);
+
+    // And the database refuses to oversell whatever the caller
+    let conn = &mut pool.get().expect("Failed to get db connection");
+    let oversold = diesel::update(equipment::table.find(drill.id))
+        .set(equipment::stock_level.eq(-1))
+        .execute(conn);
+    assert!(oversold.is_err());
+}
+
+#[actix_web::test]
+async fn test_place_order_requires_verified_buyer_and_valid_lines() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let unverified = create_test_user(&pool, Some(UserRole::Buyer));
+    let buyer = create_verified_buyer(&pool);
+    let category = create_test_category(&pool);
+    let loader = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    for user in [&supplier, &unverified] {
+        let req = test::TestRequest::post()
+            .uri("/orders")
+            .insert_header(bearer_header(user))
+            .set_json(order_request(&[(loader.id, 1)]))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+    }
+
+    let cases = [
+        (order_request(&[]), vec!["items"]),
+        (order_request(&[(loader.id, 1), (loader.id, 0)]), vec!["items[1].equipment_id", "items[1].quantity"]),
+        (order_request(&[(loader.id, 1), (-1, 1)]), vec!["items[1].equipment_id"]),
+    ];
+    for (body, expected) in cases {
+        let req = test::TestRequest::post()
+            .uri("/orders")
+            .insert_header(bearer_header(&buyer))
+            .set_json(body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        let fields: Vec<_> = body["details"].as_array().unwrap().iter().map(|d| d["field"].as_str().unwrap()).collect();
+        assert_eq!(fields, expected);
+    }
+
+    assert_eq!(stock_level(&pool, loader.id), 3);
+    assert_eq!(order_count(&pool, buyer.id), 0);
+}
+
+#[actix_web::test]
+async fn test_concurrent_orders_never_oversell() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let category = create_test_category(&pool);
+    let loader = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let buyers: Vec<_> = (0..4).map(|_| create_verified_buyer(&pool)).collect();
+
+    let handles: Vec<_> = buyers
+        .iter()
+        .map(|buyer| {
+            let pool = pool.clone();
+            let buyer_id = buyer.id;
+            let request = PlaceOrderRequest {
+                items: vec![OrderLineRequest {
+                    equipment_id: loader.id,
+                    quantity: 2,
+                    warranty_selected: None,
+                    special_requirements: None,
+                }],
+                shipping_address: "Pit 4".to_string(),
+                shipping_method: "freight".to_string(),
+                special_instructions: None,
+            };
+            std::thread::spawn(move || db::orders::place_order(&pool, buyer_id, &request))
+        })
+        .collect();
+    let results: Vec<_> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+
+    // Three in stock: exactly one order of two fits
+    assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+    for error in results.iter().filter_map(|result| result.as_ref().err()) {
+        assert_eq!(error.code(), codes::INSUFFICIENT_STOCK);
+    }
+    assert_eq!(stock_level(&pool, loader.id), 1);
+}
+
+#[actix_web::test]
+async fn test_order_lifecycle_is_recorded_in_history() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let buyer = create_verified_buyer(&pool);
+    let category = create_test_category(&pool);
+    let loader = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let order_id = place_order(&pool, buyer.id, &[(loader.id, 1)]).order.id;
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let post = |action: &str| test::TestRequest::post()
+        .uri(&format!("/orders/{}/{}", order_id, action))
+        .insert_header(bearer_header(&supplier));
+
+    let resp = test::call_service(&app, post("confirm").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let confirmed: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(confirmed["status"], "confirmed");
+
+    let delivery_date = (chrono::Utc::now() + chrono::Duration::days(14)).date_naive();
+    let req = post("ship")
+        .set_json(json!({ "tracking_number": " TRK-0042 ", "estimated_delivery_date": delivery_date }))
+        .to_request();
+    let shipped: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(shipped["status"], "shipped");
+    assert_eq!(shipped["tracking_number"], "TRK-0042");
+    assert_eq!(shipped["estimated_delivery_date"], delivery_date.to_string());
+    assert_eq!(shipped["items"].as_array().unwrap().len(), 1);
+
+    // Shipped orders can no longer be cancelled
+    let cancel = OrderTransition::Cancel(CancelOrderRequest {
+        reason: CancellationReason::BuyerRequest,
+        note: Some("Changed my mind".into()),
+    });
+    let error = db::orders::transition_order(&pool, order_id, buyer.id, &cancel).unwrap_err();
+    assert_eq!(error.code(), codes::ORDER_INVALID_TRANSITION);
+
+    let resp = test::call_service(&app, post("deliver").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = test::call_service(&app, post("confirm").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::ORDER_INVALID_TRANSITION);
+    assert_eq!(body["message"], format!("Order {} is delivered and cannot be confirmed", order_id));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/orders/{}/history", order_id))
+        .insert_header(bearer_header(&buyer))
+        .to_request();
+    let history: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let timeline: Vec<_> = history
+        .iter()
+        .map(|entry| (entry["from_status"].clone(), entry["to_status"].clone(), entry["changed_by"].as_i64().unwrap()))
+        .collect();
+    assert_eq!(timeline, vec![
+        (json!(null), json!("pending"), buyer.id as i64),
+        (json!("pending"), json!("confirmed"), supplier.id as i64),
+        (json!("confirmed"), json!("shipped"), supplier.id as i64),
+        (json!("shipped"), json!("delivered"), supplier.id as i64),
+    ]);
+}
+
+#[actix_web::test]
+async fn test_only_involved_users_can_view_or_fulfil_orders() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let other_supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let buyer = create_verified_buyer(&pool);
+    let other_buyer = create_verified_buyer(&pool);
+    let admin = create_test_user(&pool, Some(UserRole::Admin));
+    let category = create_test_category(&pool);
+    let loader = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let drill = create_test_equipment(&pool, category.id, Some(other_supplier.id));
+    let order_id = place_order(&pool, buyer.id, &[(loader.id, 1)]).order.id;
+    let shared_order_id = place_order(&pool, buyer.id, &[(loader.id, 1), (drill.id, 1)]).order.id;
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    for (user, expected) in [
+        (&buyer, StatusCode::OK),
+        (&supplier, StatusCode::OK),
+        (&admin, StatusCode::OK),
+        (&other_buyer, StatusCode::FORBIDDEN),
+        (&other_supplier, StatusCode::FORBIDDEN),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/orders/{}", order_id))
+            .insert_header(bearer_header(user))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), expected);
+    }
+
+    for user in [&buyer, &other_supplier] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/orders/{}/confirm", order_id))
+            .insert_header(bearer_header(user))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+    }
+
+    // Shipping details are order-wide, so an order with lines from two
+    // suppliers can be viewed by either but fulfilled by neither
+    for user in [&supplier, &other_supplier] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/orders/{}", shared_order_id))
+            .insert_header(bearer_header(user))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::post()
+            .uri(&format!("/orders/{}/confirm", shared_order_id))
+            .insert_header(bearer_header(user))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+    }
+
+    // Pending orders must be confirmed before they ship, and shipping
+    // needs a tracking number and a delivery date that is not past
+    let ship = |body: serde_json::Value| test::TestRequest::post()
+        .uri(&format!("/orders/{}/ship", order_id))
+        .insert_header(bearer_header(&supplier))
+        .set_json(body)
+        .to_request();
+    let tomorrow = (chrono::Utc::now() + chrono::Duration::days(1)).date_naive();
+    let resp = test::call_service(&app, ship(json!({ "tracking_number": "TRK-1", "estimated_delivery_date": tomorrow }))).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/orders/{}/confirm", order_id))
+        .insert_header(bearer_header(&admin))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let resp = test::call_service(&app, ship(json!({ "tracking_number": " ", "estimated_delivery_date": "2000-01-01" }))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let fields: Vec<_> = body["details"].as_array().unwrap().iter().map(|d| d["field"].as_str().unwrap()).collect();
+    assert_eq!(fields, vec!["tracking_number", "estimated_delivery_date"]);
+
+    // Confirmed orders can still be cancelled
+    let cancel = OrderTransition::Cancel(CancelOrderRequest {
+        reason: CancellationReason::BuyerRequest,
+        note: Some("Buyer withdrew".into()),
+    });
+    let cancelled = db::orders::transition_order(&pool, order_id, buyer.id, &cancel).unwrap();
+    assert_eq!(cancelled.order.status, OrderStatus::Cancelled);
+    let history = db::orders::order_history(&pool, order_id).unwrap();
+    assert_eq!(history.last().unwrap().note.as_deref(), Some("buyer_request: Buyer withdrew"));
+
+    let req = test::TestRequest::get()
+        .uri("/orders/0")
+        .insert_header(bearer_header(&admin))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::ORDER_NOT_FOUND);
+}
@@ -6,7 +6,6 @@ use rust_market::{
     db::establish_connection_pool,
     test_helpers,
 };
-use std::error::Error;
 
 #[test]
 fn test_performance() {
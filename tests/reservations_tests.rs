@@ -0,0 +1,139 @@
+use actix_web::{test, http::StatusCode};
+use serde_json::json;
+use rust_market::{
+    db::{self, establish_connection_pool},
+    errors::codes,
+    models::UserRole,
+    test_helpers::{
+        self, bearer_header, create_test_category, create_test_equipment, create_test_user, create_verified_buyer,
+        order_request, set_stock_level, shipping_details, stock_level,
+    },
+};
+
+#[actix_web::test]
+async fn test_reserved_stock_is_held_until_checkout() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let first = create_verified_buyer(&pool);
+    let second = create_verified_buyer(&pool);
+    let category = create_test_category(&pool);
+    let loader = create_test_equipment(&pool, category.id, Some(supplier.id));
+    set_stock_level(&pool, loader.id, 1);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let add = |buyer| test::TestRequest::post()
+        .uri("/cart/items")
+        .insert_header(bearer_header(buyer))
+        .set_json(json!({ "equipment_id": loader.id, "quantity": 1 }))
+        .to_request();
+    let available = || test::TestRequest::get().uri(&format!("/equipment/{}", loader.id)).to_request();
+
+    let resp = test::call_service(&app, add(&first)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let req = test::TestRequest::post()
+        .uri("/cart/reserve")
+        .insert_header(bearer_header(&first))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let held: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(held.as_array().unwrap().len(), 1);
+    assert_eq!(held[0]["equipment_id"], loader.id);
+    assert_eq!(held[0]["quantity"], 1);
+
+    let listing: serde_json::Value = test::call_and_read_body_json(&app, available()).await;
+    assert_eq!(listing["stock_level"], 1);
+    assert_eq!(listing["available_quantity"], 0);
+
+    // The holder still sees the stock as theirs
+    let req = test::TestRequest::get().uri("/cart").insert_header(bearer_header(&first)).to_request();
+    let cart: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(cart["items"][0]["available"], true);
+    assert!(cart["items"][0]["reserved_until"].is_string());
+
+    let resp = test::call_service(&app, add(&second)).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::INSUFFICIENT_STOCK);
+    assert!(body["message"].as_str().unwrap().contains("1 is held for other buyers"));
+
+    let req = test::TestRequest::post()
+        .uri("/orders")
+        .insert_header(bearer_header(&second))
+        .set_json(order_request(&[(loader.id, 1)]))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::INSUFFICIENT_STOCK);
+
+    let req = test::TestRequest::post()
+        .uri("/cart/checkout")
+        .insert_header(bearer_header(&first))
+        .set_json(shipping_details())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    assert_eq!(stock_level(&pool, loader.id), 0);
+    assert!(db::reservations::user_reservations(&pool, first.id).unwrap().is_empty());
+    let listing: serde_json::Value = test::call_and_read_body_json(&app, available()).await;
+    assert_eq!(listing["available_quantity"], 0);
+}
+
+#[actix_web::test]
+async fn test_expired_and_released_holds_free_stock() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let buyer = create_verified_buyer(&pool);
+    let category = create_test_category(&pool);
+    let loader = create_test_equipment(&pool, category.id, Some(supplier.id));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/cart/reserve")
+        .insert_header(bearer_header(&buyer))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::CART_EMPTY);
+
+    let req = test::TestRequest::post()
+        .uri("/cart/items")
+        .insert_header(bearer_header(&buyer))
+        .set_json(json!({ "equipment_id": loader.id, "quantity": 2 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // A hold that expires as it is made no longer counts against stock
+    db::reservations::reserve_cart(&pool, buyer.id, chrono::Duration::zero()).unwrap();
+    let req = test::TestRequest::get().uri(&format!("/equipment/{}", loader.id)).to_request();
+    let listing: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(listing["available_quantity"], 3);
+    assert!(db::reservations::release_expired(&pool).unwrap() >= 1);
+    assert!(db::reservations::user_reservations(&pool, buyer.id).unwrap().is_empty());
+
+    let req = test::TestRequest::post()
+        .uri("/cart/reserve")
+        .insert_header(bearer_header(&buyer))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get().uri(&format!("/equipment/{}", loader.id)).to_request();
+    let listing: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(listing["available_quantity"], 1);
+
+    let req = test::TestRequest::delete()
+        .uri("/cart/reserve")
+        .insert_header(bearer_header(&buyer))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+    assert!(db::reservations::user_reservations(&pool, buyer.id).unwrap().is_empty());
+    let req = test::TestRequest::get().uri(&format!("/equipment/{}", loader.id)).to_request();
+    let listing: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(listing["available_quantity"], 3);
+}
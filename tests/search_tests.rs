@@ -0,0 +1,252 @@
+use actix_web::{test, http::StatusCode};
+use rust_market::{
+    db::{equipment, establish_connection_pool, DbPool},
+    errors::codes,
+    models::{CategoryRequest, Equipment, EquipmentChangeset, EquipmentCondition},
+    test_helpers::{self, create_test_category, create_test_equipment},
+};
+
+fn listing(pool: &DbPool, category_id: i32, manufacturer: &str, condition: EquipmentCondition, price: &str, year: i32, stock: i32) -> Equipment {
+    let item = create_test_equipment(pool, category_id, None);
+    equipment::update_equipment(pool, item.id, &EquipmentChangeset {
+        manufacturer: Some(manufacturer.to_string()),
+        condition: Some(condition),
+        price: Some(price.parse().unwrap()),
+        year_manufactured: Some(Some(year)),
+        stock_level: Some(stock),
+        ..Default::default()
+    })
+    .expect("Failed to update test equipment")
+}
+
+fn ids(body: &serde_json::Value) -> Vec<i64> {
+    body["items"].as_array().unwrap().iter().map(|i| i["id"].as_i64().unwrap()).collect()
+}
+
+fn facet(body: &serde_json::Value, name: &str, value: &str) -> i64 {
+    body["facets"][name]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|f| f["value"] == value)
+        .map(|f| f["count"].as_i64().unwrap())
+        .unwrap_or(0)
+}
+
+#[actix_web::test]
+async fn test_search_filters_and_sorts() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let category = create_test_category(&pool);
+    let cat_new = listing(&pool, category.id, "Caterpillar", EquipmentCondition::New, "900000.00", 2024, 1);
+    let cat_used = listing(&pool, category.id, "Caterpillar", EquipmentCondition::Used, "350000.00", 2015, 2);
+    let komatsu = listing(&pool, category.id, "Komatsu", EquipmentCondition::Used, "420000.00", 2019, 0);
+    let liebherr = listing(&pool, category.id, "Liebherr", EquipmentCondition::Refurbished, "150000.00", 2008, 4);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+    let base = format!("/equipment/search?category_id={}", category.id);
+
+    let cases = [
+        ("&sort=price_asc", vec![liebherr.id, cat_used.id, komatsu.id, cat_new.id]),
+        ("&sort=year_desc", vec![cat_new.id, komatsu.id, cat_used.id, liebherr.id]),
+        ("&manufacturer=Caterpillar,Komatsu&sort=price_desc", vec![cat_new.id, komatsu.id, cat_used.id]),
+        ("&condition=used&in_stock=true", vec![cat_used.id]),
+        ("&min_price=200000&max_price=500000&sort=price_asc", vec![cat_used.id, komatsu.id]),
+        ("&min_year=2010&max_year=2020&sort=year_asc", vec![cat_used.id, komatsu.id]),
+        ("&in_stock=false", vec![komatsu.id]),
+    ];
+    for (params, expected) in cases {
+        let req = test::TestRequest::get().uri(&format!("{}{}", base, params)).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{}", params);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        let expected: Vec<i64> = expected.into_iter().map(i64::from).collect();
+        assert_eq!(ids(&body), expected, "{}", params);
+        assert_eq!(body["total"], expected.len(), "{}", params);
+    }
+
+    // Pagination reports the full total
+    let req = test::TestRequest::get()
+        .uri(&format!("{}&sort=price_asc&per_page=2&page=2", base))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(ids(&body), vec![komatsu.id as i64, cat_new.id as i64]);
+    assert_eq!(body["total"], 4);
+    assert_eq!(body["total_pages"], 2);
+}
+
+#[actix_web::test]
+async fn test_search_facet_counts() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let category = create_test_category(&pool);
+    listing(&pool, category.id, "Caterpillar", EquipmentCondition::New, "900000.00", 2024, 1);
+    listing(&pool, category.id, "Caterpillar", EquipmentCondition::Used, "350000.00", 2015, 2);
+    listing(&pool, category.id, "Komatsu", EquipmentCondition::Used, "420000.00", 2019, 1);
+    listing(&pool, category.id, "Liebherr", EquipmentCondition::Refurbished, "150000.00", 2008, 4);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/search?category_id={}&manufacturer=Caterpillar", category.id))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["total"], 2);
+
+    // The manufacturer facet ignores the manufacturer filter itself...
+    assert_eq!(facet(&body, "manufacturer", "Caterpillar"), 2);
+    assert_eq!(facet(&body, "manufacturer", "Komatsu"), 1);
+    assert_eq!(facet(&body, "manufacturer", "Liebherr"), 1);
+    assert_eq!(body["facets"]["manufacturer"][0]["value"], "Caterpillar");
+    // ...but other facets respect it
+    assert_eq!(facet(&body, "condition", "new"), 1);
+    assert_eq!(facet(&body, "condition", "used"), 1);
+    assert_eq!(facet(&body, "condition", "refurbished"), 0);
+}
+
+#[actix_web::test]
+async fn test_search_category_includes_subcategories_by_default() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let parent = create_test_category(&pool);
+    let child = rust_market::db::categories::create_category(&pool, CategoryRequest {
+        name: format!("{} / Haul Trucks", parent.name),
+        description: None,
+        parent_category_id: Some(parent.id),
+    }.into_new_category())
+    .expect("Failed to create subcategory");
+    listing(&pool, parent.id, "Caterpillar", EquipmentCondition::Used, "100000.00", 2015, 1);
+    listing(&pool, child.id, "Caterpillar", EquipmentCondition::Used, "200000.00", 2016, 1);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    for (params, total) in [("", 2), ("&include_subcategories=false", 1)] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/equipment/search?category_id={}{}", parent.id, params))
+            .to_request();
+        let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(body["total"], total, "{}", params);
+    }
+}
+
+#[actix_web::test]
+async fn test_search_rejects_invalid_filters() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    for (params, field) in [
+        ("condition=used,scrap", "condition"),
+        ("min_price=10&max_price=5", "min_price"),
+        ("min_year=2020&max_year=2010", "min_year"),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/equipment/search?{}", params))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", params);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], codes::VALIDATION_FAILED);
+        assert_eq!(body["details"][0]["field"], field, "{}", params);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/equipment/search?sort=cheapest")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::INVALID_QUERY);
+}
+
+fn described(pool: &DbPool, category_id: i32, name: &str, manufacturer: &str, model: &str, description: &str) -> Equipment {
+    let item = create_test_equipment(pool, category_id, None);
+    equipment::update_equipment(pool, item.id, &EquipmentChangeset {
+        name: Some(name.to_string()),
+        manufacturer: Some(manufacturer.to_string()),
+        model_number: Some(model.to_string()),
+        description: Some(Some(description.to_string())),
+        ..Default::default()
+    })
+    .expect("Failed to update test equipment")
+}
+
+#[actix_web::test]
+async fn test_text_search_ranks_exact_model_number_first() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let category = create_test_category(&pool);
+    let text_match = described(&pool, category.id, "CAT haul truck", "Komatsu", "HD785",
+        "Rigid frame haul truck, a cheaper alternative to the 797F");
+    let exact_model = described(&pool, category.id, "Ultra class mining truck", "Caterpillar", "797F",
+        "400 tonne payload");
+    described(&pool, category.id, "Wheel loader", "Volvo", "L350H", "High lift bucket");
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/search?category_id={}&q=CAT%20797F%20haul%20truck", category.id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(ids(&body), vec![exact_model.id as i64, text_match.id as i64]);
+
+    let items = body["items"].as_array().unwrap();
+    assert!(items[0]["rank"].as_f64().unwrap() >= 1.0);
+    let text_rank = items[1]["rank"].as_f64().unwrap();
+    assert!(text_rank > 0.0 && text_rank < 1.0);
+    let snippet = items[1]["snippet"].as_str().unwrap();
+    assert!(snippet.contains("<mark>haul</mark>"), "{}", snippet);
+
+    // Stemmed matches, with an explicit order overriding relevance
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/search?category_id={}&q=trucks&sort=name_asc", category.id))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(ids(&body), vec![text_match.id as i64, exact_model.id as i64]);
+}
+
+#[actix_web::test]
+async fn test_text_search_snippets_escape_html_and_follow_updates() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let category = create_test_category(&pool);
+    let item = described(&pool, category.id, "Electric rope shovel", "P&H", "4100XPC",
+        "<script>alert(1)</script> 60 tonne dipper");
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/search?category_id={}&q=dipper", category.id))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(ids(&body), vec![item.id as i64]);
+    let snippet = body["items"][0]["snippet"].as_str().unwrap();
+    assert!(snippet.contains("<mark>dipper</mark>"), "{}", snippet);
+    assert!(snippet.contains("&lt;script&gt;"), "{}", snippet);
+    assert!(!snippet.contains("<script>"), "{}", snippet);
+
+    // The search document follows edits
+    equipment::update_equipment(&pool, item.id, &EquipmentChangeset {
+        description: Some(Some("Twin boom dragline bucket".to_string())),
+        ..Default::default()
+    })
+    .expect("Failed to update test equipment");
+    for (q, total) in [("dipper", 0), ("dragline", 1)] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/equipment/search?category_id={}&q={}", category.id, q))
+            .to_request();
+        let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(body["total"], total, "q={}", q);
+    }
+
+    // Plain filtering carries no text match fields
+    let req = test::TestRequest::get()
+        .uri(&format!("/equipment/search?category_id={}", category.id))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(body["items"][0].get("rank").is_none());
+    assert!(body["items"][0].get("snippet").is_none());
+}
@@ -0,0 +1,250 @@
+use actix_web::{test, http::StatusCode};
+use serde_json::json;
+use rust_market::{
+    db::{categories, equipment, establish_connection_pool, spec_schemas, DbPool},
+    errors::codes,
+    models::{CategoryRequest, EquipmentCategory, EquipmentChangeset, SpecAttributeRequest, SpecDataType, UserRole},
+    test_helpers::{self, bearer_header, create_test_category, create_test_equipment, create_test_user},
+};
+
+fn haul_truck_schema(pool: &DbPool, category: &EquipmentCategory) {
+    let attributes = vec![
+        SpecAttributeRequest {
+            name: "payload_t".into(),
+            data_type: SpecDataType::Number,
+            unit: Some("t".into()),
+            required: true,
+            allowed_values: None,
+        },
+        SpecAttributeRequest {
+            name: "drive".into(),
+            data_type: SpecDataType::Text,
+            unit: None,
+            required: false,
+            allowed_values: Some(vec![json!("diesel"), json!("electric")]),
+        },
+    ];
+    spec_schemas::replace_spec_schema(
+        pool,
+        category.id,
+        attributes.into_iter().map(|a| a.into_new_attribute(category.id)).collect(),
+    )
+    .expect("Failed to set spec schema");
+}
+
+fn fields(body: &serde_json::Value) -> Vec<&str> {
+    body["details"].as_array().unwrap()
+        .iter()
+        .map(|d| d["field"].as_str().unwrap())
+        .collect()
+}
+
+#[actix_web::test]
+async fn test_admin_defines_schema_and_subcategories_inherit_it() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let admin = create_test_user(&pool, Some(UserRole::Admin));
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let parent = create_test_category(&pool);
+    let child = categories::create_category(&pool, CategoryRequest {
+        name: format!("{} / Electric", parent.name),
+        description: None,
+        parent_category_id: Some(parent.id),
+    }.into_new_category())
+    .expect("Failed to create subcategory");
+    haul_truck_schema(&pool, &parent);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    // The subcategory narrows `drive` and adds an attribute of its own
+    let payload = json!({"attributes": [
+        {"name": "drive", "data_type": "text", "required": true, "allowed_values": ["electric"]},
+        {"name": "battery_kwh", "data_type": "integer", "unit": "kWh"}
+    ]});
+    let req = test::TestRequest::put()
+        .uri(&format!("/categories/{}/spec-schema", child.id))
+        .insert_header(bearer_header(&supplier))
+        .set_json(&payload)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/categories/{}/spec-schema", child.id))
+        .insert_header(bearer_header(&admin))
+        .set_json(&payload)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/categories/{}/spec-schema", child.id))
+        .to_request();
+    let schema: Vec<serde_json::Value> = test::read_body_json(test::call_service(&app, req).await).await;
+    let summary: Vec<(&str, i64, bool)> = schema
+        .iter()
+        .map(|a| (a["name"].as_str().unwrap(), a["category_id"].as_i64().unwrap(), a["required"].as_bool().unwrap()))
+        .collect();
+    assert_eq!(summary, vec![
+        ("battery_kwh", child.id as i64, false),
+        ("drive", child.id as i64, true),
+        ("payload_t", parent.id as i64, true),
+    ]);
+    assert_eq!(schema[2]["unit"], "t");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/categories/{}/spec-schema", i32::MAX))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::CATEGORY_NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_invalid_schema_is_rejected() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let admin = create_test_user(&pool, Some(UserRole::Admin));
+    let category = create_test_category(&pool);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/categories/{}/spec-schema", category.id))
+        .insert_header(bearer_header(&admin))
+        .set_json(json!({"attributes": [
+            {"name": "Payload (tons)", "data_type": "number"},
+            {"name": "axles", "data_type": "integer", "allowed_values": [2, 2.5]},
+            {"name": "axles", "data_type": "boolean"}
+        ]}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(fields(&body), vec!["attributes[0].name", "attributes[1].allowed_values", "attributes[2].name"]);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/categories/{}/spec-schema", category.id))
+        .insert_header(bearer_header(&admin))
+        .set_json(json!({"attributes": [{"name": "payload_t", "data_type": "decimal"}]}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::INVALID_JSON);
+}
+
+#[actix_web::test]
+async fn test_listing_specifications_follow_category_schema() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let category = create_test_category(&pool);
+    haul_truck_schema(&pool, &category);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let listing = |specifications: serde_json::Value| json!({
+        "category_id": category.id,
+        "name": "797F",
+        "manufacturer": "Caterpillar",
+        "model_number": "797F",
+        "condition": "used",
+        "price": "4500000.00",
+        "stock_level": 1,
+        "specifications": specifications
+    });
+
+    let req = test::TestRequest::post()
+        .uri("/equipment")
+        .insert_header(bearer_header(&supplier))
+        .set_json(listing(json!({"Payload (tons)": "400", "drive": "steam"})))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(fields(&body), vec!["specifications.drive", "specifications.payload_t", "specifications.Payload (tons)"]);
+
+    let req = test::TestRequest::post()
+        .uri("/equipment")
+        .insert_header(bearer_header(&supplier))
+        .set_json(listing(json!({"payload_t": "363"})))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(fields(&body), vec!["specifications.payload_t"]);
+
+    let req = test::TestRequest::post()
+        .uri("/equipment")
+        .insert_header(bearer_header(&supplier))
+        .set_json(listing(json!({"payload_t": 363, "drive": "diesel"})))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let created: serde_json::Value = test::read_body_json(resp).await;
+
+    // Partial updates are checked against the schema as well
+    let req = test::TestRequest::patch()
+        .uri(&format!("/equipment/{}", created["id"]))
+        .insert_header(bearer_header(&supplier))
+        .set_json(json!({"specifications": null}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(fields(&body), vec!["specifications.payload_t"]);
+}
+
+#[actix_web::test]
+async fn test_search_filters_on_specifications() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let category = create_test_category(&pool);
+    let with_specs = |specs: serde_json::Value| {
+        let item = create_test_equipment(&pool, category.id, None);
+        equipment::update_equipment(&pool, item.id, &EquipmentChangeset {
+            specifications: Some(Some(specs)),
+            ..Default::default()
+        })
+        .expect("Failed to update test equipment")
+    };
+    let big_diesel = with_specs(json!({"payload_t": 363, "drive": "diesel"}));
+    let big_electric = with_specs(json!({"payload_t": 400, "drive": "electric"}));
+    let small = with_specs(json!({"payload_t": 90.5, "drive": "diesel"}));
+    // Free-form legacy data must not break numeric comparisons
+    let legacy = with_specs(json!({"payload_t": "400 tonnes"}));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let cases = [
+        ("payload_t>=300", vec![big_electric.id, big_diesel.id]),
+        ("payload_t<100", vec![small.id]),
+        ("drive=diesel", vec![small.id, big_diesel.id]),
+        ("payload_t>=300,drive!=diesel", vec![big_electric.id]),
+        ("payload_t=400", vec![big_electric.id]),
+        ("payload_t=400 tonnes", vec![legacy.id]),
+    ];
+    for (spec, expected) in cases {
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/equipment/search?category_id={}&sort=newest&spec={}",
+                category.id,
+                spec.replace('=', "%3D").replace('>', "%3E").replace('<', "%3C").replace(' ', "%20")
+            ))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{}", spec);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        let ids: Vec<i64> = body["items"].as_array().unwrap().iter().map(|i| i["id"].as_i64().unwrap()).collect();
+        let expected: Vec<i64> = expected.into_iter().map(i64::from).collect();
+        assert_eq!(ids, expected, "{}", spec);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/equipment/search?spec=payload_t%3Eheavy")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(fields(&body), vec!["spec"]);
+}
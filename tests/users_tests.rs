@@ -0,0 +1,461 @@
+use actix_web::{test, http::StatusCode};
+use rust_market::{
+    db::{establish_connection_pool, users},
+    errors::{codes, ServiceError},
+    models::{UserChangeset, UserRole},
+    test_helpers::{self, bearer_header, create_test_user},
+};
+
+#[actix_web::test]
+async fn test_get_me_and_own_profile_hide_password_hash() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let user = create_test_user(&pool, Some(UserRole::Buyer));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    for uri in ["/users/me".to_string(), format!("/users/{}", user.id)] {
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(bearer_header(&user))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK, "GET {} should succeed", uri);
+
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["id"], user.id);
+        assert_eq!(body["business_type"], "buyer");
+        assert!(body.get("password_hash").is_none());
+    }
+}
+
+#[actix_web::test]
+async fn test_users_cannot_access_other_accounts() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let user = create_test_user(&pool, Some(UserRole::Buyer));
+    let other = create_test_user(&pool, Some(UserRole::Supplier));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/users/{}", other.id))
+        .insert_header(bearer_header(&user))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/users/{}", other.id))
+        .insert_header(bearer_header(&user))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    // Listing is admin-only
+    let req = test::TestRequest::get()
+        .uri("/users")
+        .insert_header(bearer_header(&user))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_update_own_account() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let user = create_test_user(&pool, Some(UserRole::Buyer));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let payload = serde_json::json!({
+        "username": format!("{}_renamed", user.username),
+        "email": user.email,
+        "password": "new haul truck 798",
+        "business_type": "buyer",
+    });
+    let req = test::TestRequest::put()
+        .uri(&format!("/users/{}", user.id))
+        .insert_header(bearer_header(&user))
+        .set_json(&payload)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["username"], format!("{}_renamed", user.username));
+
+    // Self-promotion to admin is refused. `PUT` sets a password, so only
+    // tokens issued since are accepted.
+    let user = users::get_user_by_id(&pool, user.id).unwrap();
+    let mut escalation = payload.clone();
+    escalation["business_type"] = "admin".into();
+    let req = test::TestRequest::put()
+        .uri(&format!("/users/{}", user.id))
+        .insert_header(bearer_header(&user))
+        .set_json(&escalation)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn test_admin_lists_users_with_filters_and_pagination() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let admin = create_test_user(&pool, Some(UserRole::Admin));
+    let supplier = create_test_user(&pool, Some(UserRole::Supplier));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/users?search={}&business_type=supplier", supplier.username))
+        .insert_header(bearer_header(&admin))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["total"], 1);
+    assert_eq!(body["items"][0]["id"], supplier.id);
+    assert!(body["items"][0].get("password_hash").is_none());
+
+    // Wildcards are searched for literally
+    let wildcarded = supplier.username.replacen('_', "%25", 1);
+    for search in ["%25", "%5C", wildcarded.as_str()] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/users?search={}", search))
+            .insert_header(bearer_header(&admin))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["total"], 0, "search {}", search);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/users?per_page=1&page=1")
+        .insert_header(bearer_header(&admin))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["per_page"], 1);
+    assert_eq!(body["items"].as_array().unwrap().len(), 1);
+}
+
+#[actix_web::test]
+async fn test_delete_own_account() {
+    use diesel::prelude::*;
+    use rust_market::schema::orders;
+
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let user = create_test_user(&pool, Some(UserRole::Buyer));
+
+    // Order history must survive the deletion
+    let conn = &mut pool.get().expect("Failed to get db connection");
+    let order_id: i32 = diesel::insert_into(orders::table)
+        .values((
+            orders::user_id.eq(user.id),
+            orders::status.eq("delivered"),
+            orders::total_amount.eq(bigdecimal::BigDecimal::from(1200)),
+            orders::shipping_address.eq("1 Pit Road"),
+            orders::shipping_method.eq("freight"),
+        ))
+        .returning(orders::id)
+        .get_result(conn)
+        .expect("Failed to create order");
+
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/users/{}", user.id))
+        .insert_header(bearer_header(&user))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+
+    // The token no longer resolves to a user
+    let req = test::TestRequest::get()
+        .uri("/users/me")
+        .insert_header(bearer_header(&user))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(serde_json::json!({ "email": user.email, "password": test_helpers::TEST_PASSWORD }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+    let owner: i32 = orders::table.find(order_id).select(orders::user_id).first(conn)
+        .expect("Order should still exist");
+    assert_eq!(owner, user.id);
+    let error = users::delete_user(&pool, user.id).unwrap_err();
+    assert!(matches!(error.root(), ServiceError::NotFound(_)));
+    assert_eq!(error.code(), codes::USER_NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_admin_lists_and_restores_deactivated_users() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let admin = create_test_user(&pool, Some(UserRole::Admin));
+    let user = create_test_user(&pool, Some(UserRole::Buyer));
+    users::delete_user(&pool, user.id).expect("Delete should succeed");
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/users?search={}", user.username))
+        .insert_header(bearer_header(&admin))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["total"], 0);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/users?search={}&include_deleted=true", user.username))
+        .insert_header(bearer_header(&admin))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["total"], 1);
+    assert!(body["items"][0]["deleted_at"].is_string());
+
+    // Only admins may restore
+    let other = create_test_user(&pool, Some(UserRole::Buyer));
+    let req = test::TestRequest::post()
+        .uri(&format!("/users/{}/restore", user.id))
+        .insert_header(bearer_header(&other))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/users/{}/restore", user.id))
+        .insert_header(bearer_header(&admin))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body.get("deleted_at").is_none());
+
+    let req = test::TestRequest::get()
+        .uri("/users/me")
+        .insert_header(bearer_header(&user))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // Restoring an active account is a conflict
+    let req = test::TestRequest::post()
+        .uri(&format!("/users/{}/restore", user.id))
+        .insert_header(bearer_header(&admin))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+}
+
+#[actix_web::test]
+async fn test_anonymize_own_account() {
+    use diesel::prelude::*;
+    use rust_market::models::User;
+    use rust_market::schema::users as users_table;
+
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let admin = create_test_user(&pool, Some(UserRole::Admin));
+    let user = create_test_user(&pool, Some(UserRole::Buyer));
+    users::update_user(&pool, user.id, &UserChangeset {
+        company_name: Some(Some("Deep Pit Mining".to_string())),
+        contact_number: Some(Some("+61400111222".to_string())),
+        ..Default::default()
+    }).expect("Update should succeed");
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/users/{}/anonymize", user.id))
+        .insert_header(bearer_header(&user))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+
+    let conn = &mut pool.get().expect("Failed to get db connection");
+    let stored: User = users_table::table.find(user.id).first(conn).expect("Row should be kept");
+    assert_eq!(stored.username, format!("deleted_user_{}", user.id));
+    assert_ne!(stored.email, user.email);
+    assert!(stored.company_name.is_none());
+    assert!(stored.contact_number.is_none());
+    assert!(stored.anonymized_at.is_some());
+    assert_eq!(stored.deleted_at, stored.anonymized_at);
+
+    // The original email is free again, and the account cannot come back
+    assert!(users::find_user_by_email(&pool, &user.email).unwrap().is_none());
+    let req = test::TestRequest::post()
+        .uri(&format!("/users/{}/restore", user.id))
+        .insert_header(bearer_header(&admin))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/users/{}/anonymize", user.id))
+        .insert_header(bearer_header(&admin))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_update_user_changeset_only_touches_given_fields() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let user = create_test_user(&pool, Some(UserRole::Buyer));
+
+    let updated = users::update_user(&pool, user.id, &UserChangeset {
+        company_name: Some(Some("Deep Pit Mining".to_string())),
+        contact_number: Some(Some("+61400111222".to_string())),
+        ..Default::default()
+    }).expect("Update should succeed");
+    assert_eq!(updated.company_name.as_deref(), Some("Deep Pit Mining"));
+    assert_eq!(updated.contact_number.as_deref(), Some("+61400111222"));
+    assert_eq!(updated.username, user.username);
+    assert_eq!(updated.password_hash, user.password_hash);
+    assert_eq!(updated.business_type, Some(UserRole::Buyer));
+
+    // Some(None) clears a nullable column, None leaves it alone
+    let cleared = users::update_user(&pool, user.id, &UserChangeset {
+        company_name: Some(None),
+        ..Default::default()
+    }).expect("Update should succeed");
+    assert_eq!(cleared.company_name, None);
+    assert_eq!(cleared.contact_number.as_deref(), Some("+61400111222"));
+}
+
+#[actix_web::test]
+async fn test_update_user_maps_conflict_and_not_found() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let user = create_test_user(&pool, None);
+    let other = create_test_user(&pool, None);
+
+    let taken = users::update_user(&pool, user.id, &UserChangeset {
+        email: Some(other.email.clone()),
+        ..Default::default()
+    });
+    let error = taken.unwrap_err();
+    assert!(matches!(error.root(), ServiceError::Conflict(_)));
+    assert_eq!(error.code(), codes::USER_EMAIL_TAKEN);
+
+    let taken = users::update_user(&pool, user.id, &UserChangeset {
+        username: Some(other.username.clone()),
+        ..Default::default()
+    });
+    assert_eq!(taken.unwrap_err().code(), codes::USER_USERNAME_TAKEN);
+
+    let missing = users::update_user(&pool, i32::MAX, &UserChangeset::default()).unwrap_err();
+    assert!(matches!(missing.root(), ServiceError::NotFound(_)));
+    assert_eq!(missing.code(), codes::USER_NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_patch_user_updates_individual_fields() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let user = create_test_user(&pool, Some(UserRole::Buyer));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/users/{}", user.id))
+        .insert_header(bearer_header(&user))
+        .set_json(serde_json::json!({ "company_name": "Open Cut Ltd", "business_type": "both" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["company_name"], "Open Cut Ltd");
+    assert_eq!(body["business_type"], "both");
+    assert_eq!(body["username"], user.username.as_str());
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/users/{}", user.id))
+        .insert_header(bearer_header(&user))
+        .set_json(serde_json::json!({ "company_name": null }))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(body["company_name"].is_null());
+    assert_eq!(body["business_type"], "both");
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/users/{}", user.id))
+        .insert_header(bearer_header(&user))
+        .set_json(serde_json::json!({ "email": "not-an-email" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn test_password_change_ends_other_sessions() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let user = create_test_user(&pool, Some(UserRole::Buyer));
+    let old_session = bearer_header(&user);
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    // Other changes leave sessions alone
+    let req = test::TestRequest::patch()
+        .uri(&format!("/users/{}", user.id))
+        .insert_header(old_session.clone())
+        .set_json(serde_json::json!({ "company_name": "Open Cut Ltd" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/users/{}", user.id))
+        .insert_header(old_session.clone())
+        .set_json(serde_json::json!({ "password": "new haul truck 798" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get().uri("/users/me").insert_header(old_session).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], codes::AUTH_TOKEN_INVALID);
+
+    let current = users::get_user_by_id(&pool, user.id).unwrap();
+    let req = test::TestRequest::get().uri("/users/me").insert_header(bearer_header(&current)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_admin_patch_missing_user_is_not_found() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let admin = create_test_user(&pool, Some(UserRole::Admin));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/users/{}", i32::MAX))
+        .insert_header(bearer_header(&admin))
+        .set_json(serde_json::json!({ "company_name": "Ghost Mining" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_patch_user_reports_every_invalid_field() {
+    test_helpers::setup();
+    let pool = establish_connection_pool(None)
+        .expect("Failed to create test pool");
+    let user = create_test_user(&pool, Some(UserRole::Buyer));
+    let app = test::init_service(test_helpers::app(&pool)).await;
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/users/{}", user.id))
+        .insert_header(bearer_header(&user))
+        .set_json(serde_json::json!({ "email": "not-an-email", "contact_number": "0400 111 222" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "VALIDATION_FAILED");
+    let fields: Vec<_> = body["details"].as_array().unwrap().iter()
+        .map(|d| d["field"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(fields, ["email", "contact_number"]);
+}